@@ -5,15 +5,33 @@
 //! - CI (other branches): dev-{branch}-{short_sha}
 //! - Local build: dev
 
+#[path = "src/build_version.rs"]
+mod build_version;
+#[path = "src/ico.rs"]
+mod ico;
+#[path = "src/icon.rs"]
+#[allow(dead_code)]
+mod icon;
+#[path = "src/raster.rs"]
+#[allow(dead_code)]
+mod raster;
+
 use std::env;
+use std::path::PathBuf;
 use std::process::Command;
 
+/// Sizes in the application icon (Explorer views, taskbar, Alt+Tab, high DPI)
+const APP_ICON_SIZES: &[u32] = &[16, 20, 24, 32, 40, 48, 64, 128, 256];
+
 fn main() {
     // Tell Cargo to rerun if these change
     println!("cargo:rerun-if-env-changed=CI");
     println!("cargo:rerun-if-env-changed=RELEASE_VERSION");
     println!("cargo:rerun-if-env-changed=GITHUB_REF_NAME");
     println!("cargo:rerun-if-env-changed=GITHUB_SHA");
+    println!("cargo:rerun-if-changed=src/icon.rs");
+    println!("cargo:rerun-if-changed=src/ico.rs");
+    println!("cargo:rerun-if-changed=src/raster.rs");
 
     let version = determine_version();
     println!("cargo:rustc-env=BUILD_VERSION={}", version);
@@ -39,6 +57,13 @@ fn embed_windows_resource(version: &str) {
         .set("FileVersion", version)
         .set("ProductVersion", version);
 
+    match write_app_icon() {
+        Ok(path) => {
+            res.set_icon(&path.to_string_lossy());
+        }
+        Err(e) => eprintln!("Warning: Failed to generate application icon: {}", e),
+    }
+
     // Set numeric version
     res.set_version_info(
         winres::VersionInfo::FILEVERSION,
@@ -54,6 +79,20 @@ fn embed_windows_resource(version: &str) {
     }
 }
 
+/// Draw the shield at every size and write it as an .ico to OUT_DIR
+fn write_app_icon() -> Result<PathBuf, String> {
+    let images: Vec<(u32, Vec<u8>)> = APP_ICON_SIZES
+        .iter()
+        .map(|&size| (size, icon::render(icon::TrayStatus::Active, size)))
+        .collect();
+    let bytes = ico::encode(&images)?;
+
+    let out_dir = env::var("OUT_DIR").map_err(|e| e.to_string())?;
+    let path = PathBuf::from(out_dir).join("app.ico");
+    std::fs::write(&path, bytes).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(path)
+}
+
 fn parse_version_numbers(version: &str) -> (u16, u16, u16, u16) {
     // Try to parse YYYYMMDD-NN format
     if let Some((date, build_num)) = version.split_once('-') {
@@ -90,7 +129,7 @@ fn determine_version() -> String {
         let sha = env::var("GITHUB_SHA").unwrap_or_else(|_| "unknown".to_string());
         let short_sha = &sha[..7.min(sha.len())];
 
-        return format!("dev-{}-{}", sanitize_branch(&branch), short_sha);
+        return build_version::dev_version(Some(&branch), short_sha);
     }
 
     // Local build: try to get git info, fallback to "dev"
@@ -131,23 +170,8 @@ fn get_local_git_version() -> Option<String> {
         .to_string();
 
     if branch == "main" || branch == "master" {
-        Some(format!("dev-{}", short_sha))
+        Some(build_version::dev_version(None, &short_sha))
     } else {
-        Some(format!("dev-{}-{}", sanitize_branch(&branch), short_sha))
+        Some(build_version::dev_version(Some(&branch), &short_sha))
     }
 }
-
-fn sanitize_branch(branch: &str) -> String {
-    branch
-        .chars()
-        .map(|c| {
-            if c.is_alphanumeric() || c == '-' {
-                c
-            } else {
-                '-'
-            }
-        })
-        .collect::<String>()
-        .trim_matches('-')
-        .to_string()
-}
@@ -0,0 +1,101 @@
+//! Audit trail of actions taken on KakaoTalk windows
+
+use std::collections::{HashMap, VecDeque};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::logger;
+use crate::rules::Rule;
+
+/// Maximum number of entries kept in memory
+const MAX_ENTRIES: usize = 200;
+
+/// What was done to a window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AuditAction {
+    /// Resized to 0x0 and sent WM_CLOSE
+    Close,
+    /// Resized to hide an ad area
+    Resize,
+    /// Hidden with SW_HIDE
+    Hide,
+}
+
+/// A single recorded action
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub timestamp: String,
+    pub action: AuditAction,
+    pub hwnd: isize,
+    pub class_name: String,
+    pub rule: Option<Rule>,
+}
+
+/// Bounded list of recent actions, oldest first
+#[derive(Debug, Default)]
+pub struct AuditTrail {
+    entries: VecDeque<AuditEntry>,
+    /// Actions recorded since start, including those no longer kept
+    totals: HashMap<AuditAction, u64>,
+}
+
+impl AuditTrail {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(
+        &mut self,
+        action: AuditAction,
+        hwnd: isize,
+        class_name: &str,
+        rule: Option<Rule>,
+    ) {
+        if self.entries.len() == MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+        *self.totals.entry(action).or_default() += 1;
+        self.entries.push_back(AuditEntry {
+            timestamp: logger::format_timestamp(SystemTime::now()),
+            action,
+            hwnd,
+            class_name: class_name.to_string(),
+            rule,
+        });
+    }
+
+    pub fn entries(&self) -> Vec<AuditEntry> {
+        self.entries.iter().cloned().collect()
+    }
+
+    /// How often `action` was recorded since start
+    pub fn total(&self, action: AuditAction) -> u64 {
+        self.totals.get(&action).copied().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_is_bounded() {
+        let mut trail = AuditTrail::new();
+        for i in 0..MAX_ENTRIES + 5 {
+            trail.record(
+                AuditAction::Hide,
+                i as isize,
+                "EVA_Window",
+                Some(Rule::PopupAd),
+            );
+        }
+        let entries = trail.entries();
+        assert_eq!(entries.len(), MAX_ENTRIES);
+        assert_eq!(entries[0].hwnd, 5);
+        assert_eq!(entries.last().unwrap().rule, Some(Rule::PopupAd));
+        assert_eq!(trail.total(AuditAction::Hide), (MAX_ENTRIES + 5) as u64);
+        assert_eq!(trail.total(AuditAction::Close), 0);
+    }
+}
@@ -0,0 +1,34 @@
+//! Version string formats emitted by the build
+//!
+//! Shared with `build.rs` (via `#[path]`) so the parser in `version.rs` is
+//! tested against the same formatting code that stamps `BUILD_VERSION`.
+//!
+//! - Release: `YYYYMMDD-NN` (passed in as `RELEASE_VERSION` by the release
+//!   workflow)
+//! - Dev on main/master: `dev-{short_sha}`
+//! - Dev on other branches: `dev-{branch}-{short_sha}`
+//! - No git information: `dev`
+
+/// Format a development version
+pub fn dev_version(branch: Option<&str>, short_sha: &str) -> String {
+    match branch {
+        Some(branch) => format!("dev-{}-{}", sanitize_branch(branch), short_sha),
+        None => format!("dev-{}", short_sha),
+    }
+}
+
+/// Make a branch name safe for use in a version string
+pub fn sanitize_branch(branch: &str) -> String {
+    branch
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect::<String>()
+        .trim_matches('-')
+        .to_string()
+}
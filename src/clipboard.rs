@@ -0,0 +1,47 @@
+//! Clipboard helpers
+
+use windows::Win32::{
+    Foundation::{GlobalFree, HANDLE, HWND},
+    System::DataExchange::{CloseClipboard, EmptyClipboard, OpenClipboard, SetClipboardData},
+    System::Memory::{GlobalAlloc, GlobalLock, GlobalUnlock, GMEM_MOVEABLE},
+};
+
+/// Standard clipboard format for UTF-16 text
+const CF_UNICODETEXT: u32 = 13;
+
+/// Put text on the clipboard as CF_UNICODETEXT
+pub fn set_text(owner: HWND, text: &str) -> bool {
+    let wide: Vec<u16> = text.encode_utf16().chain(std::iter::once(0)).collect();
+    let byte_len = wide.len() * std::mem::size_of::<u16>();
+
+    unsafe {
+        if OpenClipboard(owner).is_err() {
+            return false;
+        }
+        let _ = EmptyClipboard();
+
+        let copied = match GlobalAlloc(GMEM_MOVEABLE, byte_len) {
+            Ok(hmem) => {
+                let ptr = GlobalLock(hmem) as *mut u16;
+                if ptr.is_null() {
+                    let _ = GlobalFree(hmem);
+                    false
+                } else {
+                    std::ptr::copy_nonoverlapping(wide.as_ptr(), ptr, wide.len());
+                    let _ = GlobalUnlock(hmem);
+                    // The clipboard owns the memory once SetClipboardData succeeds
+                    if SetClipboardData(CF_UNICODETEXT, HANDLE(hmem.0)).is_ok() {
+                        true
+                    } else {
+                        let _ = GlobalFree(hmem);
+                        false
+                    }
+                }
+            }
+            Err(_) => false,
+        };
+
+        let _ = CloseClipboard();
+        copied
+    }
+}
@@ -0,0 +1,715 @@
+//! Local control API for scripts and dashboards
+//!
+//! Off unless `control_api` is set in the settings. The server only listens on
+//! 127.0.0.1, on a port chosen by the OS, and writes that port together with a
+//! fresh random token to `control.json` in the per-user data directory. Every
+//! request must carry the token, so only the user (who can read the file) is
+//! let in.
+//!
+//! Clients send one JSON request per line and get one JSON response per line:
+//!
+//! ```text
+//! -> {"token": "...", "command": "status"}
+//! <- {"ok": true, "result": {"version": "...", "kakaotalk_pids": [1234], ...}}
+//! -> {"token": "...", "command": "set-category", "category": "popup-ad", "enabled": false}
+//! <- {"ok": true, "result": null}
+//! ```
+//!
+//! Commands: `status`, `pause`, `resume`, `set-category`, `reload-rules` and
+//! `window-tree`. The work is done by a [`Backend`], so the protocol can be
+//! tested without KakaoTalk.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::paths;
+use crate::rules::Rule;
+use crate::window_tree::WindowNode;
+
+const TOKEN_FILE: &str = "control.json";
+
+/// Random bytes in a token
+const TOKEN_BYTES: usize = 32;
+
+/// Longest request line accepted
+const MAX_REQUEST_LEN: u64 = 4096;
+
+/// Idle connections are closed after this long
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Clients served at once; further connections are turned away
+const MAX_CONNECTIONS: usize = 16;
+
+/// Rules that can be switched off; the main window rule only finds windows
+pub const CATEGORIES: [Rule; 6] = [
+    Rule::EmbeddedAd,
+    Rule::PopupAd,
+    Rule::BannerChild,
+    Rule::MainViewArea,
+    Rule::LockScreenArea,
+    Rule::Script,
+];
+
+/// What the blocker is doing
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Status {
+    pub version: String,
+    /// Client processes of every enabled profile
+    pub kakaotalk_pids: Vec<u32>,
+    /// The same processes, with their profile and version
+    pub clients: Vec<Client>,
+    /// Names of the enabled profiles
+    pub profiles: Vec<String>,
+    pub main_windows: Vec<MainWindow>,
+    pub candidates: Vec<Candidate>,
+    pub counters: Counters,
+    pub paused: bool,
+    pub dry_run: bool,
+    pub disabled_categories: Vec<Rule>,
+    /// Scripts that failed to load or in their latest run
+    pub script_errors: Vec<String>,
+    /// What scripts printed in their latest run
+    pub script_output: Vec<String>,
+}
+
+/// A running client process
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Client {
+    pub pid: u32,
+    pub profile: String,
+    /// File version of the executable (`a.b.c.d`), if it could be read
+    pub version: Option<String>,
+    /// Newest version the profile's rules are known to work with, set only
+    /// when the client is newer than that
+    #[serde(default)]
+    pub newer_than_rules: Option<String>,
+}
+
+/// A client main window being watched
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MainWindow {
+    pub hwnd: isize,
+    pub title: String,
+    pub profile: String,
+    /// False once the window is gone but not yet cleaned up
+    pub valid: bool,
+}
+
+/// A window that may show an ad
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Candidate {
+    pub hwnd: isize,
+    pub profile: String,
+    pub blocked: bool,
+}
+
+/// Actions taken since start, and cache size
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Counters {
+    pub closed: u64,
+    pub resized: u64,
+    pub hidden: u64,
+    pub cached_classes: usize,
+}
+
+/// Carries out the commands
+pub trait Backend: Send + Sync {
+    fn status(&self) -> Status;
+    fn set_paused(&self, paused: bool);
+    /// Switch one of the [`CATEGORIES`] on or off
+    fn set_category(&self, category: Rule, enabled: bool);
+    fn reload_rules(&self);
+    fn window_tree(&self) -> Vec<WindowNode>;
+}
+
+#[derive(Debug, Deserialize)]
+struct Request {
+    token: String,
+    #[serde(flatten)]
+    command: Command,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+enum Command {
+    Status,
+    Pause,
+    Resume,
+    SetCategory { category: Rule, enabled: bool },
+    ReloadRules,
+    WindowTree,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Response {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Response {
+    fn ok(result: impl Serialize) -> Self {
+        match serde_json::to_value(result) {
+            Ok(value) => Self {
+                ok: true,
+                result: Some(value),
+                error: None,
+            },
+            Err(e) => Self::error(format!("Failed to encode result: {}", e)),
+        }
+    }
+
+    fn error(message: impl Into<String>) -> Self {
+        Self {
+            ok: false,
+            result: None,
+            error: Some(message.into()),
+        }
+    }
+}
+
+/// Answer one request line
+pub fn handle(backend: &dyn Backend, token: &str, line: &str) -> Response {
+    let request: Request = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(e) => return Response::error(format!("Invalid request: {}", e)),
+    };
+    if !tokens_match(&request.token, token) {
+        return Response::error("Invalid token");
+    }
+
+    match request.command {
+        Command::Status => Response::ok(backend.status()),
+        Command::Pause | Command::Resume => {
+            backend.set_paused(matches!(request.command, Command::Pause));
+            Response::ok(())
+        }
+        Command::SetCategory { category, enabled } => {
+            if !CATEGORIES.contains(&category) {
+                return Response::error(format!("Not a category: {}", category.name()));
+            }
+            backend.set_category(category, enabled);
+            Response::ok(())
+        }
+        Command::ReloadRules => {
+            backend.reload_rules();
+            Response::ok(())
+        }
+        Command::WindowTree => Response::ok(backend.window_tree()),
+    }
+}
+
+/// Compare without revealing through timing how much of the token matched
+fn tokens_match(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// A new random token
+pub fn generate_token() -> Result<String, String> {
+    let mut bytes = [0u8; TOKEN_BYTES];
+    getrandom::getrandom(&mut bytes).map_err(|e| format!("Failed to generate token: {}", e))?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Contents of the token file
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct TokenFile {
+    pub port: u16,
+    pub token: String,
+}
+
+/// Location of the token file
+pub fn token_file_path() -> PathBuf {
+    paths::local_data_dir().join(TOKEN_FILE)
+}
+
+pub fn write_token_file(path: &Path, contents: &TokenFile) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    let json = serde_json::to_string_pretty(contents)
+        .map_err(|e| format!("Failed to encode token file: {}", e))?;
+    std::fs::write(path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Listen on a free loopback port and serve clients on background threads;
+/// returns the port
+pub fn start(backend: Arc<dyn Backend>, token: String) -> Result<u16, String> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+        .map_err(|e| format!("Failed to start control API: {}", e))?;
+    let port = listener
+        .local_addr()
+        .map_err(|e| format!("Failed to start control API: {}", e))?
+        .port();
+
+    let token = Arc::new(token);
+    let open = Arc::new(AtomicUsize::new(0));
+    std::thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let Some(slot) = ConnectionSlot::take(&open) else {
+                let _ = writeln!(stream, "{}", too_many_connections());
+                continue;
+            };
+            let backend = Arc::clone(&backend);
+            let token = Arc::clone(&token);
+            std::thread::spawn(move || {
+                serve(stream, backend.as_ref(), &token);
+                drop(slot);
+            });
+        }
+    });
+    Ok(port)
+}
+
+/// One of the [`MAX_CONNECTIONS`] places, given back when dropped
+struct ConnectionSlot(Arc<AtomicUsize>);
+
+impl ConnectionSlot {
+    fn take(open: &Arc<AtomicUsize>) -> Option<Self> {
+        open.fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+            (n < MAX_CONNECTIONS).then_some(n + 1)
+        })
+        .ok()
+        .map(|_| Self(Arc::clone(open)))
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// The line sent to a client that is turned away
+fn too_many_connections() -> String {
+    serde_json::to_string(&Response::error("Too many connections")).unwrap_or_default()
+}
+
+/// Answer requests until the client disconnects or fails to authenticate
+fn serve(stream: TcpStream, backend: &dyn Backend, token: &str) {
+    let _ = stream.set_read_timeout(Some(IDLE_TIMEOUT));
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    let mut reader = BufReader::new(stream);
+
+    loop {
+        let mut line = String::new();
+        match (&mut reader).take(MAX_REQUEST_LEN).read_line(&mut line) {
+            Ok(0) | Err(_) => return,
+            Ok(_) => {}
+        }
+        let response = if line.ends_with('\n') {
+            handle(backend, token, line.trim_end())
+        } else {
+            // Skip the rest of the line in bounded chunks
+            while !line.ends_with('\n') {
+                line.clear();
+                match (&mut reader).take(MAX_REQUEST_LEN).read_line(&mut line) {
+                    Ok(0) | Err(_) => return,
+                    Ok(_) => {}
+                }
+            }
+            Response::error("Request too long")
+        };
+
+        let Ok(mut json) = serde_json::to_string(&response) else {
+            return;
+        };
+        json.push('\n');
+        // Stop talking to clients that fail to authenticate
+        let rejected = response.error.as_deref() == Some("Invalid token");
+        if writer.write_all(json.as_bytes()).is_err() || rejected {
+            return;
+        }
+    }
+}
+
+/// The blocker's shared state as a [`Backend`]
+#[cfg(windows)]
+pub struct LiveBackend {
+    pub state: Arc<parking_lot::Mutex<crate::AdBlockState>>,
+}
+
+#[cfg(windows)]
+impl Backend for LiveBackend {
+    fn status(&self) -> Status {
+        Status::capture(&self.state)
+    }
+
+    fn set_paused(&self, paused: bool) {
+        self.state.lock().paused = paused;
+        crate::logger::log(if paused {
+            "Blocking paused"
+        } else {
+            "Blocking resumed"
+        });
+    }
+
+    fn set_category(&self, category: Rule, enabled: bool) {
+        let mut state = self.state.lock();
+        if enabled {
+            state.disabled_rules.remove(&category);
+        } else {
+            state.disabled_rules.insert(category);
+        }
+    }
+
+    fn reload_rules(&self) {
+        self.state.lock().forget_decisions();
+    }
+
+    fn window_tree(&self) -> Vec<WindowNode> {
+        let targets = self.state.lock().target_rules();
+        crate::window_tree::capture(&targets)
+    }
+}
+
+#[cfg(windows)]
+impl Status {
+    /// What the blocker knows right now
+    pub fn capture(state: &parking_lot::Mutex<crate::AdBlockState>) -> Self {
+        use crate::audit::AuditAction;
+        use crate::window;
+        use windows::Win32::Foundation::HWND;
+
+        let state = state.lock();
+        let profile_of = |hwnd: isize| {
+            state
+                .window_targets
+                .get(&hwnd)
+                .map(|t| state.profiles[t.profile].name.clone())
+                .unwrap_or_default()
+        };
+
+        let mut main_windows: Vec<MainWindow> = state
+            .main_windows
+            .iter()
+            .map(|&hwnd| {
+                let valid = window::is_window_valid(HWND(hwnd as *mut _));
+                MainWindow {
+                    hwnd,
+                    title: if valid {
+                        window::get_window_text(HWND(hwnd as *mut _))
+                    } else {
+                        String::new()
+                    },
+                    profile: profile_of(hwnd),
+                    valid,
+                }
+            })
+            .collect();
+        main_windows.sort_by_key(|w| w.hwnd);
+        let mut candidates: Vec<Candidate> = state
+            .ad_candidates
+            .iter()
+            .map(|(&hwnd, &blocked)| Candidate {
+                hwnd,
+                profile: profile_of(hwnd),
+                blocked,
+            })
+            .collect();
+        candidates.sort_by_key(|c| c.hwnd);
+        let mut disabled_categories: Vec<Rule> = state.disabled_rules.iter().copied().collect();
+        disabled_categories.sort_by_key(|rule| rule.name());
+
+        Status {
+            version: crate::VERSION.to_string(),
+            kakaotalk_pids: state.targets.iter().map(|t| t.pid).collect(),
+            clients: state
+                .targets
+                .iter()
+                .map(|t| {
+                    let profile = &state.profiles[t.profile];
+                    Client {
+                        pid: t.pid,
+                        profile: profile.name.clone(),
+                        version: t.version.map(|v| v.to_string()),
+                        newer_than_rules: t
+                            .version
+                            .filter(|v| profile.is_newer_than_known(*v))
+                            .and(profile.newest_known_version())
+                            .map(|v| v.to_string()),
+                    }
+                })
+                .collect(),
+            profiles: state.profiles.iter().map(|p| p.name.clone()).collect(),
+            main_windows,
+            candidates,
+            counters: Counters {
+                closed: state.audit.total(AuditAction::Close),
+                resized: state.audit.total(AuditAction::Resize),
+                hidden: state.audit.total(AuditAction::Hide),
+                cached_classes: state.window_class_cache.len(),
+            },
+            paused: state.paused,
+            dry_run: state.dry_run,
+            disabled_categories,
+            script_errors: state.script_errors.clone(),
+            script_output: state.script_output.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+    use crate::window_tree::NodeRect;
+    use parking_lot::Mutex;
+
+    /// Records what it was asked to do
+    #[derive(Default)]
+    struct FakeBackend {
+        paused: Mutex<bool>,
+        disabled: Mutex<Vec<Rule>>,
+        reloads: Mutex<u32>,
+    }
+
+    impl Backend for FakeBackend {
+        fn status(&self) -> Status {
+            Status {
+                version: "test".to_string(),
+                kakaotalk_pids: vec![1234],
+                clients: vec![Client {
+                    pid: 1234,
+                    profile: "kakaotalk".to_string(),
+                    version: Some("25.8.2.4567".to_string()),
+                    newer_than_rules: Some("25.5.0.0".to_string()),
+                }],
+                profiles: vec!["kakaotalk".to_string()],
+                main_windows: vec![MainWindow {
+                    hwnd: 0x100,
+                    title: "KakaoTalk".to_string(),
+                    profile: "kakaotalk".to_string(),
+                    valid: true,
+                }],
+                candidates: vec![Candidate {
+                    hwnd: 0x200,
+                    profile: "kakaotalk".to_string(),
+                    blocked: true,
+                }],
+                counters: Counters {
+                    hidden: 1,
+                    ..Default::default()
+                },
+                paused: *self.paused.lock(),
+                dry_run: false,
+                disabled_categories: self.disabled.lock().clone(),
+                script_errors: Vec::new(),
+                script_output: vec!["banner.rhai: checked".to_string()],
+            }
+        }
+
+        fn set_paused(&self, paused: bool) {
+            *self.paused.lock() = paused;
+        }
+
+        fn set_category(&self, category: Rule, enabled: bool) {
+            let mut disabled = self.disabled.lock();
+            disabled.retain(|&rule| rule != category);
+            if !enabled {
+                disabled.push(category);
+            }
+        }
+
+        fn reload_rules(&self) {
+            *self.reloads.lock() += 1;
+        }
+
+        fn window_tree(&self) -> Vec<WindowNode> {
+            vec![WindowNode {
+                hwnd: 0x100,
+                class_name: "EVA_Window_Dblclk".to_string(),
+                text: "KakaoTalk".to_string(),
+                rect: NodeRect::default(),
+                visible: true,
+                rule: Some(Rule::MainWindow),
+                children: Vec::new(),
+            }]
+        }
+    }
+
+    struct TestClient {
+        reader: BufReader<TcpStream>,
+        writer: TcpStream,
+    }
+
+    impl TestClient {
+        fn connect(port: u16) -> Self {
+            let writer = TcpStream::connect((Ipv4Addr::LOCALHOST, port)).unwrap();
+            Self {
+                reader: BufReader::new(writer.try_clone().unwrap()),
+                writer,
+            }
+        }
+
+        fn request(&mut self, request: serde_json::Value) -> Response {
+            self.send_line(&request.to_string())
+        }
+
+        fn send_line(&mut self, line: &str) -> Response {
+            writeln!(self.writer, "{}", line).unwrap();
+            self.response()
+        }
+
+        fn response(&mut self) -> Response {
+            let mut response = String::new();
+            self.reader.read_line(&mut response).unwrap();
+            serde_json::from_str(&response).unwrap()
+        }
+
+        /// Whether the server closes the connection without saying more
+        fn closed_by_server(mut self) -> bool {
+            let mut rest = Vec::new();
+            matches!(self.reader.read_to_end(&mut rest), Ok(0))
+        }
+    }
+
+    #[test]
+    fn test_end_to_end() {
+        let backend = Arc::new(FakeBackend::default());
+        let token = generate_token().unwrap();
+        let port = start(backend.clone(), token.clone()).unwrap();
+
+        // Several requests over one connection
+        let mut client = TestClient::connect(port);
+        let response = client.request(serde_json::json!({"token": token, "command": "status"}));
+        assert!(response.ok, "{:?}", response);
+        let status: Status = serde_json::from_value(response.result.unwrap()).unwrap();
+        assert_eq!(status, backend.status());
+        assert_eq!(status.kakaotalk_pids, vec![1234]);
+
+        let response = client.request(serde_json::json!({"token": token, "command": "pause"}));
+        assert!(response.ok);
+        assert!(*backend.paused.lock());
+        client.request(serde_json::json!({"token": token, "command": "resume"}));
+        assert!(!*backend.paused.lock());
+
+        let response = client.request(serde_json::json!({
+            "token": token,
+            "command": "set-category",
+            "category": "popup-ad",
+            "enabled": false
+        }));
+        assert!(response.ok);
+        assert_eq!(*backend.disabled.lock(), vec![Rule::PopupAd]);
+        let response = client.request(serde_json::json!({
+            "token": token,
+            "command": "set-category",
+            "category": "main-window",
+            "enabled": false
+        }));
+        assert_eq!(
+            response.error.as_deref(),
+            Some("Not a category: main-window")
+        );
+
+        client.request(serde_json::json!({"token": token, "command": "reload-rules"}));
+        assert_eq!(*backend.reloads.lock(), 1);
+
+        let response =
+            client.request(serde_json::json!({"token": token, "command": "window-tree"}));
+        let tree: Vec<WindowNode> = serde_json::from_value(response.result.unwrap()).unwrap();
+        assert_eq!(tree, backend.window_tree());
+
+        // Malformed requests are answered without closing the connection
+        let response = client.send_line("{\"token\": ");
+        assert!(response.error.unwrap().starts_with("Invalid request"));
+        let response = client.request(serde_json::json!({"token": token, "command": "format"}));
+        assert!(!response.ok);
+        assert!(
+            client
+                .request(serde_json::json!({"token": token, "command": "status"}))
+                .ok
+        );
+    }
+
+    #[test]
+    fn test_rejects_wrong_token() {
+        let backend = Arc::new(FakeBackend::default());
+        let port = start(backend.clone(), generate_token().unwrap()).unwrap();
+
+        let mut client = TestClient::connect(port);
+        let response = client.request(serde_json::json!({"token": "guess", "command": "pause"}));
+        assert_eq!(response.error.as_deref(), Some("Invalid token"));
+        assert!(!*backend.paused.lock());
+        assert!(client.closed_by_server());
+
+        let mut client = TestClient::connect(port);
+        let response = client.request(serde_json::json!({"command": "status"}));
+        assert!(!response.ok);
+
+        // Oversized requests are skipped
+        let mut client = TestClient::connect(port);
+        let response = client.send_line(&"x".repeat(MAX_REQUEST_LEN as usize * 3));
+        assert_eq!(response.error.as_deref(), Some("Request too long"));
+        let response = client.request(serde_json::json!({"token": "guess", "command": "status"}));
+        assert_eq!(response.error.as_deref(), Some("Invalid token"));
+    }
+
+    #[test]
+    fn test_connection_limit() {
+        let backend = Arc::new(FakeBackend::default());
+        let token = generate_token().unwrap();
+        let port = start(backend, token.clone()).unwrap();
+        let status = serde_json::json!({"token": token, "command": "status"});
+
+        let mut clients: Vec<_> = (0..MAX_CONNECTIONS)
+            .map(|_| TestClient::connect(port))
+            .collect();
+        let mut extra = TestClient::connect(port);
+        assert_eq!(
+            extra.response().error.as_deref(),
+            Some("Too many connections")
+        );
+        assert!(extra.closed_by_server());
+        assert!(clients[0].request(status.clone()).ok);
+
+        // A closed connection frees its place
+        clients.pop();
+        let mut client = loop {
+            // Turned away clients are told so straight away
+            let mut client = TestClient::connect(port);
+            let timeout = Some(Duration::from_millis(100));
+            client.writer.set_read_timeout(timeout).unwrap();
+            if client.reader.fill_buf().is_err() {
+                client.writer.set_read_timeout(None).unwrap();
+                break client;
+            }
+        };
+        assert!(client.request(status).ok);
+    }
+
+    #[test]
+    fn test_tokens() {
+        let token = generate_token().unwrap();
+        assert_eq!(token.len(), TOKEN_BYTES * 2);
+        assert_ne!(token, generate_token().unwrap());
+        assert!(tokens_match(&token, &token.clone()));
+        assert!(!tokens_match(&token, &token[1..]));
+        assert!(!tokens_match("abc", "abd"));
+
+        let dir = TestDir::new("control_token");
+        let path = dir.join("data").join(TOKEN_FILE);
+        let contents = TokenFile { port: 4242, token };
+        write_token_file(&path, &contents).unwrap();
+        let read: TokenFile =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(read, contents);
+    }
+}
@@ -1,7 +1,8 @@
 //! Debug window - semi-transparent, draggable, topmost overlay with scrolling
 
+use once_cell::sync::Lazy;
 use parking_lot::Mutex;
-use std::sync::atomic::{AtomicBool, AtomicI32, AtomicPtr, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicPtr, AtomicUsize, Ordering};
 use std::sync::Arc;
 use windows::{
     core::PCWSTR,
@@ -14,11 +15,17 @@ use windows::{
         OUT_DEFAULT_PRECIS, PAINTSTRUCT, SRCCOPY, TRANSPARENT,
     },
     Win32::System::LibraryLoader::GetModuleHandleW,
-    Win32::UI::Input::KeyboardAndMouse::ReleaseCapture,
+    Win32::UI::Input::KeyboardAndMouse::{
+        GetKeyState, ReleaseCapture, VIRTUAL_KEY, VK_BACK, VK_CONTROL, VK_DOWN, VK_END, VK_ESCAPE,
+        VK_HOME, VK_LEFT, VK_NEXT, VK_PRIOR, VK_RETURN, VK_RIGHT, VK_TAB, VK_UP,
+    },
     Win32::UI::WindowsAndMessaging::*,
 };
 
-use crate::{process, window, AdBlockState};
+use crate::control::Status;
+use crate::update::Release;
+use crate::window_tree::{self, NavKey, TreeInspector};
+use crate::{clipboard, release_notes, AdBlockState};
 
 const DEBUG_WINDOW_CLASS: &str = "KakaoTalkAdBlockDebug";
 const WINDOW_WIDTH: i32 = 320;
@@ -27,9 +34,17 @@ const BG_COLOR: u32 = 0x2D2D2D; // Dark gray
 const TEXT_COLOR: u32 = 0x00FF00; // Green (BGR format for Windows)
 const TITLE_COLOR: u32 = 0x00D4FA; // Yellow/gold
 const PROCESSED_COLOR: u32 = 0x808080; // Gray for processed items
+const SELECTED_BG_COLOR: u32 = 0x505050; // Selected tree row
 const UPDATE_TIMER_ID: usize = 1;
 const UPDATE_INTERVAL_MS: u32 = 500;
 const LINE_HEIGHT: i32 = 16;
+const TEXT_TOP: i32 = 10;
+// Tree view layout: title + filter above the rows, separator + details + hint below
+const TREE_HEADER_LINES: i32 = 2;
+const TREE_DETAIL_LINES: usize = 6;
+const TREE_FOOTER_LINES: i32 = TREE_DETAIL_LINES as i32 + 2;
+// Release notes are wrapped to fit the window width
+const NOTES_COLUMNS: usize = 40;
 
 // Global state for the debug window
 static DEBUG_HWND: AtomicPtr<std::ffi::c_void> = AtomicPtr::new(std::ptr::null_mut());
@@ -38,6 +53,12 @@ static SCROLL_OFFSET: AtomicI32 = AtomicI32::new(0);
 static mut DEBUG_STATE: Option<Arc<Mutex<AdBlockState>>> = None;
 static mut DEBUG_FONT: Option<HFONT> = None;
 static mut TOTAL_LINES: i32 = 0;
+// Window tree inspector state
+static TREE_MODE: AtomicBool = AtomicBool::new(false);
+static TREE_TOP: AtomicUsize = AtomicUsize::new(0);
+static INSPECTOR: Lazy<Mutex<TreeInspector>> = Lazy::new(|| Mutex::new(TreeInspector::new()));
+// Release notes shown instead of the summary while set
+static RELEASE_NOTES: Lazy<Mutex<Option<Vec<String>>>> = Lazy::new(|| Mutex::new(None));
 
 /// Initialize the debug window (call once at startup)
 pub fn init(state: Arc<Mutex<AdBlockState>>) {
@@ -81,8 +102,17 @@ pub fn show() {
     }
 }
 
+/// Show release notes in the debug window (until closed, Tab or Esc)
+pub fn show_release_notes(release: &Release) {
+    *RELEASE_NOTES.lock() = Some(release_notes::preview_lines(release, NOTES_COLUMNS));
+    TREE_MODE.store(false, Ordering::Relaxed);
+    SCROLL_OFFSET.store(0, Ordering::Relaxed);
+    show();
+}
+
 /// Hide the debug window
 pub fn hide() {
+    *RELEASE_NOTES.lock() = None;
     let hwnd = HWND(DEBUG_HWND.load(Ordering::Relaxed));
     if !hwnd.0.is_null() {
         unsafe {
@@ -209,16 +239,41 @@ unsafe extern "system" fn window_proc(
                 SelectObject(mem_dc, font);
             }
 
-            // Get debug info and draw
-            let (info, line_colors) = get_debug_info_with_colors();
-            let lines: Vec<&str> = info.lines().collect();
-
-            TOTAL_LINES = lines.len() as i32;
-            let scroll = SCROLL_OFFSET.load(Ordering::Relaxed);
-
-            let mut y = 10 - scroll;
+            // Get debug info (or the window tree) and draw
+            let tree_mode = TREE_MODE.load(Ordering::Relaxed);
+            let (lines, line_colors, highlight) = if tree_mode {
+                get_tree_view_with_colors(tree_rows_visible(rect.bottom))
+            } else if let Some((lines, colors)) = get_release_notes_with_colors() {
+                TOTAL_LINES = lines.len() as i32;
+                (lines, colors, None)
+            } else {
+                let (info, colors) = get_debug_info_with_colors();
+                let lines: Vec<String> = info.lines().map(String::from).collect();
+                TOTAL_LINES = lines.len() as i32;
+                (lines, colors, None)
+            };
+
+            let scroll = if tree_mode {
+                0
+            } else {
+                SCROLL_OFFSET.load(Ordering::Relaxed)
+            };
+
+            let mut y = TEXT_TOP - scroll;
             for (i, line) in lines.iter().enumerate() {
                 if y + LINE_HEIGHT > 0 && y < rect.bottom {
+                    if highlight == Some(i) {
+                        let row_rect = RECT {
+                            left: 4,
+                            top: y,
+                            right: rect.right - 4,
+                            bottom: y + LINE_HEIGHT,
+                        };
+                        let brush = CreateSolidBrush(COLORREF(SELECTED_BG_COLOR));
+                        FillRect(mem_dc, &row_rect, brush);
+                        let _ = DeleteObject(brush);
+                    }
+
                     let color = line_colors.get(i).copied().unwrap_or(TEXT_COLOR);
                     SetTextColor(mem_dc, COLORREF(color));
 
@@ -252,6 +307,9 @@ unsafe extern "system" fn window_proc(
         }
         WM_TIMER => {
             if wparam.0 == UPDATE_TIMER_ID {
+                if TREE_MODE.load(Ordering::Relaxed) {
+                    refresh_tree();
+                }
                 // Redraw with updated info (no flicker due to double buffering)
                 let _ = InvalidateRect(hwnd, None, false);
             }
@@ -260,14 +318,105 @@ unsafe extern "system" fn window_proc(
         WM_MOUSEWHEEL => {
             // Handle scroll
             let delta = (wparam.0 >> 16) as i16;
-            let current = SCROLL_OFFSET.load(Ordering::Relaxed);
-            let max_scroll = (TOTAL_LINES * LINE_HEIGHT - WINDOW_HEIGHT + 40).max(0);
-            let new_scroll = (current - (delta as i32 / 4)).clamp(0, max_scroll);
-            SCROLL_OFFSET.store(new_scroll, Ordering::Relaxed);
+            if TREE_MODE.load(Ordering::Relaxed) {
+                let key = if delta > 0 { NavKey::Up } else { NavKey::Down };
+                let mut inspector = INSPECTOR.lock();
+                for _ in 0..3 {
+                    inspector.handle_key(key);
+                }
+            } else {
+                let current = SCROLL_OFFSET.load(Ordering::Relaxed);
+                let max_scroll = (TOTAL_LINES * LINE_HEIGHT - WINDOW_HEIGHT + 40).max(0);
+                let new_scroll = (current - (delta as i32 / 4)).clamp(0, max_scroll);
+                SCROLL_OFFSET.store(new_scroll, Ordering::Relaxed);
+            }
             let _ = InvalidateRect(hwnd, None, false);
             LRESULT(0)
         }
+        WM_KEYDOWN => {
+            let vk = VIRTUAL_KEY(wparam.0 as u16);
+            if (vk == VK_TAB || vk == VK_ESCAPE) && RELEASE_NOTES.lock().take().is_some() {
+                // Leave the release notes for the summary
+                SCROLL_OFFSET.store(0, Ordering::Relaxed);
+                let _ = InvalidateRect(hwnd, None, false);
+                return LRESULT(0);
+            }
+            if vk == VK_TAB {
+                // Switch between the summary and the window tree
+                let tree_mode = !TREE_MODE.load(Ordering::Relaxed);
+                TREE_MODE.store(tree_mode, Ordering::Relaxed);
+                if tree_mode {
+                    refresh_tree();
+                }
+                let _ = InvalidateRect(hwnd, None, false);
+                return LRESULT(0);
+            }
+
+            if !TREE_MODE.load(Ordering::Relaxed) {
+                return DefWindowProcW(hwnd, msg, wparam, lparam);
+            }
+
+            let ctrl_down = GetKeyState(VK_CONTROL.0 as i32) < 0;
+            if ctrl_down && vk.0 == b'C' as u16 {
+                let text = INSPECTOR.lock().copy_text();
+                if let Some(text) = text {
+                    clipboard::set_text(hwnd, &text);
+                }
+                return LRESULT(0);
+            }
+
+            let key = match vk {
+                VK_UP => Some(NavKey::Up),
+                VK_DOWN => Some(NavKey::Down),
+                VK_PRIOR => Some(NavKey::PageUp),
+                VK_NEXT => Some(NavKey::PageDown),
+                VK_HOME => Some(NavKey::Home),
+                VK_END => Some(NavKey::End),
+                VK_RIGHT => Some(NavKey::Right),
+                VK_LEFT => Some(NavKey::Left),
+                VK_RETURN => Some(NavKey::Toggle),
+                VK_BACK => Some(NavKey::Backspace),
+                VK_ESCAPE => Some(NavKey::Escape),
+                _ => None,
+            };
+            if let Some(key) = key {
+                if INSPECTOR.lock().handle_key(key) {
+                    let _ = InvalidateRect(hwnd, None, false);
+                }
+            }
+            LRESULT(0)
+        }
+        WM_CHAR => {
+            // Printable characters go to the tree filter (control chars are ignored)
+            if TREE_MODE.load(Ordering::Relaxed) {
+                if let Some(c) = char::from_u32(wparam.0 as u32) {
+                    if INSPECTOR.lock().handle_key(NavKey::Char(c)) {
+                        let _ = InvalidateRect(hwnd, None, false);
+                    }
+                }
+            }
+            LRESULT(0)
+        }
         WM_LBUTTONDOWN => {
+            // In tree mode, clicking a row selects it
+            if TREE_MODE.load(Ordering::Relaxed) {
+                let _ = SetForegroundWindow(hwnd);
+
+                let y = ((lparam.0 >> 16) & 0xFFFF) as i16 as i32;
+                let rows_top = TEXT_TOP + TREE_HEADER_LINES * LINE_HEIGHT;
+                let mut rect = RECT::default();
+                GetClientRect(hwnd, &mut rect).ok();
+                let visible = tree_rows_visible(rect.bottom);
+
+                if y >= rows_top && y < rows_top + visible as i32 * LINE_HEIGHT {
+                    let index =
+                        TREE_TOP.load(Ordering::Relaxed) + ((y - rows_top) / LINE_HEIGHT) as usize;
+                    INSPECTOR.lock().select_row(index);
+                    let _ = InvalidateRect(hwnd, None, false);
+                    return LRESULT(0);
+                }
+            }
+
             // Allow dragging the window
             let _ = ReleaseCapture();
             SendMessageW(
@@ -297,91 +446,267 @@ fn get_debug_info_with_colors() -> (String, Vec<u32>) {
     info.push_str("━━━━━━━━━━━━━━━━━━━━━━━\n");
     colors.push(TITLE_COLOR);
 
-    let pids = process::find_process_ids(process::KAKAOTALK_EXE);
+    let status = unsafe {
+        match DEBUG_STATE {
+            Some(ref state) => Status::capture(state),
+            None => return (info, colors),
+        }
+    };
 
-    if pids.is_empty() {
-        info.push_str("\n");
+    if status.kakaotalk_pids.is_empty() {
+        info.push('\n');
         colors.push(TEXT_COLOR);
         info.push_str("[!] KakaoTalk not running\n");
         colors.push(0x0000FF); // Red for warning
     } else {
-        info.push_str(&format!("\nPIDs: {:?}\n", pids));
-        colors.push(TEXT_COLOR);
+        info.push('\n');
         colors.push(TEXT_COLOR);
-    }
-
-    unsafe {
-        if let Some(ref state_arc) = DEBUG_STATE {
-            let state = state_arc.lock();
-
-            info.push_str(&format!("\nMain Windows: {}\n", state.main_windows.len()));
-            colors.push(TEXT_COLOR);
+        for client in &status.clients {
+            info.push_str(&format!(
+                "PID {}: {} {}\n",
+                client.pid,
+                client.profile,
+                client.version.as_deref().unwrap_or("(version unknown)")
+            ));
             colors.push(TEXT_COLOR);
-
-            for &hwnd_key in &state.main_windows {
-                let hwnd = HWND(hwnd_key as *mut _);
-                let valid = window::is_window_valid(hwnd);
-                let title = if valid {
-                    window::get_window_text(hwnd)
-                } else {
-                    String::new()
-                };
-                let status = if !valid {
-                    " [INVALID]"
-                } else if title.is_empty() {
-                    " (empty)"
-                } else {
-                    ""
-                };
-                let title_display = if title.is_empty() {
-                    String::new()
-                } else {
-                    format!(" {}", title)
-                };
+            if let Some(newest) = &client.newer_than_rules {
                 info.push_str(&format!(
-                    "  0x{:08X}{}{}\n",
-                    hwnd_key, title_display, status
+                    "[!] Newer than any known rule set (up to {}); ads may not be blocked\n",
+                    newest
                 ));
-                colors.push(if !valid { PROCESSED_COLOR } else { TEXT_COLOR });
+                colors.push(0x0000FF); // Red for warning
             }
+        }
+    }
 
-            let processed_count = state.ad_candidates.values().filter(|&&v| v).count();
-            info.push_str(&format!(
-                "\nAd Candidates: {} ({} blocked)\n",
-                state.ad_candidates.len(),
-                processed_count
-            ));
-            colors.push(TEXT_COLOR);
-            colors.push(TEXT_COLOR);
+    if status.paused {
+        info.push_str("\n[!] Blocking paused\n");
+        colors.push(TEXT_COLOR);
+        colors.push(0x0000FF);
+    }
+    if status.dry_run {
+        info.push_str("\n[i] Dry run: ads are recorded, not hidden\n");
+        colors.push(TEXT_COLOR);
+        colors.push(0x0000FF);
+    }
+    if !status.disabled_categories.is_empty() {
+        let names: Vec<&str> = status
+            .disabled_categories
+            .iter()
+            .map(|r| r.name())
+            .collect();
+        info.push_str(&format!("\n[i] Off: {}\n", names.join(", ")));
+        colors.push(TEXT_COLOR);
+        colors.push(0x0000FF);
+    }
+    if !status.script_errors.is_empty() {
+        info.push_str("\n[!] Script errors:\n");
+        colors.push(TEXT_COLOR);
+        colors.push(0x0000FF);
+        for error in &status.script_errors {
+            info.push_str(&format!("  {}\n", error));
+            colors.push(0x0000FF);
+        }
+    }
+    if !status.script_output.is_empty() {
+        info.push_str("\n[i] Script output:\n");
+        colors.push(TEXT_COLOR);
+        colors.push(TEXT_COLOR);
+        for line in &status.script_output {
+            info.push_str(&format!("  {}\n", line));
+            colors.push(PROCESSED_COLOR);
+        }
+    }
 
-            for (&hwnd_key, &processed) in &state.ad_candidates {
-                let status = if processed { "✓" } else { "○" };
-                info.push_str(&format!("  {} 0x{:08X}\n", status, hwnd_key));
-                colors.push(if processed {
-                    PROCESSED_COLOR
-                } else {
-                    TEXT_COLOR
-                });
-            }
+    info.push_str(&format!("\nMain Windows: {}\n", status.main_windows.len()));
+    colors.push(TEXT_COLOR);
+    colors.push(TEXT_COLOR);
 
-            info.push_str(&format!(
-                "\nCached: {} classes\n",
-                state.window_class_cache.len()
-            ));
-            colors.push(TEXT_COLOR);
-            colors.push(TEXT_COLOR);
+    // Profile names only matter when there is more than one
+    let profile_tag = |profile: &str| {
+        if status.profiles.len() > 1 {
+            format!(" [{}]", profile)
+        } else {
+            String::new()
         }
+    };
+    for main_window in &status.main_windows {
+        let state = if !main_window.valid {
+            " [INVALID]"
+        } else if main_window.title.is_empty() {
+            " (empty)"
+        } else {
+            ""
+        };
+        let title_display = if main_window.title.is_empty() {
+            String::new()
+        } else {
+            format!(" {}", main_window.title)
+        };
+        info.push_str(&format!(
+            "  0x{:08X}{}{}{}\n",
+            main_window.hwnd,
+            title_display,
+            state,
+            profile_tag(&main_window.profile)
+        ));
+        colors.push(if !main_window.valid {
+            PROCESSED_COLOR
+        } else {
+            TEXT_COLOR
+        });
     }
 
+    let processed_count = status.candidates.iter().filter(|c| c.blocked).count();
+    info.push_str(&format!(
+        "\nAd Candidates: {} ({} blocked)\n",
+        status.candidates.len(),
+        processed_count
+    ));
+    colors.push(TEXT_COLOR);
+    colors.push(TEXT_COLOR);
+
+    for candidate in &status.candidates {
+        let mark = if candidate.blocked { "✓" } else { "○" };
+        info.push_str(&format!(
+            "  {} 0x{:08X}{}\n",
+            mark,
+            candidate.hwnd,
+            profile_tag(&candidate.profile)
+        ));
+        colors.push(if candidate.blocked {
+            PROCESSED_COLOR
+        } else {
+            TEXT_COLOR
+        });
+    }
+
+    info.push_str(&format!(
+        "\nCached: {} classes\n",
+        status.counters.cached_classes
+    ));
+    colors.push(TEXT_COLOR);
+    colors.push(TEXT_COLOR);
+
     info.push_str("\n─────────────────────\n");
     colors.push(TEXT_COLOR);
     colors.push(TITLE_COLOR);
-    info.push_str("Drag | RClick close | Scroll");
+    info.push_str("Drag | RClick close | Scroll | Tab tree");
     colors.push(TITLE_COLOR);
 
     (info, colors)
 }
 
+/// Returns (lines, colors_per_line) for the release notes, if they are shown
+fn get_release_notes_with_colors() -> Option<(Vec<String>, Vec<u32>)> {
+    let mut lines = RELEASE_NOTES.lock().clone()?;
+    let mut colors = vec![TEXT_COLOR; lines.len()];
+    if let Some(title) = colors.first_mut() {
+        *title = TITLE_COLOR;
+    }
+
+    lines.push("─────────────────────".to_string());
+    colors.push(TITLE_COLOR);
+    lines.push("Scroll | Esc/Tab back | RClick close".to_string());
+    colors.push(TITLE_COLOR);
+
+    Some((lines, colors))
+}
+
+/// Number of tree rows that fit between the header and the details pane
+fn tree_rows_visible(client_bottom: i32) -> usize {
+    ((client_bottom - TEXT_TOP * 2) / LINE_HEIGHT - TREE_HEADER_LINES - TREE_FOOTER_LINES).max(1)
+        as usize
+}
+
+/// Re-capture the client window hierarchy for the tree view
+fn refresh_tree() {
+    let targets = unsafe {
+        match DEBUG_STATE {
+            Some(ref state) => state.lock().target_rules(),
+            None => return,
+        }
+    };
+    let roots = window_tree::capture(&targets);
+    INSPECTOR.lock().set_roots(roots);
+}
+
+/// Returns (lines, colors_per_line, highlighted_line) for the tree view
+fn get_tree_view_with_colors(visible_rows: usize) -> (Vec<String>, Vec<u32>, Option<usize>) {
+    let inspector = INSPECTOR.lock();
+    let rows = inspector.rows();
+    let selected = inspector.selected_index(&rows);
+
+    // Scroll so the selected row stays visible
+    let mut top = TREE_TOP.load(Ordering::Relaxed);
+    if let Some(sel) = selected {
+        if sel < top {
+            top = sel;
+        } else if sel >= top + visible_rows {
+            top = sel + 1 - visible_rows;
+        }
+    }
+    top = top.min(rows.len().saturating_sub(visible_rows));
+    TREE_TOP.store(top, Ordering::Relaxed);
+
+    let mut lines = vec![
+        format!("Window Tree ({} rows)", rows.len()),
+        format!("Filter: {}_", inspector.filter()),
+    ];
+    let mut colors = vec![TITLE_COLOR, TEXT_COLOR];
+
+    for i in top..top + visible_rows {
+        let Some(row) = rows.get(i) else {
+            lines.push(String::new());
+            colors.push(TEXT_COLOR);
+            continue;
+        };
+        let Some(node) = inspector.node(row) else {
+            continue;
+        };
+        let marker = match (row.has_children, row.expanded) {
+            (false, _) => "  ",
+            (true, false) => "▸ ",
+            (true, true) => "▾ ",
+        };
+        lines.push(format!(
+            "{}{}{}",
+            "  ".repeat(row.depth),
+            marker,
+            node.label()
+        ));
+        colors.push(if !node.visible {
+            PROCESSED_COLOR
+        } else if node.rule.is_some() {
+            TITLE_COLOR
+        } else {
+            TEXT_COLOR
+        });
+    }
+
+    let highlight = selected
+        .filter(|&sel| sel >= top && sel < top + visible_rows)
+        .map(|sel| sel - top + TREE_HEADER_LINES as usize);
+
+    lines.push("─────────────────────".to_string());
+    colors.push(TITLE_COLOR);
+
+    let mut details = inspector
+        .selected_node()
+        .map(|node| node.details())
+        .unwrap_or_else(|| vec!["No window selected".to_string()]);
+    details.resize(TREE_DETAIL_LINES, String::new());
+    for line in details {
+        lines.push(line);
+        colors.push(TEXT_COLOR);
+    }
+
+    lines.push("Tab summary | Arrows | Enter | Ctrl+C copy".to_string());
+    colors.push(TITLE_COLOR);
+
+    (lines, colors, highlight)
+}
+
 fn to_wide(s: &str) -> Vec<u16> {
     s.encode_utf16().chain(std::iter::once(0)).collect()
 }
@@ -0,0 +1,245 @@
+//! Windows icon (.ico) encoder
+//!
+//! Used by `build.rs` to embed the application icon. Small sizes are stored
+//! as 32-bit DIBs, which every icon loader understands; large sizes as PNG,
+//! which keeps the 256 px image small (supported since Windows Vista).
+
+use std::io::Write;
+
+/// Sizes from which images are stored as PNG
+const PNG_MIN_SIZE: u32 = 64;
+
+const ICONDIR_SIZE: usize = 6;
+const ICONDIRENTRY_SIZE: usize = 16;
+const BITMAPINFOHEADER_SIZE: u32 = 40;
+
+const PNG_SIGNATURE: &[u8; 8] = b"\x89PNG\r\n\x1a\n";
+
+/// Encode square RGBA images as (size, pixels) into an ICO file
+pub fn encode(images: &[(u32, Vec<u8>)]) -> Result<Vec<u8>, String> {
+    let mut payloads = Vec::with_capacity(images.len());
+    for (size, rgba) in images {
+        if !(1..=256).contains(size) || rgba.len() != (size * size * 4) as usize {
+            return Err(format!("Invalid {0}x{0} icon image", size));
+        }
+        payloads.push(if *size >= PNG_MIN_SIZE {
+            encode_png(*size, *size, rgba)?
+        } else {
+            encode_dib(*size, rgba)
+        });
+    }
+
+    let mut out = Vec::new();
+    out.extend_from_slice(&0u16.to_le_bytes()); // reserved
+    out.extend_from_slice(&1u16.to_le_bytes()); // type: icon
+    out.extend_from_slice(&(images.len() as u16).to_le_bytes());
+
+    let mut offset = ICONDIR_SIZE + ICONDIRENTRY_SIZE * images.len();
+    for ((size, _), payload) in images.iter().zip(&payloads) {
+        // 256 is stored as 0
+        out.push(*size as u8);
+        out.push(*size as u8);
+        out.push(0); // palette colors
+        out.push(0); // reserved
+        out.extend_from_slice(&1u16.to_le_bytes()); // planes
+        out.extend_from_slice(&32u16.to_le_bytes()); // bits per pixel
+        out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(offset as u32).to_le_bytes());
+        offset += payload.len();
+    }
+    for payload in payloads {
+        out.extend_from_slice(&payload);
+    }
+    Ok(out)
+}
+
+/// 32-bit DIB as stored in icons: double height (image plus AND mask),
+/// bottom-up BGRA rows, then an all-zero mask since alpha is used instead
+fn encode_dib(size: u32, rgba: &[u8]) -> Vec<u8> {
+    let mask_row = size.div_ceil(32) * 4;
+    let mut out = Vec::new();
+    out.extend_from_slice(&BITMAPINFOHEADER_SIZE.to_le_bytes());
+    out.extend_from_slice(&(size as i32).to_le_bytes());
+    out.extend_from_slice(&(size as i32 * 2).to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes()); // planes
+    out.extend_from_slice(&32u16.to_le_bytes()); // bits per pixel
+    out.extend_from_slice(&0u32.to_le_bytes()); // BI_RGB
+    out.extend_from_slice(&(size * size * 4 + mask_row * size).to_le_bytes());
+    out.extend_from_slice(&[0; 16]); // resolution and palette
+
+    for row in rgba.chunks_exact(size as usize * 4).rev() {
+        for pixel in row.chunks_exact(4) {
+            out.extend_from_slice(&[pixel[2], pixel[1], pixel[0], pixel[3]]);
+        }
+    }
+    out.resize(out.len() + (mask_row * size) as usize, 0);
+    out
+}
+
+/// Minimal 8-bit RGBA PNG (no filtering, zlib-compressed)
+fn encode_png(width: u32, height: u32, rgba: &[u8]) -> Result<Vec<u8>, String> {
+    let mut raw = Vec::with_capacity(rgba.len() + height as usize);
+    for row in rgba.chunks_exact(width as usize * 4) {
+        raw.push(0); // filter: none
+        raw.extend_from_slice(row);
+    }
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::best());
+    encoder
+        .write_all(&raw)
+        .and_then(|_| encoder.finish())
+        .map(|data| {
+            let mut header = Vec::with_capacity(13);
+            header.extend_from_slice(&width.to_be_bytes());
+            header.extend_from_slice(&height.to_be_bytes());
+            // 8-bit depth, RGBA, deflate, adaptive filtering, no interlace
+            header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+            let mut out = PNG_SIGNATURE.to_vec();
+            write_chunk(&mut out, b"IHDR", &header);
+            write_chunk(&mut out, b"IDAT", &data);
+            write_chunk(&mut out, b"IEND", &[]);
+            out
+        })
+        .map_err(|e| format!("Failed to compress icon image: {}", e))
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(kind);
+    crc.update(data);
+    out.extend_from_slice(&crc.finalize().to_be_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn u16_at(bytes: &[u8], pos: usize) -> u16 {
+        u16::from_le_bytes(bytes[pos..pos + 2].try_into().unwrap())
+    }
+
+    fn u32_at(bytes: &[u8], pos: usize) -> u32 {
+        u32::from_le_bytes(bytes[pos..pos + 4].try_into().unwrap())
+    }
+
+    /// Decode an ICO file back into (size, RGBA) images
+    fn decode(bytes: &[u8]) -> Vec<(u32, Vec<u8>)> {
+        assert_eq!(u16_at(bytes, 0), 0);
+        assert_eq!(u16_at(bytes, 2), 1);
+        let count = u16_at(bytes, 4) as usize;
+
+        (0..count)
+            .map(|i| {
+                let entry = &bytes[ICONDIR_SIZE + i * ICONDIRENTRY_SIZE..];
+                let size = if entry[0] == 0 { 256 } else { entry[0] as u32 };
+                assert_eq!(entry[0], entry[1]);
+                assert_eq!(u16_at(entry, 6), 32);
+                let len = u32_at(entry, 8) as usize;
+                let offset = u32_at(entry, 12) as usize;
+                let payload = &bytes[offset..offset + len];
+                let rgba = if payload.starts_with(PNG_SIGNATURE) {
+                    decode_png(size, payload)
+                } else {
+                    decode_dib(size, payload)
+                };
+                (size, rgba)
+            })
+            .collect()
+    }
+
+    fn decode_dib(size: u32, dib: &[u8]) -> Vec<u8> {
+        assert_eq!(u32_at(dib, 0), BITMAPINFOHEADER_SIZE);
+        assert_eq!(u32_at(dib, 4), size);
+        assert_eq!(u32_at(dib, 8), size * 2);
+        let pixels = &dib[BITMAPINFOHEADER_SIZE as usize..][..(size * size * 4) as usize];
+        pixels
+            .chunks_exact(size as usize * 4)
+            .rev()
+            .flat_map(|row| row.chunks_exact(4))
+            .flat_map(|p| [p[2], p[1], p[0], p[3]])
+            .collect()
+    }
+
+    fn decode_png(size: u32, png: &[u8]) -> Vec<u8> {
+        let mut pos = PNG_SIGNATURE.len();
+        let mut idat = Vec::new();
+        loop {
+            let len = u32::from_be_bytes(png[pos..pos + 4].try_into().unwrap()) as usize;
+            let kind = &png[pos + 4..pos + 8];
+            let data = &png[pos + 8..pos + 8 + len];
+            let crc = u32::from_be_bytes(png[pos + 8 + len..pos + 12 + len].try_into().unwrap());
+            assert_eq!(crc, crc32fast::hash(&png[pos + 4..pos + 8 + len]));
+            match kind {
+                b"IHDR" => {
+                    assert_eq!(&data[..4], &size.to_be_bytes());
+                    assert_eq!(&data[8..10], &[8, 6]);
+                }
+                b"IDAT" => idat.extend_from_slice(data),
+                b"IEND" => break,
+                _ => {}
+            }
+            pos += 12 + len;
+        }
+
+        let mut raw = Vec::new();
+        flate2::read::ZlibDecoder::new(&idat[..])
+            .read_to_end(&mut raw)
+            .unwrap();
+        raw.chunks_exact(size as usize * 4 + 1)
+            .flat_map(|row| {
+                assert_eq!(row[0], 0);
+                row[1..].to_vec()
+            })
+            .collect()
+    }
+
+    fn gradient(size: u32) -> Vec<u8> {
+        (0..size * size)
+            .flat_map(|i| {
+                let (x, y) = (i % size, i / size);
+                [x as u8, y as u8, (x ^ y) as u8, (x + y) as u8]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let images: Vec<(u32, Vec<u8>)> = [16, 24, 32, 48, 64, 128, 256]
+            .into_iter()
+            .map(|size| (size, gradient(size)))
+            .collect();
+        let ico = encode(&images).unwrap();
+        assert_eq!(decode(&ico), images);
+
+        // Large sizes are PNG-compressed, small ones plain bitmaps
+        let entry =
+            |i: usize| &ico[u32_at(&ico, ICONDIR_SIZE + i * ICONDIRENTRY_SIZE + 12) as usize..];
+        assert!(!entry(0).starts_with(PNG_SIGNATURE));
+        assert!(entry(6).starts_with(PNG_SIGNATURE));
+        assert_eq!(ico[ICONDIR_SIZE + 6 * ICONDIRENTRY_SIZE], 0);
+    }
+
+    #[test]
+    fn test_app_icon_round_trip() {
+        let images: Vec<(u32, Vec<u8>)> = [16, 32, 256]
+            .into_iter()
+            .map(|size| {
+                (
+                    size,
+                    crate::icon::render(crate::icon::TrayStatus::Active, size),
+                )
+            })
+            .collect();
+        assert_eq!(decode(&encode(&images).unwrap()), images);
+    }
+
+    #[test]
+    fn test_rejects_invalid_images() {
+        assert!(encode(&[(16, vec![0; 10])]).is_err());
+        assert!(encode(&[(512, vec![0; 512 * 512 * 4])]).is_err());
+    }
+}
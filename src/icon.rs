@@ -1,169 +1,506 @@
 //! Tray icon generation
 //!
-//! Creates a shield icon for the ad blocker
-
-/// Generate a shield icon with block symbol
-/// Returns RGBA pixel data
-fn generate_shield_icon(size: u32) -> Vec<u8> {
-    let mut rgba = vec![0u8; (size * size * 4) as usize];
-    let center_x = size as f32 / 2.0;
-    let center_y = size as f32 / 2.0;
-
-    // Shield colors - KakaoTalk style
-    let shield_color = [0xFA, 0xE1, 0x00, 0xFF]; // KakaoTalk Yellow #FAE100
-    let block_color = [0x3C, 0x1E, 0x1E, 0xFF]; // Dark brown (KakaoTalk text color)
-    let outline_color = [0xD4, 0xBE, 0x00, 0xFF]; // Darker yellow
-
-    for y in 0..size {
-        for x in 0..size {
-            let idx = ((y * size + x) * 4) as usize;
-            let fx = x as f32;
-            let fy = y as f32;
-
-            // Shield shape: rounded top, pointed bottom
-            let in_shield = is_in_shield(fx, fy, center_x, center_y, size as f32);
-            let on_outline = is_on_shield_outline(fx, fy, center_x, center_y, size as f32);
-
-            if on_outline {
-                rgba[idx..idx + 4].copy_from_slice(&outline_color);
-            } else if in_shield {
-                // Check if in the "block" symbol (diagonal line)
-                let in_block = is_in_block_symbol(fx, fy, center_x, center_y, size as f32);
-
-                if in_block {
-                    rgba[idx..idx + 4].copy_from_slice(&block_color);
-                } else {
-                    rgba[idx..idx + 4].copy_from_slice(&shield_color);
-                }
-            }
-            // else: transparent (already 0)
+//! Creates a shield icon for the ad blocker, with a variant for each status:
+//! grey while blocking is off, and a corner badge for states worth noticing.
+//! Shapes are vector paths drawn with anti-aliasing, at the exact tray size so
+//! they stay crisp at every DPI. `build.rs` also includes this module (and
+//! `raster`) to draw the application icon, so it must not use other crates.
+
+use std::f32::consts::PI;
+
+use crate::raster::{Canvas, Path};
+
+/// Sizes rendered for the tray (100% to 300% scaling)
+pub const SIZES: [u32; 5] = [16, 20, 24, 32, 48];
+
+/// What the tray icon shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrayStatus {
+    /// Blocking ads in a running KakaoTalk
+    Active,
+    /// Blocking paused from the tray
+    Paused,
+    /// Ads are only logged, not hidden
+    DryRun,
+    KakaoTalkNotRunning,
+    UpdateAvailable,
+    /// Blocking stopped working (process enumeration keeps failing)
+    Error,
+}
+
+impl TrayStatus {
+    /// The status to show, most important first
+    pub fn resolve(
+        error: bool,
+        paused: bool,
+        kakaotalk_running: bool,
+        dry_run: bool,
+        update_available: bool,
+    ) -> Self {
+        if error {
+            TrayStatus::Error
+        } else if paused {
+            TrayStatus::Paused
+        } else if !kakaotalk_running {
+            TrayStatus::KakaoTalkNotRunning
+        } else if dry_run {
+            TrayStatus::DryRun
+        } else if update_available {
+            TrayStatus::UpdateAvailable
+        } else {
+            TrayStatus::Active
         }
     }
 
-    rgba
+    fn palette(self) -> &'static Palette {
+        match self {
+            TrayStatus::Paused | TrayStatus::KakaoTalkNotRunning => &INACTIVE,
+            _ => &ACTIVE,
+        }
+    }
+
+    fn badge(self) -> Option<Badge> {
+        match self {
+            TrayStatus::Active | TrayStatus::KakaoTalkNotRunning => None,
+            TrayStatus::Paused => Some(Badge::Pause),
+            TrayStatus::DryRun => Some(Badge::DryRun),
+            TrayStatus::UpdateAvailable => Some(Badge::Update),
+            TrayStatus::Error => Some(Badge::Error),
+        }
+    }
 }
 
-/// Check if point is inside shield shape
-fn is_in_shield(x: f32, y: f32, cx: f32, cy: f32, size: f32) -> bool {
-    let margin = size * 0.1;
-    let top = margin;
-    let bottom = size - margin;
-    let left = margin + size * 0.05;
-    let right = size - margin - size * 0.05;
+/// Shield colors
+struct Palette {
+    shield: [u8; 4],
+    outline: [u8; 4],
+    block: [u8; 4],
+}
 
-    // Upper part: rounded rectangle
-    let upper_bottom = cy + size * 0.1;
-
-    if y >= top && y <= upper_bottom {
-        // Check horizontal bounds with rounded corners
-        let corner_radius = size * 0.15;
-        let in_x = x >= left && x <= right;
-
-        if in_x {
-            // Round the top corners
-            if y < top + corner_radius {
-                if x < left + corner_radius {
-                    let dx = x - (left + corner_radius);
-                    let dy = y - (top + corner_radius);
-                    return dx * dx + dy * dy <= corner_radius * corner_radius;
-                } else if x > right - corner_radius {
-                    let dx = x - (right - corner_radius);
-                    let dy = y - (top + corner_radius);
-                    return dx * dx + dy * dy <= corner_radius * corner_radius;
-                }
-            }
-            return true;
+/// KakaoTalk style
+const ACTIVE: Palette = Palette {
+    shield: [0xFA, 0xE1, 0x00, 0xFF],  // KakaoTalk Yellow #FAE100
+    outline: [0xD4, 0xBE, 0x00, 0xFF], // Darker yellow
+    block: [0x3C, 0x1E, 0x1E, 0xFF],   // Dark brown (KakaoTalk text color)
+};
+
+/// Greyed out while nothing is being blocked
+const INACTIVE: Palette = Palette {
+    shield: [0xC8, 0xC8, 0xC8, 0xFF],
+    outline: [0x96, 0x96, 0x96, 0xFF],
+    block: [0x5A, 0x5A, 0x5A, 0xFF],
+};
+
+/// Corner badge: a colored disc with a white glyph
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Badge {
+    /// Two bars
+    Pause,
+    /// Ring
+    DryRun,
+    /// Up arrow
+    Update,
+    /// Exclamation mark
+    Error,
+}
+
+const BADGE_GLYPH_COLOR: [u8; 4] = [0xFF, 0xFF, 0xFF, 0xFF];
+
+impl Badge {
+    fn color(self) -> [u8; 4] {
+        match self {
+            Badge::Pause => [0x4A, 0x4A, 0x4A, 0xFF],
+            Badge::DryRun => [0x2F, 0x80, 0xED, 0xFF],
+            Badge::Update => [0x27, 0xAE, 0x60, 0xFF],
+            Badge::Error => [0xE5, 0x39, 0x35, 0xFF],
         }
-        return false;
     }
 
-    // Lower part: triangle pointing down
-    if y > upper_bottom && y <= bottom {
-        let progress = (y - upper_bottom) / (bottom - upper_bottom);
-        let half_width = (right - left) / 2.0 * (1.0 - progress);
-        return x >= cx - half_width && x <= cx + half_width;
+    /// Glyph for a badge of `radius` around (`center`, `center`)
+    ///
+    /// Glyph units are fractions of the radius. Bars are snapped to whole
+    /// pixels so they stay sharp at 16 px.
+    fn glyph(self, center: f32, radius: f32) -> Path {
+        let at = |u: f32| center + u * radius;
+        let bar = |left: f32, top: f32, right: f32, bottom: f32| {
+            Path::rect(
+                at(left).round(),
+                at(top).round(),
+                at(right).round().max(at(left).round() + 1.0),
+                at(bottom).round().max(at(top).round() + 1.0),
+            )
+        };
+        match self {
+            Badge::Pause => bar(-0.45, -0.5, -0.15, 0.5).add(bar(0.15, -0.5, 0.45, 0.5)),
+            Badge::DryRun => Path::circle(center, center, 0.55 * radius)
+                .add(Path::circle(center, center, 0.25 * radius).hole()),
+            Badge::Update => bar(-0.15, -0.1, 0.15, 0.6).add(Path::polygon(&[
+                (at(0.0), at(-0.65)),
+                (at(0.6), at(0.0)),
+                (at(-0.6), at(0.0)),
+            ])),
+            Badge::Error => bar(-0.15, -0.6, 0.15, 0.15).add(bar(-0.15, 0.35, 0.15, 0.6)),
+        }
     }
+}
+
+/// Render the icon for `status` as RGBA pixel data
+pub fn render(status: TrayStatus, size: u32) -> Vec<u8> {
+    let mut canvas = Canvas::new(size, size);
+    draw_shield(&mut canvas, size as f32, status.palette());
+    if let Some(badge) = status.badge() {
+        draw_badge(&mut canvas, size as f32, badge);
+    }
+    canvas.to_rgba()
+}
+
+/// Draw `badge` over the bottom-right corner
+fn draw_badge(canvas: &mut Canvas, size: f32, badge: Badge) {
+    // Large enough for the glyph to stay legible at 16 px, and centred on a
+    // pixel so that symmetric glyphs land on whole pixels
+    let radius = (size * 0.25).max(4.0).round() + 0.5;
+    let center = size - radius;
+
+    canvas.fill(&Path::circle(center, center, radius), badge.color());
+    canvas.fill(&badge.glyph(center, radius), BADGE_GLYPH_COLOR);
+}
+
+/// Tray size to render for the system's small icon size
+pub fn size_for(small_icon_size: u32) -> u32 {
+    SIZES
+        .into_iter()
+        .find(|&size| size >= small_icon_size)
+        .unwrap_or(SIZES[SIZES.len() - 1])
+}
+
+/// Draw the shield with its outline and block symbol
+fn draw_shield(canvas: &mut Canvas, size: f32, palette: &Palette) {
+    let outline = size * 0.06;
+    canvas.fill(&shield_path(size, 0.0), palette.outline);
+    canvas.fill(&shield_path(size, outline), palette.shield);
+    canvas.fill(&block_symbol_path(size), palette.block);
+}
+
+/// Shield outline: rounded top, pointed bottom, inset by `inset`
+fn shield_path(size: f32, inset: f32) -> Path {
+    let margin = size * 0.1;
+    let top = margin + inset;
+    let left = margin + size * 0.05 + inset;
+    let right = size - margin - size * 0.05 - inset;
+    let radius = (size * 0.15 - inset).max(0.0);
+    let center_x = size / 2.0;
+
+    // Upper part: rounded rectangle
+    let upper_bottom = size / 2.0 + size * 0.1;
 
-    false
+    // Lower part: triangle pointing down; moving its sides in by `inset`
+    // moves the tip up by inset / sin(half the tip angle)
+    let outer_half_width = (right - left) / 2.0 + inset;
+    let outer_tip = size - margin;
+    let half_angle = outer_half_width.atan2(outer_tip - upper_bottom);
+    let tip = outer_tip - inset / half_angle.sin();
+
+    let mut path = Path::new();
+    path.move_to(left, upper_bottom)
+        .line_to(left, top + radius)
+        .arc(left + radius, top + radius, radius, PI, 1.5 * PI)
+        .arc(right - radius, top + radius, radius, 1.5 * PI, 2.0 * PI)
+        .line_to(right, upper_bottom)
+        .line_to(center_x, tip);
+    path
 }
 
-/// Check if point is on shield outline
-fn is_on_shield_outline(x: f32, y: f32, cx: f32, cy: f32, size: f32) -> bool {
-    let thickness = size * 0.06;
+/// Block symbol: an X of two diagonal bars, cut square at the ends
+fn block_symbol_path(size: f32) -> Path {
+    let half = size * 0.25;
+    let thickness = size * 0.08;
+    // Offset the symbol center slightly up
+    let (cx, cy) = (size / 2.0, size / 2.0 - size * 0.05);
+
+    // Band |dx - dy| < w inside the square |dx|, |dy| <= half
+    let w = thickness * std::f32::consts::SQRT_2;
+    let bar = [
+        (-half, -half),
+        (-half + w, -half),
+        (half, half - w),
+        (half, half),
+        (half - w, half),
+        (-half, -half + w),
+    ];
+    let mirrored: Vec<(f32, f32)> = bar.iter().map(|&(x, y)| (-x, y)).collect();
 
-    is_in_shield(x, y, cx, cy, size) && !is_in_shield_inner(x, y, cx, cy, size, thickness)
+    Path::polygon(&bar)
+        .add(Path::polygon(&mirrored))
+        .transform(1.0, cx, cy)
 }
 
-/// Check if point is inside inner shield (for outline calculation)
-fn is_in_shield_inner(x: f32, y: f32, cx: f32, cy: f32, size: f32, shrink: f32) -> bool {
-    let margin = size * 0.1 + shrink;
-    let top = margin;
-    let bottom = size - margin;
-    let left = margin + size * 0.05;
-    let right = size - margin - size * 0.05;
-
-    let upper_bottom = cy + size * 0.1;
-
-    if y >= top && y <= upper_bottom {
-        let corner_radius = (size * 0.15 - shrink).max(0.0);
-        let in_x = x >= left && x <= right;
-
-        if in_x {
-            if y < top + corner_radius && corner_radius > 0.0 {
-                if x < left + corner_radius {
-                    let dx = x - (left + corner_radius);
-                    let dy = y - (top + corner_radius);
-                    return dx * dx + dy * dy <= corner_radius * corner_radius;
-                } else if x > right - corner_radius {
-                    let dx = x - (right - corner_radius);
-                    let dy = y - (top + corner_radius);
-                    return dx * dx + dy * dy <= corner_radius * corner_radius;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [TrayStatus; 6] = [
+        TrayStatus::Active,
+        TrayStatus::Paused,
+        TrayStatus::DryRun,
+        TrayStatus::KakaoTalkNotRunning,
+        TrayStatus::UpdateAvailable,
+        TrayStatus::Error,
+    ];
+
+    fn pixel(rgba: &[u8], size: u32, x: u32, y: u32) -> [u8; 4] {
+        let idx = ((y * size + x) * 4) as usize;
+        rgba[idx..idx + 4].try_into().unwrap()
+    }
+
+    /// Pixels of the top-left area, which badges never reach
+    fn shield_area(rgba: &[u8], size: u32) -> Vec<[u8; 4]> {
+        (0..size * 2 / 5)
+            .flat_map(|y| (0..size * 2 / 5).map(move |x| (x, y)))
+            .map(|(x, y)| pixel(rgba, size, x, y))
+            .collect()
+    }
+
+    #[test]
+    fn test_every_size_and_status_renders() {
+        for size in SIZES {
+            for status in ALL {
+                let rgba = render(status, size);
+                assert_eq!(rgba.len(), (size * size * 4) as usize);
+                // Transparent corner, opaque shield body
+                assert_eq!(pixel(&rgba, size, 0, 0)[3], 0, "{:?} {}", status, size);
+                // Between the top of the outline and the block symbol
+                let body = pixel(&rgba, size, size / 2, size * 22 / 100);
+                assert_eq!(body, status.palette().shield, "{:?} {}", status, size);
+            }
+        }
+    }
+
+    #[test]
+    fn test_variants_differ() {
+        for size in SIZES {
+            let icons: Vec<Vec<u8>> = ALL.iter().map(|&s| render(s, size)).collect();
+            for i in 0..icons.len() {
+                for j in i + 1..icons.len() {
+                    assert_ne!(icons[i], icons[j], "{:?} vs {:?}", ALL[i], ALL[j]);
                 }
             }
-            return true;
         }
-        return false;
     }
 
-    if y > upper_bottom && y <= bottom {
-        let progress = (y - upper_bottom) / (bottom - upper_bottom);
-        let half_width = ((right - left) / 2.0 - shrink).max(0.0) * (1.0 - progress);
-        return x >= cx - half_width && x <= cx + half_width;
+    #[test]
+    fn test_badges() {
+        for size in SIZES {
+            let active = render(TrayStatus::Active, size);
+            for status in [
+                TrayStatus::Paused,
+                TrayStatus::DryRun,
+                TrayStatus::UpdateAvailable,
+                TrayStatus::Error,
+            ] {
+                let badge = status.badge().unwrap();
+                let rgba = render(status, size);
+                // The badge stays in its corner, leaving the shield readable
+                assert_eq!(
+                    shield_area(&rgba, size),
+                    shield_area(&render_without_badge(status, size), size)
+                );
+                // Its disc and glyph are both visible (glyph strokes may be
+                // thinner than a pixel at small sizes)
+                let pixels: Vec<[u8; 4]> = (0..size * size)
+                    .map(|i| pixel(&rgba, size, i % size, i / size))
+                    .collect();
+                assert!(pixels.contains(&badge.color()), "{:?} {}", status, size);
+                assert!(
+                    pixels.iter().any(|p| is_near(*p, BADGE_GLYPH_COLOR)),
+                    "{:?} {}",
+                    status,
+                    size
+                );
+            }
+            assert!(!(0..size * size)
+                .any(|i| is_near(pixel(&active, size, i % size, i / size), BADGE_GLYPH_COLOR)));
+        }
     }
 
-    false
-}
+    fn is_near(a: [u8; 4], b: [u8; 4]) -> bool {
+        a.iter().zip(b).all(|(&a, b)| a.abs_diff(b) <= 64)
+    }
 
-/// Check if point is in the block symbol (X or diagonal line)
-fn is_in_block_symbol(x: f32, y: f32, cx: f32, cy: f32, size: f32) -> bool {
-    let symbol_size = size * 0.25;
-    let thickness = size * 0.08;
+    fn render_without_badge(status: TrayStatus, size: u32) -> Vec<u8> {
+        let mut canvas = Canvas::new(size, size);
+        draw_shield(&mut canvas, size as f32, status.palette());
+        canvas.to_rgba()
+    }
 
-    // Offset the symbol center slightly up
-    let sy = cy - size * 0.05;
+    /// One character per pixel: `.` if transparent, `+` if partly
+    /// transparent, otherwise the closest of the icon's colors
+    fn art(status: TrayStatus, size: u32) -> Vec<String> {
+        let palette = status.palette();
+        let mut colors = vec![
+            ('o', palette.outline),
+            ('s', palette.shield),
+            ('x', palette.block),
+        ];
+        if let Some(badge) = status.badge() {
+            colors.push(('b', badge.color()));
+            colors.push(('w', BADGE_GLYPH_COLOR));
+        }
+        let distance = |a: [u8; 4], b: [u8; 4]| -> u32 {
+            a.iter()
+                .zip(b)
+                .map(|(&a, b)| (a.abs_diff(b) as u32).pow(2))
+                .sum()
+        };
 
-    // Distance from center
-    let dx = (x - cx).abs();
-    let dy = (y - sy).abs();
+        let rgba = render(status, size);
+        (0..size)
+            .map(|y| {
+                (0..size)
+                    .map(|x| match pixel(&rgba, size, x, y) {
+                        [_, _, _, 0] => '.',
+                        [_, _, _, 0xFF] => {
+                            let p = pixel(&rgba, size, x, y);
+                            colors
+                                .iter()
+                                .min_by_key(|(_, color)| distance(p, *color))
+                                .unwrap()
+                                .0
+                        }
+                        _ => '+',
+                    })
+                    .collect()
+            })
+            .collect()
+    }
 
-    // Check if within symbol bounds
-    if dx > symbol_size || dy > symbol_size {
-        return false;
+    fn assert_golden(status: TrayStatus, size: u32, expected: &[&str]) {
+        let actual = art(status, size);
+        assert!(
+            actual == expected,
+            "{:?} {} px differs from the golden image:\n{}",
+            status,
+            size,
+            actual.join("\n")
+        );
     }
 
-    // X shape: two diagonal lines
-    // Line 1: top-left to bottom-right
-    let dist1 = ((x - cx) - (y - sy)).abs() / 1.414;
-    // Line 2: top-right to bottom-left
-    let dist2 = ((x - cx) + (y - sy)).abs() / 1.414;
+    #[test]
+    fn test_golden_images() {
+        assert_golden(
+            TrayStatus::Active,
+            16,
+            &[
+                "................",
+                "...++++++++++...",
+                "..++oooooooo++..",
+                "..+sxxossoxxs+..",
+                "..+sxxxooxxxs+..",
+                "..+soxxxxxxos+..",
+                "..+ssoxxxxoss+..",
+                "..+ssoxxxxoss+..",
+                "..+soxxxxxxos+..",
+                "..+sxxxooxxxs+..",
+                "..++xxossoxx++..",
+                "....++ssss++....",
+                ".....++ss++.....",
+                "......++++......",
+                ".......++.......",
+                "................",
+            ],
+        );
+        assert_golden(
+            TrayStatus::Error,
+            16,
+            &[
+                "................",
+                "...++++++++++...",
+                "..++oooooooo++..",
+                "..+sxxossoxxs+..",
+                "..+sxxxooxxxs+..",
+                "..+soxxxxxxos+..",
+                "..+ssoxxxxoss+..",
+                "..+ssoxxxbbbb++.",
+                "..+soxxxbbbbbb++",
+                "..+sxxxbbbbwbbb+",
+                "..++xxobbbbwbbb+",
+                "....++sbbbbwbbb+",
+                ".....++bbbbbbbb+",
+                "......++bbbwbbb+",
+                ".......++bbbbb++",
+                "........+++++++.",
+            ],
+        );
+        assert_golden(
+            TrayStatus::Paused,
+            20,
+            &[
+                "....................",
+                "....................",
+                "...+++oooooooo+++...",
+                "...+osssssssssso+...",
+                "...+sxxossssoxxs+...",
+                "...osxxxossoxxxso...",
+                "...osoxxxooxxxoso...",
+                "...ossoxxxxxxosso...",
+                "...osssoxxxxossso...",
+                "...osssoxxxxxxbxx+..",
+                "...ossoxxxxbbbbbb++.",
+                "...osoxxxxbbbbbbbb++",
+                "...++xxxoobbwwbwwbb+",
+                "....+xxosxbbwwbwwbb+",
+                ".....++ssbbbwwbwwbb+",
+                "......++oxbbwwbwwbb+",
+                ".......++xbbwwbwwbb+",
+                "........+++bbbbbbb++",
+                "..........++bbbbb++.",
+                "...........+++b+++..",
+            ],
+        );
+    }
 
-    dist1 < thickness || dist2 < thickness
-}
+    #[test]
+    fn test_inactive_states_are_grey() {
+        for status in [TrayStatus::Paused, TrayStatus::KakaoTalkNotRunning] {
+            let rgba = render(status, 32);
+            let yellow = ACTIVE.shield;
+            assert!(!(0..32 * 32).any(|i| pixel(&rgba, 32, i % 32, i / 32) == yellow));
+        }
+    }
 
-/// Load and create the tray icon
-pub fn load_icon() -> tray_icon::Icon {
-    let size = 32u32;
-    let rgba = generate_shield_icon(size);
+    #[test]
+    fn test_resolve() {
+        assert_eq!(
+            TrayStatus::resolve(false, false, true, false, false),
+            TrayStatus::Active
+        );
+        assert_eq!(
+            TrayStatus::resolve(true, true, false, true, true),
+            TrayStatus::Error
+        );
+        assert_eq!(
+            TrayStatus::resolve(false, true, false, true, true),
+            TrayStatus::Paused
+        );
+        assert_eq!(
+            TrayStatus::resolve(false, false, false, true, true),
+            TrayStatus::KakaoTalkNotRunning
+        );
+        assert_eq!(
+            TrayStatus::resolve(false, false, true, true, true),
+            TrayStatus::DryRun
+        );
+        assert_eq!(
+            TrayStatus::resolve(false, false, true, false, true),
+            TrayStatus::UpdateAvailable
+        );
+    }
 
-    tray_icon::Icon::from_rgba(rgba, size, size).expect("Failed to create icon")
+    #[test]
+    fn test_size_for() {
+        assert_eq!(size_for(16), 16);
+        assert_eq!(size_for(20), 20);
+        assert_eq!(size_for(18), 20);
+        assert_eq!(size_for(40), 48);
+        assert_eq!(size_for(64), 48);
+    }
 }
@@ -0,0 +1,392 @@
+//! `--install` and `--uninstall`
+//!
+//! Releases are a single loose executable. Installing copies it to the
+//! per-user program directory, starts it at logon from there and optionally
+//! adds a Start menu shortcut. Uninstalling removes all of that along with the
+//! settings and data directories, then deletes the executable once it exits.
+//!
+//! File operations work on a [`Layout`] of directories, so they can run
+//! against a temp directory.
+
+use std::path::{Path, PathBuf};
+
+use crate::shortcut::Shortcut;
+use crate::startup;
+
+pub const INSTALL_ARG: &str = "--install";
+pub const UNINSTALL_ARG: &str = "--uninstall";
+/// With `--install`: skip the Start menu shortcut
+pub const NO_SHORTCUT_ARG: &str = "--no-shortcut";
+
+/// File name of the installed executable (fixed, so updates replace it in place)
+const EXE_NAME: &str = "KakaoTalkAdBlock.exe";
+const SHORTCUT_NAME: &str = "KakaoTalkAdBlock.lnk";
+
+/// How long a running copy gets to exit after being asked or terminated
+#[cfg(windows)]
+const EXIT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Install or uninstall, as requested on the command line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Install { start_menu_shortcut: bool },
+    Uninstall,
+}
+
+/// The command requested by `args` (without the program name), if any
+pub fn parse_args(args: impl Iterator<Item = String>) -> Option<Command> {
+    let args: Vec<String> = args.collect();
+    let has = |flag: &str| args.iter().any(|arg| arg.eq_ignore_ascii_case(flag));
+    if has(UNINSTALL_ARG) {
+        Some(Command::Uninstall)
+    } else if has(INSTALL_ARG) {
+        Some(Command::Install {
+            start_menu_shortcut: !has(NO_SHORTCUT_ARG),
+        })
+    } else {
+        None
+    }
+}
+
+/// Where the installed app and its data live
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    pub program_dir: PathBuf,
+    pub start_menu_dir: PathBuf,
+    pub config_dir: PathBuf,
+    pub data_dir: PathBuf,
+}
+
+impl Layout {
+    /// The current user's directories
+    pub fn current() -> Self {
+        Self {
+            program_dir: crate::paths::program_dir(),
+            start_menu_dir: crate::paths::start_menu_folder(),
+            config_dir: crate::paths::config_dir(),
+            data_dir: crate::paths::local_data_dir(),
+        }
+    }
+
+    pub fn exe(&self) -> PathBuf {
+        self.program_dir.join(EXE_NAME)
+    }
+
+    pub fn shortcut(&self) -> PathBuf {
+        self.start_menu_dir.join(SHORTCUT_NAME)
+    }
+}
+
+/// Copy `source_exe` into the program directory and create (or remove) the
+/// Start menu shortcut; returns the installed executable
+pub fn install_files(
+    layout: &Layout,
+    source_exe: &Path,
+    start_menu_shortcut: bool,
+) -> Result<PathBuf, String> {
+    let exe = layout.exe();
+    std::fs::create_dir_all(&layout.program_dir)
+        .map_err(|e| format!("Failed to create {}: {}", layout.program_dir.display(), e))?;
+    // Re-running --install from the installed copy only refreshes the rest
+    if !startup::same_path(source_exe, &exe) {
+        std::fs::copy(source_exe, &exe)
+            .map_err(|e| format!("Failed to copy to {}: {}", exe.display(), e))?;
+    }
+
+    let shortcut_path = layout.shortcut();
+    if start_menu_shortcut {
+        std::fs::create_dir_all(&layout.start_menu_dir).map_err(|e| {
+            format!(
+                "Failed to create {}: {}",
+                layout.start_menu_dir.display(),
+                e
+            )
+        })?;
+        let shortcut = Shortcut {
+            description: Some("KakaoTalkAdBlock".to_string()),
+            ..Shortcut::new(&exe, "")
+        };
+        std::fs::write(&shortcut_path, shortcut.to_bytes())
+            .map_err(|e| format!("Failed to write {}: {}", shortcut_path.display(), e))?;
+    } else {
+        remove_path(&shortcut_path)?;
+    }
+
+    Ok(exe)
+}
+
+/// Remove the shortcut, settings, data and the program directory, except for
+/// `keep` (the running executable, which cannot be deleted until it exits)
+///
+/// Returns the errors of the steps that failed.
+pub fn remove_files(layout: &Layout, keep: &Path) -> Vec<String> {
+    let mut errors: Vec<String> = [&layout.shortcut(), &layout.config_dir, &layout.data_dir]
+        .into_iter()
+        .filter_map(|path| remove_path(path).err())
+        .collect();
+
+    if let Ok(entries) = std::fs::read_dir(&layout.program_dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if !startup::same_path(&path, keep) {
+                errors.extend(remove_path(&path).err());
+            }
+        }
+        // Fails while `keep` is still inside; the self-delete command retries
+        let _ = std::fs::remove_dir(&layout.program_dir);
+    }
+
+    errors
+}
+
+/// Remove a file or directory tree; a missing path counts as removed
+fn remove_path(path: &Path) -> Result<(), String> {
+    let result = if path.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    };
+    match result {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(format!("Failed to remove {}: {}", path.display(), e))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// `cmd.exe` arguments deleting `exe`, and its directory if that is left
+/// empty, a few seconds after this process exits
+pub fn self_delete_command(exe: &Path) -> String {
+    let dir = exe.parent().unwrap_or(Path::new(""));
+    format!(
+        r#"/C ping -n 4 127.0.0.1 > nul & del /f /q "{}" & rmdir /q "{}""#,
+        exe.display(),
+        dir.display()
+    )
+}
+
+/// Stop other running copies of this app
+///
+/// The instance in this session is asked to quit over its pipe first. Copies
+/// still running from the installed or this executable are then terminated,
+/// as they would keep the files locked.
+#[cfg(windows)]
+pub fn stop_running_instances(layout: &Layout) {
+    use crate::{ipc, logger, process};
+
+    let own_pid = std::process::id();
+    if let Ok(pipe) = ipc::connect() {
+        let server_pid = ipc::server_process_id(&pipe);
+        match ipc::send(pipe, ipc::Command::Quit) {
+            Ok(_) => {
+                if let Some(pid) = server_pid.filter(|&pid| pid != own_pid) {
+                    process::wait_for_exit(pid, EXIT_TIMEOUT);
+                }
+            }
+            Err(e) => logger::log(format!("Running instance did not quit: {}", e)),
+        }
+    }
+
+    let executables = [layout.exe(), std::env::current_exe().unwrap_or_default()];
+    let names: Vec<String> = executables
+        .iter()
+        .filter_map(|exe| Some(exe.file_name()?.to_string_lossy().to_lowercase()))
+        .collect();
+    let pids = process::find_process_ids_where(|name| names.iter().any(|n| n == name));
+    for pid in pids.into_iter().filter(|&pid| pid != own_pid) {
+        let Some(image) = process::get_process_image_path(pid) else {
+            continue;
+        };
+        if !is_instance_image(Path::new(&image), &executables) {
+            continue;
+        }
+        match process::terminate(pid) {
+            Ok(()) => {
+                process::wait_for_exit(pid, EXIT_TIMEOUT);
+            }
+            Err(e) => logger::log(e),
+        }
+    }
+}
+
+/// Whether a process image is one of `executables`
+fn is_instance_image(image: &Path, executables: &[PathBuf]) -> bool {
+    executables.iter().any(|exe| startup::same_path(image, exe))
+}
+
+/// Install this executable for the current user and start at logon from the
+/// installed copy; returns the installed executable
+#[cfg(windows)]
+pub fn install(
+    layout: &Layout,
+    start_menu_shortcut: bool,
+    settings: &crate::settings::Settings,
+) -> Result<PathBuf, String> {
+    let current_exe =
+        std::env::current_exe().map_err(|e| format!("Failed to locate executable: {}", e))?;
+    // A running installed copy would keep its file locked
+    stop_running_instances(layout);
+    let exe = install_files(layout, &current_exe, start_menu_shortcut)?;
+    startup::enable_for(
+        &exe,
+        settings.startup_method,
+        std::time::Duration::from_secs(settings.startup_delay_secs),
+    )?;
+    Ok(exe)
+}
+
+/// Stop the app and remove everything it created except this executable;
+/// returns the errors of the steps that failed
+#[cfg(windows)]
+pub fn uninstall(layout: &Layout) -> Vec<String> {
+    stop_running_instances(layout);
+    let mut errors: Vec<String> = startup::disable().err().into_iter().collect();
+    let current_exe = std::env::current_exe().unwrap_or_default();
+    errors.extend(remove_files(layout, &current_exe));
+    errors
+}
+
+/// Delete this executable after it exits, if it is the installed copy
+#[cfg(windows)]
+pub fn delete_after_exit(layout: &Layout) -> Result<(), String> {
+    use std::os::windows::process::CommandExt;
+
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    let current_exe =
+        std::env::current_exe().map_err(|e| format!("Failed to locate executable: {}", e))?;
+    if !startup::same_path(&current_exe, &layout.exe()) {
+        return Ok(());
+    }
+    std::process::Command::new("cmd.exe")
+        .raw_arg(self_delete_command(&current_exe))
+        .creation_flags(CREATE_NO_WINDOW)
+        .spawn()
+        .map(|_| ())
+        .map_err(|e| format!("Failed to schedule deleting the executable: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_args::args;
+    use crate::test_dir::TestDir;
+
+    fn temp_layout(name: &str) -> (TestDir, Layout) {
+        let root = TestDir::new(&format!("install_{}", name));
+        let layout = Layout {
+            program_dir: root.join("Programs").join("KakaoTalkAdBlock"),
+            start_menu_dir: root.join("Start Menu"),
+            config_dir: root.join("Roaming").join("KakaoTalkAdBlock"),
+            data_dir: root.join("Local").join("KakaoTalkAdBlock"),
+        };
+        (root, layout)
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(parse_args(args(&[])), None);
+        assert_eq!(parse_args(args(&["--minimized"])), None);
+        assert_eq!(
+            parse_args(args(&["--install"])),
+            Some(Command::Install {
+                start_menu_shortcut: true
+            })
+        );
+        assert_eq!(
+            parse_args(args(&["--no-shortcut", "--INSTALL"])),
+            Some(Command::Install {
+                start_menu_shortcut: false
+            })
+        );
+        assert_eq!(parse_args(args(&["--uninstall"])), Some(Command::Uninstall));
+    }
+
+    #[test]
+    fn test_install_then_uninstall() {
+        let (root, layout) = temp_layout("round_trip");
+        let download = root.join("KakaoTalkAdBlock-20250101-01.exe");
+        std::fs::write(&download, b"MZ new build").unwrap();
+
+        let exe = install_files(&layout, &download, true).unwrap();
+        assert_eq!(exe, layout.exe());
+        assert_eq!(std::fs::read(&exe).unwrap(), b"MZ new build");
+        let shortcut = Shortcut::parse(&std::fs::read(layout.shortcut()).unwrap()).unwrap();
+        assert_eq!(shortcut.target, exe);
+
+        // Reinstalling from the installed copy without a shortcut removes it
+        install_files(&layout, &exe, false).unwrap();
+        assert!(exe.exists());
+        assert!(!layout.shortcut().exists());
+
+        // Settings, data and leftovers of an update
+        std::fs::create_dir_all(layout.data_dir.join("reports")).unwrap();
+        std::fs::write(layout.data_dir.join("reports").join("r.zip"), b"zip").unwrap();
+        std::fs::create_dir_all(&layout.config_dir).unwrap();
+        std::fs::write(layout.config_dir.join("settings.json"), b"{}").unwrap();
+        std::fs::write(layout.program_dir.join("KakaoTalkAdBlock.exe.old"), b"MZ").unwrap();
+        install_files(&layout, &download, true).unwrap();
+
+        assert!(remove_files(&layout, &exe).is_empty());
+        assert!(!layout.shortcut().exists());
+        assert!(!layout.config_dir.exists());
+        assert!(!layout.data_dir.exists());
+        // Only the running executable is left for the self-delete command
+        let left: Vec<PathBuf> = std::fs::read_dir(&layout.program_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(left, vec![exe.clone()]);
+
+        // Nothing left to remove is not an error
+        std::fs::remove_file(&exe).unwrap();
+        assert!(remove_files(&layout, &exe).is_empty());
+        assert!(!layout.program_dir.exists());
+    }
+
+    #[test]
+    fn test_install_reports_copy_failures() {
+        let (root, layout) = temp_layout("missing_source");
+        let err = install_files(&layout, &root.join("missing.exe"), false).unwrap_err();
+        assert!(err.starts_with("Failed to copy to "));
+    }
+
+    #[test]
+    fn test_is_instance_image() {
+        let executables = [
+            PathBuf::from(
+                r"C:\Users\Kim\AppData\Local\Programs\KakaoTalkAdBlock\KakaoTalkAdBlock.exe",
+            ),
+            PathBuf::from(r"C:\Users\Kim\Downloads\KakaoTalkAdBlock-20250101-01.exe"),
+        ];
+        assert!(is_instance_image(
+            Path::new(r"c:\users\kim\appdata\local\programs\kakaotalkadblock\KAKAOTALKADBLOCK.EXE"),
+            &executables
+        ));
+        assert!(is_instance_image(
+            Path::new(r"C:\Users\Kim\Downloads\KakaoTalkAdBlock-20250101-01.exe"),
+            &executables
+        ));
+        // Same name elsewhere, or a different program with a similar name
+        assert!(!is_instance_image(
+            Path::new(r"C:\Tools\KakaoTalkAdBlock.exe"),
+            &executables
+        ));
+        assert!(!is_instance_image(
+            Path::new(
+                r"C:\Users\Kim\AppData\Local\Programs\KakaoTalkAdBlock\KakaoTalkAdBlockHelper.exe"
+            ),
+            &executables
+        ));
+    }
+
+    #[test]
+    fn test_self_delete_command() {
+        let exe =
+            Path::new(r"C:\Users\Kim\AppData\Local\Programs\KakaoTalkAdBlock\KakaoTalkAdBlock.exe");
+        let command = self_delete_command(exe);
+        assert!(command.starts_with("/C ping "));
+        assert!(command.contains(&format!("del /f /q \"{}\"", exe.display())));
+        assert!(command.ends_with(&format!("rmdir /q \"{}\"", exe.parent().unwrap().display())));
+    }
+}
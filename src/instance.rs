@@ -0,0 +1,129 @@
+//! One running instance per logon session
+//!
+//! Each user on a shared PC (fast user switching, RDP) gets their own
+//! instance, guarded by a mutex in the session's `Local\` namespace. Versions
+//! before this used a machine-wide `Global\` mutex. Another user's copy of
+//! such a version may leave that mutex behind, which must not stop this
+//! session from blocking ads. Only an old copy running in this very session
+//! still counts as an instance.
+
+/// Mutex held by the running instance of this session
+pub const INSTANCE_MUTEX: &str = "Local\\KakaoTalkAdBlock_SingleInstance";
+
+/// Machine-wide mutex of older versions
+pub const LEGACY_GLOBAL_MUTEX: &str = "Global\\KakaoTalkAdBlock_SingleInstance";
+
+/// Outcome of the single-instance check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instance {
+    /// This is the session's instance
+    First,
+    /// Another instance of this session is running
+    AlreadyRunning,
+    /// An older version is running in this session; it cannot take commands
+    OlderVersionRunning,
+}
+
+impl Instance {
+    /// Decide from what was found
+    ///
+    /// `global_mutex_exists` alone proves nothing, as another session may own
+    /// it; it only matters together with an old copy in this session.
+    pub fn resolve(
+        acquired: bool,
+        global_mutex_exists: bool,
+        older_version_in_session: bool,
+    ) -> Self {
+        if !acquired {
+            Instance::AlreadyRunning
+        } else if global_mutex_exists && older_version_in_session {
+            Instance::OlderVersionRunning
+        } else {
+            Instance::First
+        }
+    }
+}
+
+/// Take the session's instance mutex, which is then held until exit
+#[cfg(windows)]
+pub fn acquire() -> Instance {
+    use windows::core::HSTRING;
+    use windows::Win32::Foundation::{
+        CloseHandle, ERROR_ACCESS_DENIED, WAIT_ABANDONED, WAIT_OBJECT_0,
+    };
+    use windows::Win32::System::Threading::{
+        CreateMutexW, OpenMutexW, WaitForSingleObject, SYNCHRONIZATION_SYNCHRONIZE,
+    };
+
+    let acquired = match unsafe { CreateMutexW(None, true, &HSTRING::from(INSTANCE_MUTEX)) } {
+        // Owned already if just created; otherwise wait for it without
+        // blocking. An abandoned mutex is left over from a crashed instance.
+        // The handle is kept (and released by the OS) until exit.
+        Ok(handle) => {
+            let wait = unsafe { WaitForSingleObject(handle, 0) };
+            wait == WAIT_OBJECT_0 || wait == WAIT_ABANDONED
+        }
+        Err(_) => false,
+    };
+
+    let global_mutex_exists = match unsafe {
+        OpenMutexW(
+            SYNCHRONIZATION_SYNCHRONIZE,
+            false,
+            &HSTRING::from(LEGACY_GLOBAL_MUTEX),
+        )
+    } {
+        Ok(handle) => {
+            let _ = unsafe { CloseHandle(handle) };
+            true
+        }
+        // Created by another user
+        Err(e) => e.code() == ERROR_ACCESS_DENIED.to_hresult(),
+    };
+    if acquired && global_mutex_exists {
+        crate::logger::log("An older version holds the machine-wide instance mutex");
+    }
+
+    Instance::resolve(
+        acquired,
+        global_mutex_exists,
+        acquired && global_mutex_exists && older_version_in_session(),
+    )
+}
+
+/// Whether another copy of this app (not the Go version) runs in this session
+#[cfg(windows)]
+fn older_version_in_session() -> bool {
+    let current_exe = std::env::current_exe().unwrap_or_default();
+    crate::process::find_process_ids_where(|name| {
+        crate::legacy::has_blocker_name(std::path::Path::new(name))
+    })
+    .into_iter()
+    .filter(|&pid| pid != std::process::id())
+    .filter_map(crate::process::get_process_image_path)
+    .any(|exe| !crate::legacy::is_legacy_executable(std::path::Path::new(&exe), &current_exe))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(Instance::resolve(true, false, false), Instance::First);
+        assert_eq!(
+            Instance::resolve(false, false, false),
+            Instance::AlreadyRunning
+        );
+        assert_eq!(
+            Instance::resolve(false, true, true),
+            Instance::AlreadyRunning
+        );
+        // Left behind by another user's session
+        assert_eq!(Instance::resolve(true, true, false), Instance::First);
+        assert_eq!(
+            Instance::resolve(true, true, true),
+            Instance::OlderVersionRunning
+        );
+    }
+}
@@ -0,0 +1,423 @@
+//! Commands from a second launch to the running instance
+//!
+//! Launching the app again connects to the running instance and forwards what
+//! was asked for on the command line. The protocol is one request line and a
+//! response that runs until the connection closes, all UTF-8:
+//!
+//! ```text
+//! -> KakaoTalkAdBlock/1 pause
+//! <- ok
+//! <- Blocking paused
+//! ```
+//!
+//! The first response line is `ok` or `error`, the rest a message for the user.
+//! On Windows the transport is a named pipe; the protocol itself works over any
+//! stream, which keeps it testable with local sockets elsewhere.
+
+use std::io::{Read, Write};
+use std::time::Duration;
+
+/// First word of every request, so other clients of the pipe are rejected
+const PROTOCOL: &str = "KakaoTalkAdBlock/1";
+
+/// Longest request line accepted
+const MAX_REQUEST_LEN: usize = 256;
+
+/// How long the running instance may take to carry out a command
+pub const RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What a second launch can ask the running instance to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    ShowDebugWindow,
+    Pause,
+    Resume,
+    Quit,
+    /// Forget what was decided about each window and classify them again
+    ReloadRules,
+    Status,
+}
+
+impl Command {
+    pub const ALL: [Command; 6] = [
+        Command::ShowDebugWindow,
+        Command::Pause,
+        Command::Resume,
+        Command::Quit,
+        Command::ReloadRules,
+        Command::Status,
+    ];
+
+    /// Name on the wire; the command-line flag is this with `--` in front
+    pub fn name(self) -> &'static str {
+        match self {
+            Command::ShowDebugWindow => "show-debug",
+            Command::Pause => "pause",
+            Command::Resume => "resume",
+            Command::Quit => "quit",
+            Command::ReloadRules => "reload-rules",
+            Command::Status => "status",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|command| command.name() == name)
+    }
+}
+
+/// The command given on the command line, if any
+pub fn parse_args(args: impl Iterator<Item = String>) -> Option<Command> {
+    let args: Vec<String> = args.collect();
+    Command::ALL.into_iter().find(|command| {
+        args.iter().any(|arg| {
+            arg.strip_prefix("--")
+                .is_some_and(|name| name.eq_ignore_ascii_case(command.name()))
+        })
+    })
+}
+
+/// Send `command` and wait for the instance's answer
+pub fn send(mut stream: impl Read + Write, command: Command) -> Result<String, String> {
+    stream
+        .write_all(format!("{} {}\n", PROTOCOL, command.name()).as_bytes())
+        .and_then(|_| stream.flush())
+        .map_err(|e| format!("Failed to send command: {}", e))?;
+
+    let mut response = String::new();
+    stream
+        .read_to_string(&mut response)
+        .map_err(|e| format!("Failed to read response: {}", e))?;
+    let (status, message) = response.split_once('\n').unwrap_or((&response, ""));
+    let message = message.trim_end().to_string();
+    match status {
+        "ok" => Ok(message),
+        "error" => Err(message),
+        _ => Err(format!("Unexpected response: {:?}", response)),
+    }
+}
+
+/// Read one request, let `handle` carry it out and write back the result
+///
+/// Requests that cannot be parsed are answered with an error and never reach
+/// `handle`.
+pub fn serve(
+    mut stream: impl Read + Write,
+    handle: impl FnOnce(Command) -> Result<String, String>,
+) -> Result<(), String> {
+    let result = read_request(&mut stream).and_then(handle);
+    let response = match &result {
+        Ok(message) => format!("ok\n{}\n", message),
+        Err(message) => format!("error\n{}\n", message),
+    };
+    stream
+        .write_all(response.as_bytes())
+        .and_then(|_| stream.flush())
+        .map_err(|e| format!("Failed to send response: {}", e))
+}
+
+fn read_request(stream: &mut impl Read) -> Result<Command, String> {
+    let mut line = Vec::new();
+    let mut byte = [0u8];
+    loop {
+        match stream.read(&mut byte) {
+            Ok(0) => break,
+            Ok(_) if byte[0] == b'\n' => break,
+            Ok(_) if line.len() >= MAX_REQUEST_LEN => return Err("Request too long".into()),
+            Ok(_) => line.push(byte[0]),
+            Err(e) => return Err(format!("Failed to read request: {}", e)),
+        }
+    }
+
+    let line = String::from_utf8_lossy(&line);
+    let name = line
+        .trim_end_matches('\r')
+        .strip_prefix(PROTOCOL)
+        .and_then(|rest| rest.strip_prefix(' '))
+        .ok_or_else(|| format!("Unsupported request: {:?}", line))?;
+    Command::from_name(name).ok_or_else(|| format!("Unknown command: {}", name))
+}
+
+/// A command received by [`listen`], to be carried out on the main thread
+#[cfg(windows)]
+pub struct Request {
+    pub command: Command,
+    reply: std::sync::mpsc::Sender<Result<String, String>>,
+    sent: std::sync::mpsc::Receiver<()>,
+}
+
+#[cfg(windows)]
+impl Request {
+    /// Answer the client, waiting until the answer is on its way
+    pub fn respond(self, result: Result<String, String>) {
+        if self.reply.send(result).is_ok() {
+            let _ = self.sent.recv_timeout(RESPONSE_TIMEOUT);
+        }
+    }
+}
+
+/// Pipe of the instance in logon session `session_id`
+///
+/// Pipe names are machine-wide, so every session's instance needs its own.
+pub fn pipe_name(session_id: u32) -> String {
+    format!(r"\\.\pipe\KakaoTalkAdBlock-{}", session_id)
+}
+
+#[cfg(windows)]
+fn current_pipe_name() -> String {
+    pipe_name(crate::process::current_session_id().unwrap_or_default())
+}
+
+/// Connect to the running instance
+#[cfg(windows)]
+pub fn connect() -> Result<std::fs::File, String> {
+    // The instance is still starting, or busy serving another client
+    const ERROR_FILE_NOT_FOUND: i32 = 2;
+    const ERROR_PIPE_BUSY: i32 = 231;
+
+    let name = current_pipe_name();
+    let mut attempts = 0;
+    loop {
+        match std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&name)
+        {
+            Ok(pipe) => return Ok(pipe),
+            Err(e)
+                if matches!(
+                    e.raw_os_error(),
+                    Some(ERROR_FILE_NOT_FOUND | ERROR_PIPE_BUSY)
+                ) && attempts < 20 =>
+            {
+                attempts += 1;
+                std::thread::sleep(Duration::from_millis(50));
+            }
+            Err(e) => return Err(format!("Failed to connect to {}: {}", name, e)),
+        }
+    }
+}
+
+/// Process ID of the instance at the other end of `pipe`
+#[cfg(windows)]
+pub fn server_process_id(pipe: &std::fs::File) -> Option<u32> {
+    use std::os::windows::io::AsRawHandle;
+    use windows::Win32::Foundation::HANDLE;
+    use windows::Win32::System::Pipes::GetNamedPipeServerProcessId;
+
+    let mut pid = 0;
+    unsafe { GetNamedPipeServerProcessId(HANDLE(pipe.as_raw_handle()), &mut pid) }.ok()?;
+    Some(pid)
+}
+
+/// Accept clients on a background thread and pass their commands to `requests`
+#[cfg(windows)]
+pub fn listen(requests: std::sync::mpsc::Sender<Request>) {
+    std::thread::spawn(move || loop {
+        if let Err(e) = accept(&requests) {
+            crate::logger::log(e);
+            std::thread::sleep(Duration::from_secs(1));
+        }
+    });
+}
+
+/// Wait for the next client of the pipe and serve it on its own thread, so a
+/// client that never sends its request holds up nobody else
+#[cfg(windows)]
+fn accept(requests: &std::sync::mpsc::Sender<Request>) -> Result<(), String> {
+    use std::os::windows::io::FromRawHandle;
+    use windows::core::PCWSTR;
+    use windows::Win32::Foundation::ERROR_PIPE_CONNECTED;
+    use windows::Win32::Storage::FileSystem::PIPE_ACCESS_DUPLEX;
+    use windows::Win32::System::Pipes::{
+        ConnectNamedPipe, CreateNamedPipeW, PIPE_READMODE_BYTE, PIPE_REJECT_REMOTE_CLIENTS,
+        PIPE_TYPE_BYTE, PIPE_UNLIMITED_INSTANCES, PIPE_WAIT,
+    };
+
+    let name: Vec<u16> = current_pipe_name()
+        .encode_utf16()
+        .chain(std::iter::once(0))
+        .collect();
+    let handle = unsafe {
+        CreateNamedPipeW(
+            PCWSTR(name.as_ptr()),
+            PIPE_ACCESS_DUPLEX,
+            PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+            PIPE_UNLIMITED_INSTANCES,
+            4096,
+            4096,
+            0,
+            None,
+        )
+    };
+    if handle.is_invalid() {
+        return Err(format!(
+            "Failed to create pipe: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    // Closes the handle when done
+    let pipe = unsafe { std::fs::File::from_raw_handle(handle.0) };
+
+    if let Err(e) = unsafe { ConnectNamedPipe(handle, None) } {
+        if e.code() != ERROR_PIPE_CONNECTED.to_hresult() {
+            return Err(format!("Failed to accept pipe client: {}", e));
+        }
+    }
+
+    let requests = requests.clone();
+    std::thread::spawn(move || {
+        if let Err(e) = serve_client(pipe, &requests) {
+            crate::logger::log(e);
+        }
+    });
+    Ok(())
+}
+
+/// Serve one connected client of the pipe
+#[cfg(windows)]
+fn serve_client(
+    pipe: std::fs::File,
+    requests: &std::sync::mpsc::Sender<Request>,
+) -> Result<(), String> {
+    use std::os::windows::io::AsRawHandle;
+    use std::sync::mpsc;
+    use windows::Win32::Foundation::HANDLE;
+    use windows::Win32::Storage::FileSystem::FlushFileBuffers;
+
+    let (sent_tx, sent_rx) = mpsc::channel();
+    let result = serve(&pipe, |command| {
+        let (reply_tx, reply_rx) = mpsc::channel();
+        requests
+            .send(Request {
+                command,
+                reply: reply_tx,
+                sent: sent_rx,
+            })
+            .map_err(|_| "Shutting down".to_string())?;
+        reply_rx
+            .recv_timeout(RESPONSE_TIMEOUT)
+            .unwrap_or_else(|_| Err("No response from the running instance".to_string()))
+    });
+    // Closing (rather than disconnecting) our end lets the client read to the end
+    unsafe {
+        let _ = FlushFileBuffers(HANDLE(pipe.as_raw_handle()));
+    }
+    drop(pipe);
+    let _ = sent_tx.send(());
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_args::args;
+    use std::net::{Ipv4Addr, TcpListener, TcpStream};
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(parse_args(args(&["app.exe"])), None);
+        assert_eq!(parse_args(args(&["app.exe", "--minimized"])), None);
+        assert_eq!(
+            parse_args(args(&["app.exe", "--PAUSE"])),
+            Some(Command::Pause)
+        );
+        for command in Command::ALL {
+            let flag = format!("--{}", command.name());
+            assert_eq!(parse_args(args(&["app.exe", &flag])), Some(command));
+            assert_eq!(Command::from_name(command.name()), Some(command));
+        }
+        // A bare name is not a flag
+        assert_eq!(parse_args(args(&["app.exe", "quit"])), None);
+    }
+
+    #[test]
+    fn test_pipe_name_per_session() {
+        assert_eq!(pipe_name(1), r"\\.\pipe\KakaoTalkAdBlock-1");
+        assert_ne!(pipe_name(1), pipe_name(2));
+    }
+
+    /// A loopback listener serving `count` clients with `handle` on a
+    /// background thread
+    fn server(
+        count: usize,
+        handle: impl Fn(Command) -> Result<String, String> + Send + 'static,
+    ) -> (u16, std::thread::JoinHandle<Vec<Result<(), String>>>) {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let thread = std::thread::spawn(move || {
+            listener
+                .incoming()
+                .take(count)
+                .map(|stream| serve(stream.unwrap(), &handle))
+                .collect()
+        });
+        (port, thread)
+    }
+
+    fn connect(port: u16) -> TcpStream {
+        TcpStream::connect((Ipv4Addr::LOCALHOST, port)).unwrap()
+    }
+
+    #[test]
+    fn test_commands_round_trip() {
+        let (port, thread) = server(Command::ALL.len() + 1, |command| match command {
+            Command::Status => Ok("status: active\npaused: false".to_string()),
+            Command::ReloadRules => Err("Rules are busy".to_string()),
+            command => Ok(format!("did {}", command.name())),
+        });
+
+        for command in Command::ALL {
+            let expected = match command {
+                Command::Status => Ok("status: active\npaused: false".to_string()),
+                Command::ReloadRules => Err("Rules are busy".to_string()),
+                command => Ok(format!("did {}", command.name())),
+            };
+            assert_eq!(send(connect(port), command), expected, "{:?}", command);
+        }
+
+        // Other clients are turned away without reaching the handler
+        let mut stream = connect(port);
+        stream.write_all(b"GET / HTTP/1.1\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("error\nUnsupported request"));
+
+        assert!(thread.join().unwrap().iter().all(Result::is_ok));
+    }
+
+    #[test]
+    fn test_rejects_bad_requests() {
+        let (port, thread) = server(2, |_| panic!("must not be called"));
+
+        let mut stream = connect(port);
+        stream.write_all(b"KakaoTalkAdBlock/1 format-c\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert_eq!(response, "error\nUnknown command: format-c\n");
+
+        let mut stream = connect(port);
+        // Everything written is read, so closing does not reset the connection
+        stream.write_all(&[b'x'; MAX_REQUEST_LEN + 1]).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert_eq!(response, "error\nRequest too long\n");
+
+        thread.join().unwrap();
+    }
+
+    #[test]
+    fn test_unexpected_response() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let thread = std::thread::spawn(move || {
+            let (mut server, _) = listener.accept().unwrap();
+            let mut request = [0u8; 32];
+            let n = server.read(&mut request).unwrap();
+            assert_eq!(&request[..n], b"KakaoTalkAdBlock/1 quit\n");
+            server.write_all(b"HTTP/1.1 400\r\n").unwrap();
+        });
+        let error = send(connect(port), Command::Quit).unwrap_err();
+        assert!(error.starts_with("Unexpected response"), "{}", error);
+        thread.join().unwrap();
+    }
+}
@@ -0,0 +1,324 @@
+//! Migration from the original Go KakaoTalkAdBlock
+//!
+//! Many users still have blurfx/KakaoTalkAdBlock starting at logon, and two
+//! blockers running at once fight over the same windows. Its executables are
+//! recognised by name and by the build info the Go toolchain embeds in every
+//! binary, so renamed copies of this app are never mistaken for it.
+//!
+//! The Go version keeps no settings besides starting at logon, which is the
+//! only thing carried over.
+
+use std::path::{Path, PathBuf};
+
+use crate::registry::Registry;
+use crate::shortcut::Shortcut;
+use crate::startup;
+
+/// Markers the Go linker writes into every executable
+const GO_BUILD_MARKERS: &[&[u8]] = &[b"\xff Go buildinf:", b"Go build ID: \""];
+
+/// How the old version starts at logon
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LegacyEntry {
+    /// A value in the per-user `Run` key
+    RunValue { name: String, exe: PathBuf },
+    /// A shortcut in the Startup folder
+    Shortcut { path: PathBuf, exe: PathBuf },
+}
+
+impl LegacyEntry {
+    pub fn exe(&self) -> &Path {
+        match self {
+            LegacyEntry::RunValue { exe, .. } | LegacyEntry::Shortcut { exe, .. } => exe,
+        }
+    }
+}
+
+/// Whether an executable was built by the Go toolchain
+pub fn is_go_binary(bytes: &[u8]) -> bool {
+    GO_BUILD_MARKERS
+        .iter()
+        .any(|marker| bytes.windows(marker.len()).any(|w| w == *marker))
+}
+
+/// Whether a file name looks like a KakaoTalkAdBlock build
+/// (`KakaoTalkAdBlock.exe`, `kakaotalk-adblock_v1.2.exe`, ...)
+pub fn has_blocker_name(path: &Path) -> bool {
+    let name: String = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .collect();
+    name.contains("kakaotalkadblock") && name.ends_with("exe")
+}
+
+/// Whether `path` is the Go version (and not this executable)
+pub fn is_legacy_executable(path: &Path, current_exe: &Path) -> bool {
+    has_blocker_name(path)
+        && !startup::same_path(path, current_exe)
+        && std::fs::read(path).is_ok_and(|bytes| is_go_binary(&bytes))
+}
+
+/// Startup entries of the Go version in the `Run` key and the Startup folder
+pub fn find_startup_entries(
+    registry: &dyn Registry,
+    startup_folder: &Path,
+    current_exe: &Path,
+) -> Vec<LegacyEntry> {
+    let mut entries = Vec::new();
+
+    for name in registry
+        .value_names(startup::STARTUP_KEY)
+        .unwrap_or_default()
+    {
+        let command = registry
+            .read_string(startup::STARTUP_KEY, &name)
+            .ok()
+            .flatten();
+        if let Some((exe, _)) = command.as_deref().and_then(startup::parse_command_line) {
+            if is_legacy_executable(&exe, current_exe) {
+                entries.push(LegacyEntry::RunValue { name, exe });
+            }
+        }
+    }
+
+    let shortcuts = std::fs::read_dir(startup_folder)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("lnk"))
+        });
+    for path in shortcuts {
+        let target = std::fs::read(&path)
+            .ok()
+            .and_then(|bytes| Shortcut::parse(&bytes).ok());
+        if let Some(shortcut) = target {
+            if is_legacy_executable(&shortcut.target, current_exe) {
+                entries.push(LegacyEntry::Shortcut {
+                    path,
+                    exe: shortcut.target,
+                });
+            }
+        }
+    }
+
+    entries
+}
+
+/// Remove a startup entry of the Go version
+pub fn remove_startup_entry(registry: &dyn Registry, entry: &LegacyEntry) -> Result<(), String> {
+    match entry {
+        LegacyEntry::RunValue { name, .. } => registry
+            .delete_value(startup::STARTUP_KEY, name)
+            .map_err(|e| format!("Failed to remove startup value {}: {}", name, e)),
+        LegacyEntry::Shortcut { path, .. } => std::fs::remove_file(path)
+            .map_err(|e| format!("Failed to remove {}: {}", path.display(), e)),
+    }
+}
+
+/// What was found of the Go version
+#[derive(Debug, Default)]
+pub struct LegacyInstall {
+    pub startup_entries: Vec<LegacyEntry>,
+    /// Running processes as (pid, executable)
+    pub running: Vec<(u32, PathBuf)>,
+}
+
+impl LegacyInstall {
+    pub fn is_empty(&self) -> bool {
+        self.startup_entries.is_empty() && self.running.is_empty()
+    }
+
+    /// Distinct executables, for showing to the user and deleting
+    pub fn executables(&self) -> Vec<PathBuf> {
+        let mut exes: Vec<PathBuf> = Vec::new();
+        let all = self
+            .startup_entries
+            .iter()
+            .map(LegacyEntry::exe)
+            .chain(self.running.iter().map(|(_, exe)| exe.as_path()));
+        for exe in all {
+            if !exes.iter().any(|known| startup::same_path(known, exe)) {
+                exes.push(exe.to_path_buf());
+            }
+        }
+        exes
+    }
+}
+
+/// Look for the Go version's startup entries and running processes
+#[cfg(windows)]
+pub fn detect() -> LegacyInstall {
+    let current_exe = std::env::current_exe().unwrap_or_default();
+    let running = crate::process::find_process_ids_where(|name| has_blocker_name(Path::new(name)))
+        .into_iter()
+        .filter(|&pid| pid != std::process::id())
+        .filter_map(|pid| {
+            let exe = PathBuf::from(crate::process::get_process_image_path(pid)?);
+            is_legacy_executable(&exe, &current_exe).then_some((pid, exe))
+        })
+        .collect();
+
+    LegacyInstall {
+        startup_entries: find_startup_entries(
+            &crate::registry::CurrentUser,
+            &crate::paths::startup_folder(),
+            &current_exe,
+        ),
+        running,
+    }
+}
+
+/// Stop the Go version, remove its startup entries and delete its executables
+///
+/// Returns the errors of the steps that failed.
+#[cfg(windows)]
+pub fn remove(install: &LegacyInstall) -> Vec<String> {
+    let mut errors = Vec::new();
+
+    for (pid, _) in &install.running {
+        if let Err(e) = crate::process::terminate(*pid) {
+            errors.push(e);
+        }
+        crate::process::wait_for_exit(*pid, std::time::Duration::from_secs(5));
+    }
+    for entry in &install.startup_entries {
+        if let Err(e) = remove_startup_entry(&crate::registry::CurrentUser, entry) {
+            errors.push(e);
+        }
+    }
+    for exe in install.executables() {
+        if let Err(e) = std::fs::remove_file(&exe) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                errors.push(format!("Failed to delete {}: {}", exe.display(), e));
+            }
+        }
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::MemoryRegistry;
+    use crate::test_dir::TestDir;
+
+    /// A fake executable with or without Go build info
+    fn fake_exe(dir: &Path, name: &str, go: bool) -> PathBuf {
+        let path = dir.join(name);
+        let mut bytes = b"MZ\x90\x00 some code".to_vec();
+        if go {
+            bytes.extend_from_slice(b"\x00\xff Go buildinf:\x08\x02 more");
+        }
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_recognises_go_builds_by_name_and_content() {
+        let dir = TestDir::new("legacy_recognise");
+        let go = fake_exe(&dir, "KakaoTalkAdBlock.exe", true);
+        let rust = fake_exe(&dir, "KakaoTalkAdBlock-20250101-01.exe", false);
+        let other_go = fake_exe(&dir, "hugo.exe", true);
+        let current = dir.join("current.exe");
+
+        assert!(is_legacy_executable(&go, &current));
+        assert!(!is_legacy_executable(&rust, &current));
+        assert!(!is_legacy_executable(&other_go, &current));
+        assert!(!is_legacy_executable(
+            &dir.join("kakaotalk-adblock.exe"),
+            &current
+        ));
+        // Never this executable, whatever it is built with
+        assert!(!is_legacy_executable(&go, &go));
+
+        assert!(has_blocker_name(Path::new("kakaotalk_adblock_v1.2.EXE")));
+        assert!(is_go_binary(b"..Go build ID: \"abc/def\"..."));
+    }
+
+    #[test]
+    fn test_finds_and_removes_startup_entries() {
+        let dir = TestDir::new("legacy_entries");
+        let go = fake_exe(&dir, "KakaoTalkAdBlock.exe", true);
+        let current = fake_exe(&dir, "KakaoTalkAdBlock-new.exe", false);
+        let startup_folder = dir.join("Startup");
+        std::fs::create_dir_all(&startup_folder).unwrap();
+
+        let registry = MemoryRegistry::new();
+        registry
+            .write_string(
+                startup::STARTUP_KEY,
+                "KakaoTalkAdBlock",
+                &go.to_string_lossy(),
+            )
+            .unwrap();
+        registry
+            .write_string(
+                startup::STARTUP_KEY,
+                "Ours",
+                &startup::command_line(&current, &["--minimized"]),
+            )
+            .unwrap();
+        let shortcut = startup_folder.join("AdBlock.lnk");
+        std::fs::write(&shortcut, Shortcut::new(&go, "").to_bytes()).unwrap();
+        std::fs::write(
+            startup_folder.join("Other.lnk"),
+            Shortcut::new(&current, "").to_bytes(),
+        )
+        .unwrap();
+
+        let entries = find_startup_entries(&registry, &startup_folder, &current);
+        assert_eq!(
+            entries,
+            vec![
+                LegacyEntry::RunValue {
+                    name: "KakaoTalkAdBlock".to_string(),
+                    exe: go.clone()
+                },
+                LegacyEntry::Shortcut {
+                    path: shortcut.clone(),
+                    exe: go.clone()
+                },
+            ]
+        );
+
+        let install = LegacyInstall {
+            startup_entries: entries.clone(),
+            running: vec![(42, go.clone())],
+        };
+        assert_eq!(install.executables(), vec![go.clone()]);
+
+        for entry in &entries {
+            remove_startup_entry(&registry, entry).unwrap();
+        }
+        assert!(find_startup_entries(&registry, &startup_folder, &current).is_empty());
+        assert!(registry
+            .read_string(startup::STARTUP_KEY, "Ours")
+            .unwrap()
+            .is_some());
+        assert!(!shortcut.exists());
+    }
+
+    #[test]
+    fn test_access_denied_hides_nothing_else() {
+        let dir = TestDir::new("legacy_denied");
+        let registry = MemoryRegistry::new();
+        registry.deny_access(startup::STARTUP_KEY);
+
+        // Unreadable Run key: nothing found, and no panic
+        assert!(find_startup_entries(&registry, &dir.join("missing"), &dir).is_empty());
+        let entry = LegacyEntry::RunValue {
+            name: "KakaoTalkAdBlock".to_string(),
+            exe: dir.join("KakaoTalkAdBlock.exe"),
+        };
+        assert!(remove_startup_entry(&registry, &entry)
+            .unwrap_err()
+            .contains("access denied"));
+    }
+}
@@ -1,57 +1,336 @@
-//! Localization support - Korean and English based on OS language
+//! Localization support
+//!
+//! Translations live in `src/locales/<code>.json` (flat message ID -> text maps)
+//! and are embedded at compile time. Lookups fall back from the requested
+//! language to the system language and finally to English.
 
-use windows::Win32::Globalization::GetUserDefaultUILanguage;
+use std::collections::HashMap;
 
-/// Language identifiers
-const LANG_KOREAN: u16 = 0x12; // Korean primary language ID
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+
+/// Language used when nothing else matches
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+/// Embedded translations as (language code, JSON source)
+const LOCALE_SOURCES: &[(&str, &str)] = &[
+    ("en", include_str!("locales/en.json")),
+    ("ko", include_str!("locales/ko.json")),
+    ("ja", include_str!("locales/ja.json")),
+    ("zh", include_str!("locales/zh.json")),
+];
+
+/// Primary language IDs (lower 10 bits of a LANGID)
+const LANG_CHINESE: u16 = 0x04;
+const LANG_JAPANESE: u16 = 0x11;
+const LANG_KOREAN: u16 = 0x12;
+
+/// Sublanguage IDs (upper 6 bits of a LANGID) of Simplified Chinese locales
+const SUBLANG_NEUTRAL: u16 = 0x00;
+const SUBLANG_CHINESE_SIMPLIFIED: u16 = 0x02;
+const SUBLANG_CHINESE_SINGAPORE: u16 = 0x04;
+
+type Catalog = HashMap<String, String>;
+
+static CATALOGS: Lazy<HashMap<&'static str, Catalog>> = Lazy::new(|| {
+    LOCALE_SOURCES
+        .iter()
+        .map(|&(code, source)| {
+            let catalog: Catalog = serde_json::from_str(source)
+                .unwrap_or_else(|e| panic!("Invalid locale file {}.json: {}", code, e));
+            (code, catalog)
+        })
+        .collect()
+});
+
+/// Resolved strings per requested language ("" = follow the system)
+static LOADED_STRINGS: Lazy<Mutex<HashMap<String, &'static Strings>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
 
 /// Localized strings
 pub struct Strings {
     pub run_on_startup: &'static str,
     pub run_on_startup_checked: &'static str,
+    pub startup_repair_prompt: &'static str,
+    pub startup_repair_question: &'static str,
+    pub startup_method: &'static str,
+    pub startup_method_registry: &'static str,
+    pub startup_method_task: &'static str,
+    pub startup_method_folder: &'static str,
+    pub legacy_prompt: &'static str,
+    pub legacy_question: &'static str,
+    pub install_done: &'static str,
+    pub install_failed: &'static str,
+    pub uninstall_done: &'static str,
+    pub uninstall_failed: &'static str,
+    pub ipc_not_running: &'static str,
+    pub ipc_unreachable: &'static str,
+    pub ipc_paused: &'static str,
+    pub ipc_resumed: &'static str,
+    pub ipc_rules_reloaded: &'static str,
+    pub ipc_exiting: &'static str,
     pub exit: &'static str,
     pub new_version_available: &'static str,
     pub check_for_updates: &'static str,
+    pub update_check_failed: &'static str,
+    pub installing_update: &'static str,
+    pub update_failed: &'static str,
+    pub view_release_notes: &'static str,
+    pub skip_this_version: &'static str,
+    pub update_channel: &'static str,
+    pub channel_stable: &'static str,
+    pub channel_prerelease: &'static str,
+    pub channel_pinned: &'static str,
+    pub offline_mode: &'static str,
+    pub updates_offline: &'static str,
+    pub pause_blocking: &'static str,
+    pub status_paused: &'static str,
+    pub status_dry_run: &'static str,
+    pub status_not_running: &'static str,
+    pub status_update_available: &'static str,
+    pub status_error: &'static str,
     pub show_debug_window: &'static str,
     pub hide_debug_window: &'static str,
+    pub create_diagnostic_report: &'static str,
+    pub language: &'static str,
+    pub language_auto: &'static str,
+}
+
+impl Strings {
+    /// Resolve every string through the fallback chain for `language`
+    fn load(language: Option<&str>) -> Self {
+        let chain = fallback_chain(language, system_language());
+        Self::resolve(|id| lookup(&chain, id))
+    }
+
+    /// Build the strings from a message lookup
+    fn resolve(mut get: impl FnMut(&'static str) -> &'static str) -> Self {
+        Self {
+            run_on_startup: get("run_on_startup"),
+            run_on_startup_checked: get("run_on_startup_checked"),
+            startup_repair_prompt: get("startup_repair_prompt"),
+            startup_repair_question: get("startup_repair_question"),
+            startup_method: get("startup_method"),
+            startup_method_registry: get("startup_method_registry"),
+            startup_method_task: get("startup_method_task"),
+            startup_method_folder: get("startup_method_folder"),
+            legacy_prompt: get("legacy_prompt"),
+            legacy_question: get("legacy_question"),
+            install_done: get("install_done"),
+            install_failed: get("install_failed"),
+            uninstall_done: get("uninstall_done"),
+            uninstall_failed: get("uninstall_failed"),
+            ipc_not_running: get("ipc_not_running"),
+            ipc_unreachable: get("ipc_unreachable"),
+            ipc_paused: get("ipc_paused"),
+            ipc_resumed: get("ipc_resumed"),
+            ipc_rules_reloaded: get("ipc_rules_reloaded"),
+            ipc_exiting: get("ipc_exiting"),
+            exit: get("exit"),
+            new_version_available: get("new_version_available"),
+            check_for_updates: get("check_for_updates"),
+            update_check_failed: get("update_check_failed"),
+            installing_update: get("installing_update"),
+            update_failed: get("update_failed"),
+            view_release_notes: get("view_release_notes"),
+            skip_this_version: get("skip_this_version"),
+            update_channel: get("update_channel"),
+            channel_stable: get("channel_stable"),
+            channel_prerelease: get("channel_prerelease"),
+            channel_pinned: get("channel_pinned"),
+            offline_mode: get("offline_mode"),
+            updates_offline: get("updates_offline"),
+            pause_blocking: get("pause_blocking"),
+            status_paused: get("status_paused"),
+            status_dry_run: get("status_dry_run"),
+            status_not_running: get("status_not_running"),
+            status_update_available: get("status_update_available"),
+            status_error: get("status_error"),
+            show_debug_window: get("show_debug_window"),
+            hide_debug_window: get("hide_debug_window"),
+            create_diagnostic_report: get("create_diagnostic_report"),
+            language: get("language"),
+            language_auto: get("language_auto"),
+        }
+    }
+}
+
+/// Codes of all embedded languages
+pub fn available_languages() -> impl Iterator<Item = &'static str> {
+    LOCALE_SOURCES.iter().map(|&(code, _)| code)
+}
+
+/// A language's own name (e.g. "한국어"), for the language menu
+pub fn language_name(code: &str) -> &'static str {
+    lookup(&[code], "language_name")
+}
+
+/// Languages to try in order: requested, system, then English
+fn fallback_chain<'a>(requested: Option<&'a str>, system: &'a str) -> Vec<&'a str> {
+    let mut chain = Vec::new();
+    for code in [requested, Some(system), Some(DEFAULT_LANGUAGE)]
+        .into_iter()
+        .flatten()
+    {
+        // Accept region-qualified codes such as "ko-KR"
+        let (code, region) = code.split_once(['-', '_']).unwrap_or((code, ""));
+        if code == "zh" && is_traditional_chinese(region) {
+            continue;
+        }
+        if !chain.contains(&code) {
+            chain.push(code);
+        }
+    }
+    chain
+}
+
+/// Whether the part of a "zh-..." code after "zh" names Traditional Chinese
+fn is_traditional_chinese(region: &str) -> bool {
+    let region = region.to_ascii_lowercase();
+    region.starts_with("hant") || ["tw", "hk", "mo"].contains(&region.as_str())
+}
+
+/// Find a message in the first language of the chain that has it
+fn lookup(chain: &[&str], id: &str) -> &'static str {
+    chain
+        .iter()
+        .chain(std::iter::once(&DEFAULT_LANGUAGE))
+        .filter_map(|&code| CATALOGS.get(code))
+        .find_map(|catalog| catalog.get(id))
+        .map(String::as_str)
+        .unwrap_or("")
 }
 
-/// English strings
-const STRINGS_EN: Strings = Strings {
-    run_on_startup: "Run on startup",
-    run_on_startup_checked: "\u{2713} Run on startup", // ✓
-    exit: "Exit",
-    new_version_available: "New version available: ",
-    check_for_updates: "Check for updates",
-    show_debug_window: "Show debug window",
-    hide_debug_window: "\u{2713} Show debug window", // ✓
-};
-
-/// Korean strings
-const STRINGS_KO: Strings = Strings {
-    run_on_startup: "시작 시 자동 실행",
-    run_on_startup_checked: "\u{2713} 시작 시 자동 실행", // ✓
-    exit: "종료",
-    new_version_available: "새 버전: ",
-    check_for_updates: "업데이트 확인",
-    show_debug_window: "디버그 창 표시",
-    hide_debug_window: "\u{2713} 디버그 창 표시", // ✓
-};
-
-/// Check if the system language is Korean
-pub fn is_korean() -> bool {
-    unsafe {
-        let lang_id = GetUserDefaultUILanguage();
-        // Primary language ID is in the lower 10 bits
-        (lang_id & 0x3FF) == LANG_KOREAN
-    }
-}
-
-/// Get localized strings based on system language
-pub fn get_strings() -> &'static Strings {
-    if is_korean() {
-        &STRINGS_KO
-    } else {
-        &STRINGS_EN
+/// Map a Windows LANGID to one of the embedded language codes
+///
+/// The Chinese catalog is Simplified, so Traditional Chinese locales
+/// (Taiwan, Hong Kong, Macao) get English instead.
+fn language_from_langid(lang_id: u16) -> &'static str {
+    // Primary language ID is in the lower 10 bits, the sublanguage above it
+    match (lang_id & 0x3FF, lang_id >> 10) {
+        (LANG_KOREAN, _) => "ko",
+        (LANG_JAPANESE, _) => "ja",
+        (
+            LANG_CHINESE,
+            SUBLANG_NEUTRAL | SUBLANG_CHINESE_SIMPLIFIED | SUBLANG_CHINESE_SINGAPORE,
+        ) => "zh",
+        _ => DEFAULT_LANGUAGE,
+    }
+}
+
+/// Language code of the Windows UI language
+#[cfg(windows)]
+pub fn system_language() -> &'static str {
+    use windows::Win32::Globalization::GetUserDefaultUILanguage;
+
+    language_from_langid(unsafe { GetUserDefaultUILanguage() })
+}
+
+#[cfg(not(windows))]
+pub fn system_language() -> &'static str {
+    DEFAULT_LANGUAGE
+}
+
+/// Get localized strings for a language (None = follow the system language)
+pub fn strings_for(language: Option<&str>) -> &'static Strings {
+    let mut loaded = LOADED_STRINGS.lock();
+    loaded
+        .entry(language.unwrap_or_default().to_string())
+        .or_insert_with(|| Box::leak(Box::new(Strings::load(language))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn test_all_locales_have_every_key() {
+        let reference: BTreeSet<&String> = CATALOGS[DEFAULT_LANGUAGE].keys().collect();
+
+        for code in available_languages() {
+            let keys: BTreeSet<&String> = CATALOGS[code].keys().collect();
+            let missing: Vec<_> = reference.difference(&keys).collect();
+            let extra: Vec<_> = keys.difference(&reference).collect();
+            assert!(
+                missing.is_empty(),
+                "{}.json is missing keys: {:?}",
+                code,
+                missing
+            );
+            assert!(
+                extra.is_empty(),
+                "{}.json has unknown keys: {:?}",
+                code,
+                extra
+            );
+
+            for (id, text) in &CATALOGS[code] {
+                assert!(
+                    !text.is_empty(),
+                    "{}.json has an empty value for {}",
+                    code,
+                    id
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_strings_resolve_for_every_language() {
+        for code in available_languages() {
+            // Every field is read through the lookup, so record what it asks for
+            let mut requested = BTreeSet::new();
+            Strings::resolve(|id| {
+                let text = lookup(&[code], id);
+                assert!(!text.is_empty(), "{} has no text for {}", code, id);
+                requested.insert(id);
+                text
+            });
+
+            let catalog: BTreeSet<&str> = CATALOGS[code]
+                .keys()
+                .map(String::as_str)
+                .filter(|&id| id != "language_name")
+                .collect();
+            assert_eq!(requested, catalog, "{}", code);
+
+            let strings = Strings::load(Some(code));
+            assert_eq!(strings.exit, CATALOGS[code]["exit"]);
+        }
+    }
+
+    #[test]
+    fn test_fallback_chain() {
+        assert_eq!(fallback_chain(None, "ko"), vec!["ko", "en"]);
+        assert_eq!(fallback_chain(Some("ja"), "ko"), vec!["ja", "ko", "en"]);
+        assert_eq!(fallback_chain(Some("ko-KR"), "ko"), vec!["ko", "en"]);
+        assert_eq!(fallback_chain(Some("en"), "en"), vec!["en"]);
+        assert_eq!(fallback_chain(Some("zh-CN"), "ko"), vec!["zh", "ko", "en"]);
+        assert_eq!(fallback_chain(Some("zh-TW"), "ko"), vec!["ko", "en"]);
+        assert_eq!(fallback_chain(Some("zh_Hant_HK"), "en"), vec!["en"]);
+    }
+
+    #[test]
+    fn test_lookup_falls_back() {
+        assert_eq!(lookup(&["fr", "ko"], "exit"), "종료");
+        assert_eq!(lookup(&["fr"], "exit"), "Exit");
+        assert_eq!(lookup(&["ko"], "no_such_message"), "");
+        assert_eq!(language_name("ja"), "日本語");
+    }
+
+    #[test]
+    fn test_language_from_langid() {
+        assert_eq!(language_from_langid(0x0412), "ko"); // ko-KR
+        assert_eq!(language_from_langid(0x0411), "ja"); // ja-JP
+        assert_eq!(language_from_langid(0x0804), "zh"); // zh-CN
+        assert_eq!(language_from_langid(0x1004), "zh"); // zh-SG
+        assert_eq!(language_from_langid(0x0004), "zh"); // zh-Hans
+        assert_eq!(language_from_langid(0x0404), "en"); // zh-TW
+        assert_eq!(language_from_langid(0x0C04), "en"); // zh-HK
+        assert_eq!(language_from_langid(0x1404), "en"); // zh-MO
+        assert_eq!(language_from_langid(0x7C04), "en"); // zh-Hant
+        assert_eq!(language_from_langid(0x0409), "en"); // en-US
+        assert_eq!(language_from_langid(0x040C), "en"); // fr-FR
     }
 }
@@ -0,0 +1,103 @@
+//! In-memory log of recent events
+//!
+//! The app runs without a console, so `eprintln!` output is lost. Messages are
+//! kept in a bounded ring buffer for the diagnostic report instead.
+
+use std::collections::VecDeque;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+
+/// Maximum number of log lines kept in memory
+const MAX_LINES: usize = 500;
+
+static RECENT: Lazy<Mutex<VecDeque<String>>> =
+    Lazy::new(|| Mutex::new(VecDeque::with_capacity(MAX_LINES)));
+
+/// Record a log message (never include window titles or chat content)
+pub fn log(message: impl AsRef<str>) {
+    let line = format!(
+        "{} {}",
+        format_timestamp(SystemTime::now()),
+        message.as_ref()
+    );
+
+    let mut recent = RECENT.lock();
+    if recent.len() == MAX_LINES {
+        recent.pop_front();
+    }
+    recent.push_back(line);
+}
+
+/// Snapshot of the recent log lines, oldest first
+pub fn recent() -> Vec<String> {
+    RECENT.lock().iter().cloned().collect()
+}
+
+/// Format a time as UTC ISO 8601 (`2025-01-01T12:34:56Z`)
+pub fn format_timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let rem = secs % 86_400;
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    )
+}
+
+/// Convert days since 1970-01-01 to (year, month, day)
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // Howard Hinnant's days-to-civil algorithm
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        assert_eq!(
+            format_timestamp(UNIX_EPOCH + Duration::from_secs(1_735_734_896)),
+            "2025-01-01T12:34:56Z"
+        );
+        assert_eq!(
+            format_timestamp(UNIX_EPOCH + Duration::from_secs(951_782_400)),
+            "2000-02-29T00:00:00Z"
+        );
+    }
+
+    #[test]
+    fn test_recent_is_bounded() {
+        for i in 0..MAX_LINES + 10 {
+            log(format!("message {}", i));
+        }
+        let recent = recent();
+        assert_eq!(recent.len(), MAX_LINES);
+        assert!(recent
+            .last()
+            .unwrap()
+            .ends_with(&format!("message {}", MAX_LINES + 9)));
+    }
+}
@@ -1,44 +1,106 @@
 #![windows_subsystem = "windows"]
+// Off Windows only the platform-independent modules build, for their tests
+#![cfg_attr(not(windows), allow(dead_code))]
 
 //! KakaoTalk AdBlock - Rust implementation
 //!
 //! This application runs in the background and removes ads from the KakaoTalk
 //! Windows client by monitoring and manipulating its windows.
 
+mod audit;
+#[cfg(test)]
+mod build_version;
+#[cfg(windows)]
+mod clipboard;
+mod control;
+#[cfg(windows)]
 mod debug_window;
+mod diagnostics;
+#[cfg(test)]
+mod ico;
 mod icon;
+mod install;
+mod instance;
+mod ipc;
+mod legacy;
 mod locale;
+mod logger;
+mod metrics;
+mod network;
+mod paths;
+mod pattern;
+mod pe_version;
+#[cfg(windows)]
 mod process;
+mod profile;
+mod raster;
+mod registry;
+mod release_notes;
+mod rules;
+mod scheduled_task;
+mod scripting;
+mod settings;
+mod shortcut;
 mod startup;
+#[cfg(test)]
+mod test_args;
+#[cfg(test)]
+mod test_dir;
+#[cfg(test)]
+mod test_server;
+mod update;
+mod updater;
 mod version;
 mod window;
+mod window_tree;
 
+#[cfg(windows)]
 use std::collections::{HashMap, HashSet};
+#[cfg(windows)]
 use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(windows)]
 use std::sync::mpsc;
+#[cfg(windows)]
 use std::sync::Arc;
+#[cfg(windows)]
 use std::thread;
-use std::time::Duration;
+#[cfg(windows)]
+use std::time::{Duration, Instant, SystemTime};
 
+#[cfg(windows)]
 use parking_lot::Mutex;
-use windows::core::PCWSTR;
-use windows::Win32::Foundation::{HWND, WAIT_OBJECT_0};
-use windows::Win32::System::Threading::{CreateMutexW, WaitForSingleObject};
+#[cfg(windows)]
+use windows::Win32::Foundation::HWND;
+#[cfg(windows)]
 use windows::Win32::UI::WindowsAndMessaging::{
-    DispatchMessageW, PeekMessageW, TranslateMessage, MSG, PM_REMOVE,
+    DispatchMessageW, MessageBoxW, PeekMessageW, TranslateMessage, IDYES, MB_ICONINFORMATION,
+    MB_ICONQUESTION, MB_ICONWARNING, MB_OK, MB_YESNO, MSG, PM_REMOVE,
 };
 
-use muda::{Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+#[cfg(windows)]
+use muda::{CheckMenuItem, Menu, MenuEvent, MenuItem, PredefinedMenuItem, Submenu};
+#[cfg(windows)]
 use tray_icon::{TrayIconBuilder, TrayIconEvent};
 
+#[cfg(windows)]
+use audit::AuditAction;
+#[cfg(windows)]
+use rules::Rule;
+
+#[cfg(windows)]
 const VERSION: &str = env!("BUILD_VERSION");
+#[cfg(windows)]
 const SLEEP_INTERVAL: Duration = Duration::from_millis(100);
+/// How often user scripts look at the window tree
+#[cfg(windows)]
+const SCRIPT_INTERVAL: Duration = Duration::from_secs(1);
 
-// Layout constants from the original Go implementation
-const LAYOUT_SHADOW_PADDING: i32 = 2;
-const MAIN_VIEW_PADDING: i32 = 31;
+/// Failed process scans in a row (about 5 seconds) before showing an error
+#[cfg(windows)]
+const ENUMERATION_FAILURE_LIMIT: u32 = 50;
 
 /// Shared state for tracking KakaoTalk windows
+#[cfg(windows)]
 pub struct AdBlockState {
     /// Main window handles (EVA_Window_Dblclk with title and no parent)
     pub main_windows: HashSet<isize>,
@@ -48,15 +110,96 @@ pub struct AdBlockState {
     pub window_class_cache: HashMap<isize, String>,
     /// Cache for custom scroll detection
     pub custom_scroll_cache: HashMap<isize, bool>,
+    /// Recent actions taken on windows (for diagnostic reports)
+    pub audit: audit::AuditTrail,
+    /// Blocking paused from the tray
+    pub paused: bool,
+    /// Only record what would be done, without touching windows
+    pub dry_run: bool,
+    /// Ads already recorded in dry-run mode (recorded once per window)
+    pub dry_run_reported: HashSet<isize>,
+    /// Whether the last process scan found KakaoTalk
+    pub kakaotalk_running: bool,
+    /// Consecutive failed process scans
+    pub enumeration_failures: u32,
+    /// Ad rules switched off through the control API
+    pub disabled_rules: HashSet<Rule>,
+    /// Health counters for the metrics file
+    pub metrics: metrics::Metrics,
+    /// Scripts that failed to load or in their latest run
+    pub script_errors: Vec<String>,
+    /// What scripts printed in their latest run, for the debug window only
+    pub script_output: Vec<String>,
+    /// Enabled target profiles
+    pub profiles: Vec<profile::Profile>,
+    /// Client processes found by the last scan
+    pub targets: Vec<profile::Target>,
+    /// Client process of each main window and ad candidate
+    pub window_targets: HashMap<isize, profile::Target>,
+    /// Client version of each process, read once per process
+    pub client_versions: HashMap<u32, Option<pe_version::FileVersion>>,
 }
 
+#[cfg(windows)]
 impl AdBlockState {
-    fn new() -> Self {
+    fn new(profiles: Vec<profile::Profile>) -> Self {
         Self {
             main_windows: HashSet::new(),
             ad_candidates: HashMap::new(),
             window_class_cache: HashMap::new(),
             custom_scroll_cache: HashMap::new(),
+            audit: audit::AuditTrail::new(),
+            paused: false,
+            dry_run: false,
+            dry_run_reported: HashSet::new(),
+            kakaotalk_running: false,
+            enumeration_failures: 0,
+            disabled_rules: HashSet::new(),
+            metrics: metrics::Metrics::new(),
+            script_errors: Vec::new(),
+            script_output: Vec::new(),
+            profiles,
+            targets: Vec::new(),
+            window_targets: HashMap::new(),
+            client_versions: HashMap::new(),
+        }
+    }
+
+    /// Client processes with the rules for their versions, for capturing trees
+    pub fn target_rules(&self) -> Vec<(u32, rules::RuleSet)> {
+        self.targets
+            .iter()
+            .map(|t| (t.pid, self.profiles[t.profile].select(t.version).0.clone()))
+            .collect()
+    }
+
+    /// Whether process scans have failed for long enough to stop trusting them
+    fn has_error(&self) -> bool {
+        self.enumeration_failures >= ENUMERATION_FAILURE_LIMIT
+    }
+
+    /// Record an action taken on a window
+    fn record_action(
+        &mut self,
+        action: AuditAction,
+        hwnd_key: isize,
+        class_name: &str,
+        rule: Rule,
+    ) {
+        self.audit.record(action, hwnd_key, class_name, Some(rule));
+        self.metrics.record_action(action);
+    }
+
+    /// Record an action skipped in dry-run mode, once per window
+    fn report_dry_run(
+        &mut self,
+        action: AuditAction,
+        hwnd_key: isize,
+        class_name: &str,
+        rule: Rule,
+    ) {
+        if self.dry_run_reported.insert(hwnd_key) {
+            self.audit.record(action, hwnd_key, class_name, Some(rule));
         }
     }
 
@@ -82,6 +225,22 @@ impl AdBlockState {
             .retain(|k, _| valid_main.contains(k) || valid_ads.contains(k));
         self.custom_scroll_cache
             .retain(|k, _| valid_main.contains(k));
+        self.window_targets
+            .retain(|k, _| valid_main.contains(k) || valid_ads.contains(k));
+        self.dry_run_reported.retain(|&k| {
+            let hwnd = HWND(k as *mut _);
+            window::is_window_valid(hwnd)
+        });
+    }
+
+    /// Forget every window found so far, so all are classified again
+    fn forget_decisions(&mut self) {
+        self.main_windows.clear();
+        self.ad_candidates.clear();
+        self.window_class_cache.clear();
+        self.custom_scroll_cache.clear();
+        self.dry_run_reported.clear();
+        self.window_targets.clear();
     }
 
     fn get_window_class(&mut self, hwnd: HWND) -> String {
@@ -96,19 +255,60 @@ impl AdBlockState {
 }
 
 /// Check if HWND is null/invalid
+#[cfg(windows)]
 fn is_hwnd_null(hwnd: HWND) -> bool {
     hwnd.0.is_null()
 }
 
-/// Watch for KakaoTalk windows and categorize them
+/// Watch for client windows and categorize them
+#[cfg(windows)]
 fn watch_windows(state: Arc<Mutex<AdBlockState>>, running: Arc<AtomicBool>) {
     let mut cleanup_counter = 0u32;
 
     while running.load(Ordering::Relaxed) {
-        // Find all KakaoTalk process IDs
-        let pids = process::find_process_ids(process::KAKAOTALK_EXE);
+        let started = Instant::now();
+        // Find the processes of all enabled profiles
+        let (profiles, known_pids) = {
+            let state = state.lock();
+            let known_pids: HashSet<u32> = state.client_versions.keys().copied().collect();
+            (state.profiles.clone(), known_pids)
+        };
+        let scan = profile::find_targets(&profiles);
+
+        // Read the version of each new client once, without holding the lock
+        let new_versions: Vec<_> = scan
+            .iter()
+            .flatten()
+            .filter(|target| !known_pids.contains(&target.pid))
+            .map(|target| {
+                let version = client_version(target.pid);
+                let profile = &profiles[target.profile];
+                if let Some(version) = version.filter(|v| profile.is_newer_than_known(*v)) {
+                    logger::log(format!(
+                        "{} {} is newer than any known rule set; ads may not be blocked",
+                        profile.name, version
+                    ));
+                }
+                (target.pid, version)
+            })
+            .collect();
 
         let mut state = state.lock();
+        let mut targets = match scan {
+            Ok(targets) => {
+                state.enumeration_failures = 0;
+                state.kakaotalk_running = !targets.is_empty();
+                targets
+            }
+            Err(e) => {
+                state.enumeration_failures += 1;
+                state.metrics.record_enumeration_failure();
+                if state.enumeration_failures == ENUMERATION_FAILURE_LIMIT {
+                    logger::log(e);
+                }
+                Vec::new()
+            }
+        };
 
         // Periodic cleanup of invalid handles (every ~1 second)
         cleanup_counter += 1;
@@ -117,9 +317,19 @@ fn watch_windows(state: Arc<Mutex<AdBlockState>>, running: Arc<AtomicBool>) {
             state.cleanup_invalid_handles();
         }
 
-        for pid in &pids {
+        state
+            .client_versions
+            .retain(|pid, _| targets.iter().any(|t| t.pid == *pid));
+        state.client_versions.extend(new_versions);
+        for target in &mut targets {
+            target.version = state.client_versions.get(&target.pid).copied().flatten();
+        }
+        state.targets = targets.clone();
+
+        for target in &targets {
+            let (rules, _) = profiles[target.profile].select(target.version);
             // Enumerate all windows for this process
-            let windows = window::find_windows_by_pid(*pid);
+            let windows = window::find_windows_by_pid(target.pid);
 
             for hwnd in windows {
                 let class_name = state.get_window_class(hwnd);
@@ -129,91 +339,97 @@ fn watch_windows(state: Arc<Mutex<AdBlockState>>, running: Arc<AtomicBool>) {
                 let parent_key = parent.0 as isize;
                 let hwnd_key = hwnd.0 as isize;
 
-                match class_name.as_str() {
-                    window::class_names::EVA_WINDOW_DBLCLK => {
-                        if !window_text.is_empty() && is_hwnd_null(parent) {
-                            // Main window
-                            state.main_windows.insert(hwnd_key);
-                        } else if window_text.is_empty() && !is_hwnd_null(parent) {
-                            // Potential ad window if parent is a main window
-                            if state.main_windows.contains(&parent_key) {
-                                state.ad_candidates.entry(hwnd_key).or_insert(false);
-                            }
-                        }
+                let has_parent = !is_hwnd_null(parent);
+                let parent_is_main = state.main_windows.contains(&parent_key);
+
+                match rules
+                    .classify_top_level(&class_name, &window_text, has_parent, parent_is_main)
+                    .filter(|rule| rules.allows(*rule, target.version))
+                {
+                    Some(Rule::MainWindow) => {
+                        state.main_windows.insert(hwnd_key);
+                        state.window_targets.insert(hwnd_key, *target);
                     }
-                    window::class_names::EVA_WINDOW => {
-                        if window_text.is_empty() && is_hwnd_null(parent) {
-                            // Ad popup window
-                            state.ad_candidates.entry(hwnd_key).or_insert(false);
-                        }
+                    Some(Rule::EmbeddedAd) | Some(Rule::PopupAd) => {
+                        state.ad_candidates.entry(hwnd_key).or_insert(false);
+                        state.window_targets.insert(hwnd_key, *target);
                     }
                     _ => {}
                 }
             }
         }
 
+        let (main_windows, ad_candidates) = (state.main_windows.len(), state.ad_candidates.len());
+        state.metrics.set_windows(main_windows, ad_candidates);
+        state
+            .metrics
+            .record_tick(metrics::Tick::WatchWindows, started.elapsed());
         drop(state);
         thread::sleep(SLEEP_INTERVAL);
     }
 }
 
-/// Check if window has custom scroll (starts with _EVA_)
-fn has_custom_scroll(hwnd: HWND) -> bool {
-    window::has_child_class_starting_with(hwnd, "_EVA_")
-}
-
-/// Check if this is a main window (has OnlineMainView or LockModeView child)
-fn is_main_window(children: &[HWND], state: &mut AdBlockState) -> bool {
-    for &child in children {
-        let class_name = state.get_window_class(child);
-        if class_name != window::class_names::EVA_CHILD_WINDOW {
-            continue;
-        }
-
-        let text = window::get_window_text(child);
-        if text.starts_with(window::window_texts::ONLINE_MAIN_VIEW)
-            || text.starts_with(window::window_texts::LOCK_MODE_VIEW)
-        {
-            return true;
+/// Version of a client process, from its executable
+#[cfg(windows)]
+fn client_version(pid: u32) -> Option<pe_version::FileVersion> {
+    let path = process::get_process_image_path(pid)?;
+    match pe_version::read_file_version(std::path::Path::new(&path)) {
+        Ok(version) => Some(version),
+        Err(e) => {
+            logger::log(format!("Failed to read client version: {}", e));
+            None
         }
     }
-    false
 }
 
-/// Hide the main view ad area by resizing
-fn hide_main_view_ad_area(window_text: &str, rect: &windows::Win32::Foundation::RECT, hwnd: HWND) {
-    if window_text.starts_with(window::window_texts::ONLINE_MAIN_VIEW) {
-        let width = rect.right - rect.left - LAYOUT_SHADOW_PADDING;
-        let height = rect.bottom - rect.top - MAIN_VIEW_PADDING;
-
-        if height < 1 {
-            return;
-        }
+/// Check if window has custom scroll (e.g. KakaoTalk's _EVA_ scroll bars)
+#[cfg(windows)]
+fn has_custom_scroll(hwnd: HWND, rules: &rules::RuleSet) -> bool {
+    window::has_child_class_starting_with(hwnd, &rules.scroll_class_prefix)
+}
 
-        window::update_window(hwnd);
-        window::set_window_size(hwnd, width, height);
-    }
+/// Check if this is a main window (has a main view or lock screen child)
+#[cfg(windows)]
+fn is_main_window(children: &[HWND], rules: &rules::RuleSet, state: &mut AdBlockState) -> bool {
+    children.iter().any(|&child| {
+        let class_name = state.get_window_class(child);
+        // Skip the text lookup for other classes
+        class_name == rules.child_class
+            && rules.is_content_view(&class_name, &window::get_window_text(child))
+    })
 }
 
-/// Hide the lock screen ad area by resizing
-fn hide_lock_screen_ad_area(
-    window_text: &str,
-    rect: &windows::Win32::Foundation::RECT,
-    hwnd: HWND,
-) {
-    if window_text.starts_with(window::window_texts::LOCK_MODE_VIEW) {
-        let width = rect.right - rect.left - LAYOUT_SHADOW_PADDING;
-        let height = rect.bottom - rect.top;
+/// Hide an ad area by resizing
+/// Returns true if the window size actually changed
+#[cfg(windows)]
+fn hide_ad_area(hwnd: HWND, (width, height): (i32, i32)) -> bool {
+    let changed = !has_window_size(hwnd, width, height);
+    window::update_window(hwnd);
+    window::set_window_size(hwnd, width, height);
+    changed
+}
 
-        window::update_window(hwnd);
-        window::set_window_size(hwnd, width, height);
-    }
+/// Check if a window already has the given size
+#[cfg(windows)]
+fn has_window_size(hwnd: HWND, width: i32, height: i32) -> bool {
+    window::get_window_rect(hwnd)
+        .map(|r| r.right - r.left == width && r.bottom - r.top == height)
+        .unwrap_or(false)
 }
 
-/// Remove ads from KakaoTalk windows
+/// Remove ads from client windows
+#[cfg(windows)]
 fn remove_ads(state: Arc<Mutex<AdBlockState>>, running: Arc<AtomicBool>) {
     while running.load(Ordering::Relaxed) {
+        let started = Instant::now();
         let mut state = state.lock();
+        if state.paused {
+            drop(state);
+            thread::sleep(SLEEP_INTERVAL);
+            continue;
+        }
+        let dry_run = state.dry_run;
+        let profiles = state.profiles.clone();
 
         // Process main windows
         let main_windows: Vec<isize> = state.main_windows.iter().copied().collect();
@@ -224,12 +440,16 @@ fn remove_ads(state: Arc<Mutex<AdBlockState>>, running: Arc<AtomicBool>) {
             if is_hwnd_null(hwnd) {
                 continue;
             }
+            let Some(target) = state.window_targets.get(&hwnd_key).copied() else {
+                continue;
+            };
+            let (rules, layout) = profiles[target.profile].select(target.version);
 
             // Get child windows
             let children = window::get_child_windows(hwnd);
 
             // Check if this is really a main window
-            if !is_main_window(&children, &mut state) {
+            if !is_main_window(&children, rules, &mut state) {
                 continue;
             }
 
@@ -252,36 +472,80 @@ fn remove_ads(state: Arc<Mutex<AdBlockState>>, running: Arc<AtomicBool>) {
 
                 let parent_text = window::get_window_text(parent);
 
-                // Hide ad child windows: resize to 0x0 first (instant visual removal),
-                // then close (cleanup)
-                if class_name == window::class_names::EVA_CHILD_WINDOW
-                    && window_text.is_empty()
-                    && !parent_text.is_empty()
-                {
-                    // Check for custom scroll
-                    let has_scroll = state
-                        .custom_scroll_cache
-                        .get(&(hwnd.0 as isize))
-                        .copied()
-                        .unwrap_or_else(|| {
-                            let result = has_custom_scroll(hwnd);
-                            state.custom_scroll_cache.insert(hwnd.0 as isize, result);
-                            result
-                        });
-
-                    if !has_scroll {
-                        // Step 1: Resize to 0x0 (instant visual removal)
-                        window::set_window_size(*child, 0, 0);
-                        // Step 2: Close window (cleanup)
-                        window::close_window(*child);
-                        // Step 3: Force parent to redraw (fill blank space)
-                        window::refresh_window(hwnd);
+                let rule = rules
+                    .classify_main_child(&class_name, &window_text, &parent_text)
+                    .filter(|rule| {
+                        rules.allows(*rule, target.version) && !state.disabled_rules.contains(rule)
+                    });
+                match rule {
+                    // Hide ad child windows: resize to 0x0 first (instant visual removal),
+                    // then close (cleanup)
+                    Some(Rule::BannerChild) => {
+                        // Check for custom scroll
+                        let has_scroll = state
+                            .custom_scroll_cache
+                            .get(&(hwnd.0 as isize))
+                            .copied()
+                            .unwrap_or_else(|| {
+                                let result = has_custom_scroll(hwnd, rules);
+                                state.custom_scroll_cache.insert(hwnd.0 as isize, result);
+                                result
+                            });
+
+                        if !has_scroll && dry_run {
+                            state.report_dry_run(
+                                AuditAction::Close,
+                                child.0 as isize,
+                                &class_name,
+                                Rule::BannerChild,
+                            );
+                        } else if !has_scroll {
+                            // Step 1: Resize to 0x0 (instant visual removal)
+                            window::set_window_size(*child, 0, 0);
+                            // Step 2: Close window (cleanup)
+                            window::close_window(*child);
+                            // Step 3: Force parent to redraw (fill blank space)
+                            window::refresh_window(hwnd);
+
+                            state.record_action(
+                                AuditAction::Close,
+                                child.0 as isize,
+                                &class_name,
+                                Rule::BannerChild,
+                            );
+                        }
+                    }
+                    // Resize to hide ad areas (only audit actual size changes)
+                    Some(rule @ (Rule::MainViewArea | Rule::LockScreenArea)) => {
+                        let (width, height) = (rect.right - rect.left, rect.bottom - rect.top);
+                        let target = if rule == Rule::MainViewArea {
+                            layout.main_view_size(width, height)
+                        } else {
+                            Some(layout.lock_screen_size(width, height))
+                        };
+                        let Some(target) = target else {
+                            continue;
+                        };
+                        if dry_run {
+                            if !has_window_size(*child, target.0, target.1) {
+                                state.report_dry_run(
+                                    AuditAction::Resize,
+                                    child.0 as isize,
+                                    &class_name,
+                                    rule,
+                                );
+                            }
+                        } else if hide_ad_area(*child, target) {
+                            state.record_action(
+                                AuditAction::Resize,
+                                child.0 as isize,
+                                &class_name,
+                                rule,
+                            );
+                        }
                     }
+                    _ => {}
                 }
-
-                // Resize to hide ad areas
-                hide_main_view_ad_area(&window_text, &rect, *child);
-                hide_lock_screen_ad_area(&window_text, &rect, *child);
             }
         }
 
@@ -292,9 +556,28 @@ fn remove_ads(state: Arc<Mutex<AdBlockState>>, running: Arc<AtomicBool>) {
         for (hwnd_key, was_processed) in ad_candidates {
             let hwnd = HWND(hwnd_key as *mut _);
             // Only process if window is visible (re-appeared or never hidden)
+            let Some(target) = state.window_targets.get(&hwnd_key).copied() else {
+                continue;
+            };
+            let (rules, _) = profiles[target.profile].select(target.version);
             if window::is_window_visible(hwnd) && window::has_chrome_legacy_window(hwnd) {
+                let class_name = state.get_window_class(hwnd);
+                let rule = if class_name == rules.popup_class {
+                    Rule::PopupAd
+                } else {
+                    Rule::EmbeddedAd
+                };
+                if state.disabled_rules.contains(&rule) || !rules.allows(rule, target.version) {
+                    continue;
+                }
+                if dry_run {
+                    state.report_dry_run(AuditAction::Hide, hwnd_key, &class_name, rule);
+                    continue;
+                }
+
                 window::hide_window(hwnd);
                 state.ad_candidates.insert(hwnd_key, true);
+                state.record_action(AuditAction::Hide, hwnd_key, &class_name, rule);
             } else if was_processed && !window::is_window_visible(hwnd) {
                 // Already hidden, keep processed status
             } else if !was_processed && !window::has_chrome_legacy_window(hwnd) {
@@ -302,46 +585,578 @@ fn remove_ads(state: Arc<Mutex<AdBlockState>>, running: Arc<AtomicBool>) {
             }
         }
 
+        state
+            .metrics
+            .record_tick(metrics::Tick::RemoveAds, started.elapsed());
         drop(state);
         thread::sleep(SLEEP_INTERVAL);
     }
 }
 
-/// Message for version check result
-enum VersionCheckResult {
-    NewVersionAvailable(String),
+/// Run user detection scripts against the window tree and apply their actions
+#[cfg(windows)]
+fn run_scripts(
+    state: Arc<Mutex<AdBlockState>>,
+    running: Arc<AtomicBool>,
+    host: scripting::ScriptHost,
+    load_errors: Vec<scripting::ScriptError>,
+) {
+    while running.load(Ordering::Relaxed) {
+        let skip = {
+            let state = state.lock();
+            state.paused || state.disabled_rules.contains(&Rule::Script)
+        };
+        let result = if skip {
+            scripting::RunResult::default()
+        } else {
+            // Capture and run without holding the lock; scripts may take a while
+            let targets = state.lock().target_rules();
+            host.run(&window_tree::capture(&targets))
+        };
+
+        let mut state = state.lock();
+        let errors: Vec<_> = load_errors.iter().chain(&result.errors).collect();
+        for error in &errors {
+            // Messages can quote window titles, so only the script is logged
+            if !state.script_errors.contains(&error.to_string()) {
+                logger::log(format!("Script error in {}", error.script));
+            }
+        }
+        state.script_errors = errors.iter().map(|e| e.to_string()).collect();
+        state.script_output = result.output;
+        for decision in result.decisions {
+            apply_script_decision(&mut state, &decision);
+        }
+        drop(state);
+
+        thread::sleep(SCRIPT_INTERVAL);
+    }
+}
+
+/// Carry out one script action, recording only actual changes
+#[cfg(windows)]
+fn apply_script_decision(state: &mut AdBlockState, decision: &scripting::Decision) {
+    let hwnd = HWND(decision.hwnd as *mut _);
+    let (action, needed) = match decision.action {
+        scripting::ScriptAction::Hide => (AuditAction::Hide, window::is_window_visible(hwnd)),
+        scripting::ScriptAction::Close => (AuditAction::Close, !has_window_size(hwnd, 0, 0)),
+        scripting::ScriptAction::Resize { width, height } => {
+            (AuditAction::Resize, !has_window_size(hwnd, width, height))
+        }
+    };
+    if !needed {
+        return;
+    }
+    let class_name = state.get_window_class(hwnd);
+    if state.dry_run {
+        state.report_dry_run(action, decision.hwnd, &class_name, Rule::Script);
+        return;
+    }
+
+    match decision.action {
+        scripting::ScriptAction::Hide => window::hide_window(hwnd),
+        scripting::ScriptAction::Close => {
+            window::set_window_size(hwnd, 0, 0);
+            window::close_window(hwnd);
+        }
+        scripting::ScriptAction::Resize { width, height } => {
+            window::set_window_size(hwnd, width, height)
+        }
+    }
+    let parent = window::get_parent(hwnd);
+    if !is_hwnd_null(parent) {
+        window::refresh_window(parent);
+    }
+    state.record_action(action, decision.hwnd, &class_name, Rule::Script);
 }
 
-/// Mutex name for single-instance check
-const SINGLE_INSTANCE_MUTEX: &str = "Global\\KakaoTalkAdBlock_SingleInstance";
+/// What the update menu item currently reflects
+#[cfg(windows)]
+#[derive(Default)]
+struct UpdateStatus {
+    /// Newer release found by the last successful check
+    available: Option<update::Release>,
+    /// Reason the last check failed
+    check_error: Option<String>,
+    /// The available release is being downloaded and installed
+    installing: bool,
+    /// Reason installing the available release failed
+    install_error: Option<String>,
+    /// Offline mode is on, so nothing is checked
+    offline: bool,
+}
 
+#[cfg(windows)]
+impl UpdateStatus {
+    /// Text for the update menu item: a known update wins over a failed re-check
+    fn menu_text(&self, strings: &locale::Strings) -> String {
+        if self.offline && !self.installing {
+            return strings.updates_offline.to_string();
+        }
+        match (&self.available, &self.check_error) {
+            (Some(release), _) if self.installing => {
+                format!("{}{}", strings.installing_update, release.tag_name)
+            }
+            (Some(_), _) if self.install_error.is_some() => format!(
+                "{}{}",
+                strings.update_failed,
+                self.install_error.as_deref().unwrap_or_default()
+            ),
+            (Some(release), _) => format!("{}{}", strings.new_version_available, release.tag_name),
+            (None, Some(error)) => format!("{}{}", strings.update_check_failed, error),
+            (None, None) => strings.check_for_updates.to_string(),
+        }
+    }
+}
+
+/// Download, verify and swap in a release, then start the new executable
+#[cfg(windows)]
+fn install_update(
+    release: &update::Release,
+    network: &network::NetworkConfig,
+) -> Result<(), String> {
+    let current_exe =
+        std::env::current_exe().map_err(|e| format!("Failed to locate executable: {}", e))?;
+    let download_dir = std::env::temp_dir().join("KakaoTalkAdBlock-update");
+
+    let new_exe = updater::download_verified(network, release, &download_dir)?;
+    updater::install(&new_exe, &current_exe)?;
+    updater::relaunch(&current_exe)
+}
+
+/// Offer once to replace the Go version of KakaoTalkAdBlock
+///
+/// Its only setting that maps to ours is starting at logon, which is carried
+/// over if this app does not start at logon yet.
+#[cfg(windows)]
+fn migrate_legacy(strings: &locale::Strings, settings: &mut settings::Settings) {
+    let install = legacy::detect();
+    if !install.is_empty() {
+        let exes: Vec<String> = install
+            .executables()
+            .iter()
+            .map(|exe| exe.display().to_string())
+            .collect();
+        logger::log(format!("Found the Go version: {}", exes.join(", ")));
+
+        let text = format!(
+            "{}\n{}\n\n{}",
+            strings.legacy_prompt,
+            exes.join("\n"),
+            strings.legacy_question
+        );
+        if ask_yes_no("KakaoTalkAdBlock", &text) {
+            for e in legacy::remove(&install) {
+                logger::log(e);
+            }
+            let starts_at_logon = matches!(
+                startup::current_entry(),
+                Some((_, startup::StartupState::Enabled))
+            );
+            if !install.startup_entries.is_empty() && !starts_at_logon {
+                match startup::enable(settings.startup_method, startup_delay(settings)) {
+                    Ok(_) => logger::log("Took over starting at logon from the Go version"),
+                    Err(e) => logger::log(e),
+                }
+            }
+        }
+    }
+
+    settings.legacy_migration_offered = true;
+    if let Err(e) = settings::save(settings) {
+        logger::log(e);
+    }
+}
+
+/// Keep the startup entry pointing at this executable
+///
+/// Entries in an older format are rewritten silently; entries for another
+/// location are only replaced if the user agrees, and not asked about again
+/// once declined.
+#[cfg(windows)]
+fn repair_startup_entry(strings: &locale::Strings, settings: &mut settings::Settings) {
+    let method = match startup::plan_repair(
+        startup::current_entry(),
+        settings.stale_startup_declined.as_deref(),
+    ) {
+        startup::Repair::Nothing => return,
+        startup::Repair::Rewrite(method) => method,
+        startup::Repair::Ask(method, path) => {
+            let text = format!(
+                "{}\n{}\n\n{}",
+                strings.startup_repair_prompt,
+                path.display(),
+                strings.startup_repair_question
+            );
+            if !ask_yes_no(strings.run_on_startup, &text) {
+                // Don't ask again at every logon
+                settings.stale_startup_declined = Some(path);
+                if let Err(e) = settings::save(settings) {
+                    logger::log(e);
+                }
+                return;
+            }
+            method
+        }
+    };
+
+    match startup::enable(method, startup_delay(settings)) {
+        Ok(_) => logger::log("Updated the startup entry"),
+        Err(e) => logger::log(e),
+    }
+}
+
+#[cfg(windows)]
+fn startup_delay(settings: &settings::Settings) -> Duration {
+    Duration::from_secs(settings.startup_delay_secs)
+}
+
+/// Menu ID of a startup method choice
+#[cfg(windows)]
+fn startup_method_id(method: startup::StartupMethod) -> &'static str {
+    match method {
+        startup::StartupMethod::Registry => "startup_method:registry",
+        startup::StartupMethod::ScheduledTask => "startup_method:scheduled_task",
+        startup::StartupMethod::StartupFolder => "startup_method:startup_folder",
+    }
+}
+
+#[cfg(windows)]
+fn startup_method_name(strings: &locale::Strings, method: startup::StartupMethod) -> &'static str {
+    match method {
+        startup::StartupMethod::Registry => strings.startup_method_registry,
+        startup::StartupMethod::ScheduledTask => strings.startup_method_task,
+        startup::StartupMethod::StartupFolder => strings.startup_method_folder,
+    }
+}
+
+/// "Run on startup" text, naming the method in effect
+#[cfg(windows)]
+fn startup_text(strings: &locale::Strings, enabled: Option<startup::StartupMethod>) -> String {
+    match enabled {
+        Some(method) => format!(
+            "{} ({})",
+            strings.run_on_startup_checked,
+            startup_method_name(strings, method)
+        ),
+        None => strings.run_on_startup.to_string(),
+    }
+}
+
+/// Handle `--install` / `--uninstall` instead of running the app
+#[cfg(windows)]
+fn run_install_command(command: install::Command) {
+    let settings = settings::load();
+    let strings = locale::strings_for(settings.language.as_deref());
+    let layout = install::Layout::current();
+
+    match command {
+        install::Command::Install {
+            start_menu_shortcut,
+        } => match install::install(&layout, start_menu_shortcut, &settings) {
+            Ok(exe) => {
+                let text = format!("{}\n{}", strings.install_done, exe.display());
+                show_message(&text, false);
+                if let Err(e) = std::process::Command::new(&exe).spawn() {
+                    logger::log(format!("Failed to start {}: {}", exe.display(), e));
+                }
+            }
+            Err(e) => show_message(&format!("{}{}", strings.install_failed, e), true),
+        },
+        install::Command::Uninstall => {
+            let errors = install::uninstall(&layout);
+            if errors.is_empty() {
+                show_message(strings.uninstall_done, false);
+            } else {
+                let text = format!("{}\n{}", strings.uninstall_failed, errors.join("\n"));
+                show_message(&text, true);
+            }
+            // After the message box, so the executable has exited by the time
+            // it is deleted
+            if let Err(e) = install::delete_after_exit(&layout) {
+                logger::log(e);
+            }
+        }
+    }
+}
+
+/// Pass `command` to the instance already running and exit with its result
+///
+/// Exits with 0 on success, 1 if the instance refused the command and 2 if it
+/// could not be reached.
+#[cfg(windows)]
+fn forward_command(command: ipc::Command) -> ! {
+    let result = ipc::connect().map(|pipe| ipc::send(pipe, command));
+    let code = match &result {
+        Ok(Ok(_)) => 0,
+        Ok(Err(_)) => 1,
+        Err(_) => 2,
+    };
+    let result = result.unwrap_or_else(|e| {
+        logger::log(&e);
+        let strings = locale::strings_for(settings::load().language.as_deref());
+        Err(format!("{}{}", strings.ipc_unreachable, e))
+    });
+    report_command_result(command, result);
+    std::process::exit(code);
+}
+
+/// Print the result to the console this was started from, or show it
+#[cfg(windows)]
+fn report_command_result(command: ipc::Command, result: Result<String, String>) {
+    use windows::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+
+    if unsafe { AttachConsole(ATTACH_PARENT_PROCESS) }.is_ok() {
+        match result {
+            Ok(message) if !message.is_empty() => println!("{}", message),
+            Ok(_) => {}
+            Err(message) => eprintln!("{}", message),
+        }
+        return;
+    }
+    // Started from Explorer: the debug window is answer enough
+    match result {
+        Ok(_) if command == ipc::Command::ShowDebugWindow => {}
+        Ok(message) => show_message(&message, false),
+        Err(message) => show_message(&message, true),
+    }
+}
+
+/// Serve the control API and write its token file; returns the file's path
+#[cfg(windows)]
+fn start_control_api(state: &Arc<Mutex<AdBlockState>>) -> Option<std::path::PathBuf> {
+    let backend = Arc::new(control::LiveBackend {
+        state: Arc::clone(state),
+    });
+    let path = control::token_file_path();
+    let result = control::generate_token().and_then(|token| {
+        let port = control::start(backend, token.clone())?;
+        control::write_token_file(&path, &control::TokenFile { port, token })?;
+        logger::log(format!("Control API listening on 127.0.0.1:{}", port));
+        Ok(())
+    });
+    match result {
+        Ok(()) => Some(path),
+        Err(e) => {
+            logger::log(e);
+            None
+        }
+    }
+}
+
+/// Rewrite the metrics file periodically until exit
+#[cfg(windows)]
+fn export_metrics(
+    state: Arc<Mutex<AdBlockState>>,
+    running: Arc<AtomicBool>,
+    path: std::path::PathBuf,
+) {
+    logger::log(format!("Writing metrics to {}", path.display()));
+    let mut last_error = None;
+    while running.load(Ordering::Relaxed) {
+        let text = state.lock().metrics.render(VERSION);
+        // Log a failure once, not every interval
+        let result = metrics::write_file(&path, &text).err();
+        if result.is_some() && result != last_error {
+            logger::log(result.as_deref().unwrap_or_default());
+        }
+        last_error = result;
+
+        let deadline = Instant::now() + metrics::EXPORT_INTERVAL;
+        while running.load(Ordering::Relaxed) && Instant::now() < deadline {
+            thread::sleep(SLEEP_INTERVAL);
+        }
+    }
+}
+
+/// Pause or resume blocking, keeping the tray menu in sync
+#[cfg(windows)]
+fn set_paused(state: &Mutex<AdBlockState>, item: &CheckMenuItem, paused: bool) {
+    state.lock().paused = paused;
+    item.set_checked(paused);
+    logger::log(if paused {
+        "Blocking paused"
+    } else {
+        "Blocking resumed"
+    });
+}
+
+/// Machine-readable status for `--status`, one `key: value` per line
+#[cfg(windows)]
+fn status_report(state: &AdBlockState, status: icon::TrayStatus) -> String {
+    let status = match status {
+        icon::TrayStatus::Active => "active",
+        icon::TrayStatus::Paused => "paused",
+        icon::TrayStatus::DryRun => "dry-run",
+        icon::TrayStatus::KakaoTalkNotRunning => "kakaotalk-not-running",
+        icon::TrayStatus::UpdateAvailable => "update-available",
+        icon::TrayStatus::Error => "error",
+    };
+    [
+        format!("status: {}", status),
+        format!("version: {}", VERSION),
+        format!("paused: {}", state.paused),
+        format!("dry_run: {}", state.dry_run),
+        format!("main_windows: {}", state.main_windows.len()),
+        format!("ad_windows: {}", state.ad_candidates.len()),
+    ]
+    .join("\n")
+}
+
+/// Show an information (or warning) message
+#[cfg(windows)]
+fn show_message(text: &str, warning: bool) {
+    let title = windows::core::HSTRING::from("KakaoTalkAdBlock");
+    let text = windows::core::HSTRING::from(text);
+    let icon = if warning {
+        MB_ICONWARNING
+    } else {
+        MB_ICONINFORMATION
+    };
+    unsafe { MessageBoxW(HWND::default(), &text, &title, MB_OK | icon) };
+}
+
+/// Tray icon for `status`, rendered at the system's small icon size
+#[cfg(windows)]
+fn tray_icon_image(status: icon::TrayStatus) -> tray_icon::Icon {
+    use windows::Win32::UI::WindowsAndMessaging::{GetSystemMetrics, SM_CXSMICON};
+
+    let metric = unsafe { GetSystemMetrics(SM_CXSMICON) };
+    let size = icon::size_for(metric.max(0) as u32);
+    let rgba = icon::render(status, size);
+
+    tray_icon::Icon::from_rgba(rgba, size, size).expect("Failed to create icon")
+}
+
+/// Tray tooltip naming the status, unless blocking works normally
+#[cfg(windows)]
+fn tray_tooltip(strings: &locale::Strings, status: icon::TrayStatus) -> String {
+    let detail = match status {
+        icon::TrayStatus::Active => return "KakaoTalkAdBlock".to_string(),
+        icon::TrayStatus::Paused => strings.status_paused,
+        icon::TrayStatus::DryRun => strings.status_dry_run,
+        icon::TrayStatus::KakaoTalkNotRunning => strings.status_not_running,
+        icon::TrayStatus::UpdateAvailable => strings.status_update_available,
+        icon::TrayStatus::Error => strings.status_error,
+    };
+    format!("KakaoTalkAdBlock - {}", detail)
+}
+
+/// Show a Yes/No question and return whether Yes was chosen
+#[cfg(windows)]
+fn ask_yes_no(title: &str, text: &str) -> bool {
+    let title = windows::core::HSTRING::from(title);
+    let text = windows::core::HSTRING::from(text);
+    let result = unsafe { MessageBoxW(HWND::default(), &text, &title, MB_YESNO | MB_ICONQUESTION) };
+    result == IDYES
+}
+
+/// Menu ID prefix for language choices ("language:auto", "language:ko", ...)
+#[cfg(windows)]
+const LANGUAGE_MENU_PREFIX: &str = "language:";
+
+/// Menu ID prefix for startup method choices
+#[cfg(windows)]
+const STARTUP_METHOD_MENU_PREFIX: &str = "startup_method:";
+
+/// Menu IDs for update channel choices
+#[cfg(windows)]
+const CHANNEL_STABLE_ID: &str = "channel:stable";
+#[cfg(windows)]
+const CHANNEL_PRERELEASE_ID: &str = "channel:prerelease";
+
+/// How long an updated instance waits for the previous one to exit
+#[cfg(windows)]
+const UPDATE_HANDOVER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Print a diagnostic report for offline replay; returns the exit code
+fn replay_report(path: &std::path::Path) -> i32 {
+    match diagnostics::DiagnosticReport::load(path) {
+        Ok(report) => {
+            print!("{}", report.to_text());
+            0
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
+
+#[cfg(not(windows))]
 fn main() {
-    // Single-instance check using named mutex
-    let mutex_name: Vec<u16> = SINGLE_INSTANCE_MUTEX
-        .encode_utf16()
-        .chain(std::iter::once(0))
-        .collect();
+    // Reports can be replayed anywhere; blocking needs Windows
+    if let Some(path) = diagnostics::replay_arg(std::env::args()) {
+        std::process::exit(replay_report(&path));
+    }
+    eprintln!("KakaoTalk AdBlock only runs on Windows");
+    std::process::exit(1);
+}
+
+#[cfg(windows)]
+fn main() {
+    if let Some(path) = diagnostics::replay_arg(std::env::args()) {
+        use windows::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+
+        // Print to the console it was started from
+        let _ = unsafe { AttachConsole(ATTACH_PARENT_PROCESS) };
+        std::process::exit(replay_report(&path));
+    }
+
+    // After a self-update, let the previous process exit and release the mutex
+    if let Some(pid) = updater::wait_for_pid_arg(std::env::args()) {
+        process::wait_for_exit(pid, UPDATE_HANDOVER_TIMEOUT);
+    }
+
+    if let Some(command) = install::parse_args(std::env::args().skip(1)) {
+        run_install_command(command);
+        return;
+    }
 
-    let mutex = unsafe { CreateMutexW(None, true, PCWSTR(mutex_name.as_ptr())) };
-    match mutex {
-        Ok(handle) => {
-            // Check if mutex was already owned (another instance exists)
-            let wait_result = unsafe { WaitForSingleObject(handle, 0) };
-            if wait_result != WAIT_OBJECT_0 {
-                // Another instance is running, exit silently
+    // Command for the running instance (or for this one, once it runs)
+    let forwarded = ipc::parse_args(std::env::args().skip(1));
+
+    // One instance per logon session
+    match instance::acquire() {
+        instance::Instance::First => {}
+        instance::Instance::AlreadyRunning => {
+            // A second logon entry must not pop up the debug window
+            if startup::is_startup_launch(std::env::args()) {
+                logger::log("Already running, exiting");
                 return;
             }
-            // Handle is kept alive for the lifetime of the program
-            // (HANDLE is Copy, so it doesn't have Drop - the OS releases it on process exit)
-            let _ = handle;
+            // Pass the command on to the running instance
+            forward_command(forwarded.unwrap_or(ipc::Command::ShowDebugWindow));
         }
-        Err(_) => {
-            // Failed to create mutex, another instance likely exists
+        instance::Instance::OlderVersionRunning => {
+            // It has no command channel; keep out of its way as before
+            logger::log("An older version is running in this session, exiting");
             return;
         }
     }
+    if let Some(command) = forwarded {
+        let strings = locale::strings_for(settings::load().language.as_deref());
+        report_command_result(command, Err(strings.ipc_not_running.to_string()));
+        std::process::exit(2);
+    }
+    logger::log(format!("KakaoTalkAdBlock {} started", VERSION));
+    if startup::is_startup_launch(std::env::args()) {
+        logger::log("Launched at logon");
+    }
+
+    if let Ok(exe) = std::env::current_exe() {
+        updater::remove_previous_exe(&exe);
+    }
+
+    // Load settings and localized strings
+    let mut settings = settings::load();
+    let mut strings = locale::strings_for(settings.language.as_deref());
+
     // Create shared state
-    let state = Arc::new(Mutex::new(AdBlockState::new()));
+    let state = Arc::new(Mutex::new(AdBlockState::new(profile::active(
+        &settings.profiles,
+    ))));
+    state.lock().dry_run = settings.dry_run;
     let running = Arc::new(AtomicBool::new(true));
 
     // Initialize debug window with shared state
@@ -360,59 +1175,208 @@ fn main() {
         remove_ads(state_clone, running_clone);
     });
 
-    // Create channel for version check result
-    let (version_tx, version_rx) = mpsc::channel::<VersionCheckResult>();
+    let mut script_host = scripting::ScriptHost::new(scripting::TIME_LIMIT);
+    let script_errors = script_host.load_dir(&paths::local_data_dir().join("scripts"));
+    if !script_host.is_empty() || !script_errors.is_empty() {
+        let state_clone = Arc::clone(&state);
+        let running_clone = Arc::clone(&running);
+        thread::spawn(move || run_scripts(state_clone, running_clone, script_host, script_errors));
+    }
+
+    if let Some(path) = settings.metrics_file.clone() {
+        let state_clone = Arc::clone(&state);
+        let running_clone = Arc::clone(&running);
+        thread::spawn(move || export_metrics(state_clone, running_clone, path));
+    }
 
-    // Check for new version in background
+    if !settings.legacy_migration_offered {
+        migrate_legacy(strings, &mut settings);
+    }
+    repair_startup_entry(strings, &mut settings);
+
+    // Check for new versions in background, periodically
+    let (version_tx, version_rx) = mpsc::channel();
+    let (checker_tx, checker_rx) = mpsc::channel();
+    let (install_tx, install_rx) = mpsc::channel();
+    let checker = update::UpdateChecker::new(
+        settings
+            .update_endpoint
+            .as_deref()
+            .unwrap_or(update::DEFAULT_ENDPOINT),
+        settings.update_channel.clone(),
+        network::NetworkConfig::from_settings(&settings),
+    );
     thread::spawn(move || {
-        let (tag_name, has_new) = version::check_latest_version(VERSION);
-        if has_new {
-            let _ = version_tx.send(VersionCheckResult::NewVersionAvailable(tag_name));
-        }
+        update::run_periodic(checker, VERSION, checker_rx, version_tx);
     });
 
-    // Get localized strings
-    let strings = locale::get_strings();
-
     // Create menu
     let menu = Menu::new();
 
     let version_item = MenuItem::new(VERSION, false, None);
     let check_release_item =
         MenuItem::with_id("check_release", strings.check_for_updates, true, None);
+    let release_notes_item =
+        MenuItem::with_id("release_notes", strings.view_release_notes, false, None);
+    let skip_version_item =
+        MenuItem::with_id("skip_version", strings.skip_this_version, false, None);
+    let channel_menu = Submenu::new(strings.update_channel, true);
+    let channel_stable_item = CheckMenuItem::with_id(
+        CHANNEL_STABLE_ID,
+        strings.channel_stable,
+        true,
+        settings.update_channel == update::UpdateChannel::Stable,
+        None,
+    );
+    let channel_prerelease_item = CheckMenuItem::with_id(
+        CHANNEL_PRERELEASE_ID,
+        strings.channel_prerelease,
+        true,
+        settings.update_channel == update::UpdateChannel::Prerelease,
+        None,
+    );
+    // Pinning is configured in settings.json; the menu only shows it
+    let channel_pinned_item = match &settings.update_channel {
+        update::UpdateChannel::Pinned(tag) => Some(CheckMenuItem::new(
+            format!("{}{}", strings.channel_pinned, tag),
+            false,
+            true,
+            None,
+        )),
+        _ => None,
+    };
+    let offline_item = CheckMenuItem::with_id(
+        "offline",
+        strings.offline_mode,
+        true,
+        settings.offline,
+        None,
+    );
     let separator = PredefinedMenuItem::separator();
+    let pause_item = CheckMenuItem::with_id("pause", strings.pause_blocking, true, false, None);
     let debug_item = MenuItem::with_id("debug", strings.show_debug_window, true, None);
-    let startup_item = MenuItem::with_id("startup", strings.run_on_startup, true, None);
+    let report_item = MenuItem::with_id(
+        "diagnostic_report",
+        strings.create_diagnostic_report,
+        true,
+        None,
+    );
+    // Method actually in effect, which may differ from the preference after a fallback
+    let mut startup_enabled = match startup::current_entry() {
+        Some((method, startup::StartupState::Enabled)) => Some(method),
+        _ => None,
+    };
+    let startup_item = MenuItem::with_id(
+        "startup",
+        startup_text(strings, startup_enabled),
+        true,
+        None,
+    );
+    let startup_method_menu = Submenu::new(strings.startup_method, true);
+    let startup_method_items: Vec<(startup::StartupMethod, CheckMenuItem)> =
+        startup::StartupMethod::ALL
+            .into_iter()
+            .map(|method| {
+                let item = CheckMenuItem::with_id(
+                    startup_method_id(method),
+                    startup_method_name(strings, method),
+                    true,
+                    startup_enabled.unwrap_or(settings.startup_method) == method,
+                    None,
+                );
+                (method, item)
+            })
+            .collect();
+    let language_menu = Submenu::new(strings.language, true);
+    let language_auto_item = CheckMenuItem::with_id(
+        format!("{}auto", LANGUAGE_MENU_PREFIX),
+        strings.language_auto,
+        true,
+        settings.language.is_none(),
+        None,
+    );
+    let language_items: Vec<(&str, CheckMenuItem)> = locale::available_languages()
+        .map(|code| {
+            let item = CheckMenuItem::with_id(
+                format!("{}{}", LANGUAGE_MENU_PREFIX, code),
+                locale::language_name(code),
+                true,
+                settings.language.as_deref() == Some(code),
+                None,
+            );
+            (code, item)
+        })
+        .collect();
     let exit_item = MenuItem::with_id("exit", strings.exit, true, None);
 
+    channel_menu.append(&channel_stable_item).unwrap();
+    channel_menu.append(&channel_prerelease_item).unwrap();
+    if let Some(item) = &channel_pinned_item {
+        channel_menu.append(item).unwrap();
+    }
+
+    for (_, item) in &startup_method_items {
+        startup_method_menu.append(item).unwrap();
+    }
+
+    language_menu.append(&language_auto_item).unwrap();
+    for (_, item) in &language_items {
+        language_menu.append(item).unwrap();
+    }
+
     menu.append(&version_item).unwrap();
     menu.append(&check_release_item).unwrap();
+    menu.append(&release_notes_item).unwrap();
+    menu.append(&skip_version_item).unwrap();
+    menu.append(&channel_menu).unwrap();
+    menu.append(&offline_item).unwrap();
     menu.append(&separator).unwrap();
+    menu.append(&pause_item).unwrap();
     menu.append(&debug_item).unwrap();
+    menu.append(&report_item).unwrap();
     menu.append(&startup_item).unwrap();
+    menu.append(&startup_method_menu).unwrap();
+    menu.append(&language_menu).unwrap();
     menu.append(&exit_item).unwrap();
 
-    // Check startup state and update menu
-    if startup::is_startup_enabled() {
-        // Mark as checked by changing the text
-        startup_item.set_text(strings.run_on_startup_checked);
-    }
-
-    // Load icon
-    let icon = icon::load_icon();
-
-    // Create tray icon
-    let _tray_icon = TrayIconBuilder::new()
+    // Create tray icon; it is switched to the actual status in the event loop
+    let mut tray_status = icon::TrayStatus::KakaoTalkNotRunning;
+    let tray_icon = TrayIconBuilder::new()
         .with_menu(Box::new(menu))
-        .with_tooltip("KakaoTalkAdBlock")
-        .with_icon(icon)
+        .with_tooltip(tray_tooltip(strings, tray_status))
+        .with_icon(tray_icon_image(tray_status))
         .build()
         .expect("Failed to create tray icon");
 
-    // Track startup state
-    let mut startup_enabled = startup::is_startup_enabled();
+    // Label and check the startup items after a change
+    let sync_startup_menu = |strings: &locale::Strings,
+                             enabled: Option<startup::StartupMethod>,
+                             preferred: startup::StartupMethod| {
+        startup_item.set_text(startup_text(strings, enabled));
+        for (method, item) in &startup_method_items {
+            item.set_text(startup_method_name(strings, *method));
+            item.set_checked(enabled.unwrap_or(preferred) == *method);
+        }
+    };
     // Track debug window state for menu sync
     let mut debug_window_visible = false;
+    // Result of the latest version check and any update in progress
+    let mut update_status = UpdateStatus {
+        offline: settings.offline,
+        ..Default::default()
+    };
+    check_release_item.set_text(update_status.menu_text(strings));
+    check_release_item.set_enabled(!update_status.offline);
+
+    // Commands from later launches
+    let (ipc_tx, ipc_rx) = mpsc::channel();
+    ipc::listen(ipc_tx);
+
+    // Commands from scripts, if enabled
+    let control_token_file = settings
+        .control_api
+        .then(|| start_control_api(&state))
+        .flatten();
 
     // Event loop with Windows message pump
     let menu_channel = MenuEvent::receiver();
@@ -440,14 +1404,118 @@ fn main() {
             debug_item.set_text(text);
         }
 
+        // Show the current status in the tray
+        let status = {
+            let state = state.lock();
+            // Pausing is also possible through the control API
+            if pause_item.is_checked() != state.paused {
+                pause_item.set_checked(state.paused);
+            }
+            icon::TrayStatus::resolve(
+                state.has_error(),
+                state.paused,
+                state.kakaotalk_running,
+                state.dry_run,
+                update_status.available.is_some(),
+            )
+        };
+        if status != tray_status {
+            tray_status = status;
+            if let Err(e) = tray_icon.set_icon(Some(tray_icon_image(status))) {
+                logger::log(format!("Failed to update tray icon: {}", e));
+            }
+            let _ = tray_icon.set_tooltip(Some(tray_tooltip(strings, status)));
+        }
+
+        // Carry out a command forwarded by another launch
+        if let Ok(request) = ipc_rx.try_recv() {
+            let command = request.command;
+            logger::log(format!("Command from another launch: {}", command.name()));
+            let result = match command {
+                ipc::Command::ShowDebugWindow => {
+                    if !debug_window::is_visible() {
+                        debug_window::toggle();
+                    }
+                    Ok(String::new())
+                }
+                ipc::Command::Pause | ipc::Command::Resume => {
+                    let paused = command == ipc::Command::Pause;
+                    set_paused(&state, &pause_item, paused);
+                    Ok(if paused {
+                        strings.ipc_paused
+                    } else {
+                        strings.ipc_resumed
+                    }
+                    .to_string())
+                }
+                ipc::Command::Quit => Ok(strings.ipc_exiting.to_string()),
+                ipc::Command::ReloadRules => {
+                    state.lock().forget_decisions();
+                    Ok(strings.ipc_rules_reloaded.to_string())
+                }
+                ipc::Command::Status => Ok(status_report(&state.lock(), tray_status)),
+            };
+            request.respond(result);
+            if command == ipc::Command::Quit {
+                running.store(false, Ordering::Relaxed);
+                break;
+            }
+        }
+
         // Check for version check result
         if let Ok(result) = version_rx.try_recv() {
+            let check = match &result {
+                Ok(update::CheckOutcome::UpdateAvailable(_)) => {
+                    metrics::VersionCheck::UpdateAvailable
+                }
+                Ok(update::CheckOutcome::UpToDate(_)) => metrics::VersionCheck::UpToDate,
+                Err(update::CheckError::Offline) => metrics::VersionCheck::Offline,
+                Err(_) => metrics::VersionCheck::Failed,
+            };
+            state
+                .lock()
+                .metrics
+                .set_version_check(check, SystemTime::now());
+            match result {
+                Ok(update::CheckOutcome::UpdateAvailable(release))
+                    if settings.skipped_version.as_deref() != Some(release.tag_name.as_str()) =>
+                {
+                    // A newer release deserves a fresh install attempt
+                    let known = update_status.available.as_ref().map(|r| &r.tag_name);
+                    if known != Some(&release.tag_name) {
+                        update_status.install_error = None;
+                    }
+                    update_status.available = Some(release);
+                    update_status.check_error = None;
+                }
+                // Up to date, the user skipped this release, or nothing was checked
+                Ok(_) | Err(update::CheckError::Offline) => {
+                    update_status.available = None;
+                    update_status.check_error = None;
+                }
+                Err(e) => update_status.check_error = Some(e.to_string()),
+            }
+            check_release_item.set_text(update_status.menu_text(strings));
+            release_notes_item.set_enabled(update_status.available.is_some());
+            skip_version_item.set_enabled(update_status.available.is_some());
+        }
+
+        // Check for update installation result
+        if let Ok(result) = install_rx.try_recv() {
+            update_status.installing = false;
             match result {
-                VersionCheckResult::NewVersionAvailable(tag_name) => {
-                    let text = format!("{}{}", strings.new_version_available, tag_name);
-                    check_release_item.set_text(&text);
+                Ok(()) => {
+                    // The new executable is already starting; hand over to it
+                    logger::log("Update installed, restarting");
+                    running.store(false, Ordering::Relaxed);
+                    break;
+                }
+                Err(e) => {
+                    logger::log(format!("Failed to install update: {}", e));
+                    update_status.install_error = Some(e);
                 }
             }
+            check_release_item.set_text(update_status.menu_text(strings));
         }
 
         // Handle menu events
@@ -458,22 +1526,190 @@ fn main() {
                     break;
                 }
                 "startup" => {
-                    startup_enabled = !startup_enabled;
-                    if let Err(e) = startup::set_startup_enabled(startup_enabled) {
-                        eprintln!("Failed to set startup: {:?}", e);
-                        startup_enabled = !startup_enabled; // Revert
+                    if startup_enabled.is_some() {
+                        if let Err(e) = startup::disable() {
+                            logger::log(format!("Failed to set startup: {}", e));
+                        }
+                        // Some entries may be left behind; show what is in effect
+                        startup_enabled = match startup::current_entry() {
+                            Some((method, startup::StartupState::Enabled)) => Some(method),
+                            _ => None,
+                        };
+                    } else {
+                        match startup::enable(settings.startup_method, startup_delay(&settings)) {
+                            Ok(method) => startup_enabled = Some(method),
+                            Err(e) => logger::log(format!("Failed to set startup: {}", e)),
+                        }
+                    }
+                    sync_startup_menu(strings, startup_enabled, settings.startup_method);
+                }
+                id if id.starts_with(STARTUP_METHOD_MENU_PREFIX) => {
+                    let chosen = startup::StartupMethod::ALL
+                        .into_iter()
+                        .find(|&method| startup_method_id(method) == id);
+                    if let Some(method) = chosen {
+                        settings.startup_method = method;
+                        if let Err(e) = settings::save(&settings) {
+                            logger::log(format!("Failed to save settings: {}", e));
+                        }
+                        // Switch right away if starting at logon is on
+                        if startup_enabled.is_some() {
+                            match startup::enable(method, startup_delay(&settings)) {
+                                Ok(method) => startup_enabled = Some(method),
+                                Err(e) => logger::log(format!("Failed to set startup: {}", e)),
+                            }
+                        }
                     }
+                    sync_startup_menu(strings, startup_enabled, settings.startup_method);
+                }
+                "check_release" => {
+                    if update_status.installing {
+                        // Already downloading
+                    } else if let (Some(release), None) =
+                        (&update_status.available, &update_status.install_error)
+                    {
+                        // Install in the background; the result arrives on install_rx
+                        update_status.installing = true;
+                        let release = release.clone();
+                        let network = network::NetworkConfig::from_settings(&settings);
+                        let install_tx = install_tx.clone();
+                        thread::spawn(move || {
+                            let _ = install_tx.send(install_update(&release, &network));
+                        });
+                    } else if update_status.available.is_none()
+                        && update_status.check_error.is_some()
+                    {
+                        // Retry the failed check instead of opening the page
+                        let _ = checker_tx.send(update::CheckerCommand::Recheck);
+                    } else {
+                        // Open releases page (also the fallback if installing failed)
+                        let _ = open::that(version::RELEASES_PAGE_URL);
+                    }
+                    check_release_item.set_text(update_status.menu_text(strings));
+                }
+                "release_notes" => {
+                    if let Some(release) = &update_status.available {
+                        debug_window::show_release_notes(release);
+                    }
+                }
+                "skip_version" if !update_status.installing => {
+                    if let Some(release) = update_status.available.take() {
+                        // Stay quiet until a newer release than this one appears
+                        settings.skipped_version = Some(release.tag_name);
+                        if let Err(e) = settings::save(&settings) {
+                            logger::log(format!("Failed to save settings: {}", e));
+                        }
+                        update_status.install_error = None;
+                        check_release_item.set_text(update_status.menu_text(strings));
+                        release_notes_item.set_enabled(false);
+                        skip_version_item.set_enabled(false);
+                    }
+                }
+                "diagnostic_report" => {
+                    // Collect in the background and reveal the archive in Explorer
+                    let state_clone = Arc::clone(&state);
+                    thread::spawn(move || match diagnostics::create_report(&state_clone) {
+                        Ok(path) => {
+                            let _ = std::process::Command::new("explorer")
+                                .arg(format!("/select,{}", path.display()))
+                                .spawn();
+                        }
+                        Err(e) => logger::log(format!("Failed to create diagnostic report: {}", e)),
+                    });
+                }
+                CHANNEL_STABLE_ID | CHANNEL_PRERELEASE_ID => {
+                    settings.update_channel = if event.id.0 == CHANNEL_STABLE_ID {
+                        update::UpdateChannel::Stable
+                    } else {
+                        update::UpdateChannel::Prerelease
+                    };
+                    if let Err(e) = settings::save(&settings) {
+                        logger::log(format!("Failed to save settings: {}", e));
+                    }
+
+                    // Keep exactly one channel checked
+                    channel_stable_item
+                        .set_checked(settings.update_channel == update::UpdateChannel::Stable);
+                    channel_prerelease_item
+                        .set_checked(settings.update_channel == update::UpdateChannel::Prerelease);
+                    if let Some(item) = &channel_pinned_item {
+                        item.set_checked(false);
+                    }
+
+                    let _ = checker_tx.send(update::CheckerCommand::SetChannel(
+                        settings.update_channel.clone(),
+                    ));
+                }
+                "offline" => {
+                    settings.offline = !settings.offline;
+                    if let Err(e) = settings::save(&settings) {
+                        logger::log(format!("Failed to save settings: {}", e));
+                    }
+                    offline_item.set_checked(settings.offline);
+
+                    // Going offline forgets results that can no longer be acted on
+                    update_status.offline = settings.offline;
+                    if settings.offline && !update_status.installing {
+                        update_status.available = None;
+                        update_status.check_error = None;
+                        release_notes_item.set_enabled(false);
+                        skip_version_item.set_enabled(false);
+                    }
+                    check_release_item.set_text(update_status.menu_text(strings));
+                    check_release_item.set_enabled(!settings.offline);
 
-                    let text = if startup_enabled {
-                        strings.run_on_startup_checked
+                    let _ = checker_tx.send(update::CheckerCommand::SetNetwork(
+                        network::NetworkConfig::from_settings(&settings),
+                    ));
+                }
+                id if id.starts_with(LANGUAGE_MENU_PREFIX) => {
+                    let code = &id[LANGUAGE_MENU_PREFIX.len()..];
+                    settings.language = if code == "auto" {
+                        None
                     } else {
-                        strings.run_on_startup
+                        Some(code.to_string())
                     };
-                    startup_item.set_text(text);
+                    if let Err(e) = settings::save(&settings) {
+                        logger::log(format!("Failed to save settings: {}", e));
+                    }
+
+                    // Keep exactly one language checked
+                    language_auto_item.set_checked(settings.language.is_none());
+                    for (code, item) in &language_items {
+                        item.set_checked(settings.language.as_deref() == Some(*code));
+                    }
+
+                    // Relabel the menu in the new language
+                    strings = locale::strings_for(settings.language.as_deref());
+                    check_release_item.set_text(update_status.menu_text(strings));
+                    release_notes_item.set_text(strings.view_release_notes);
+                    skip_version_item.set_text(strings.skip_this_version);
+                    channel_menu.set_text(strings.update_channel);
+                    channel_stable_item.set_text(strings.channel_stable);
+                    channel_prerelease_item.set_text(strings.channel_prerelease);
+                    if let (Some(item), update::UpdateChannel::Pinned(tag)) =
+                        (&channel_pinned_item, &settings.update_channel)
+                    {
+                        item.set_text(format!("{}{}", strings.channel_pinned, tag));
+                    }
+                    offline_item.set_text(strings.offline_mode);
+                    pause_item.set_text(strings.pause_blocking);
+                    let _ = tray_icon.set_tooltip(Some(tray_tooltip(strings, tray_status)));
+                    debug_item.set_text(if debug_window_visible {
+                        strings.hide_debug_window
+                    } else {
+                        strings.show_debug_window
+                    });
+                    report_item.set_text(strings.create_diagnostic_report);
+                    startup_method_menu.set_text(strings.startup_method);
+                    sync_startup_menu(strings, startup_enabled, settings.startup_method);
+                    language_menu.set_text(strings.language);
+                    language_auto_item.set_text(strings.language_auto);
+                    exit_item.set_text(strings.exit);
                 }
-                "check_release" => {
-                    // Open releases page
-                    let _ = open::that(version::RELEASES_PAGE_URL);
+                "pause" => {
+                    let paused = !state.lock().paused;
+                    set_paused(&state, &pause_item, paused);
                 }
                 "debug" => {
                     // Toggle debug window
@@ -493,6 +1729,10 @@ fn main() {
         thread::sleep(Duration::from_millis(10));
     }
 
+    if let Some(path) = control_token_file {
+        let _ = std::fs::remove_file(path);
+    }
+
     // Wait for background threads to finish
     let _ = watch_thread.join();
     let _ = remove_thread.join();
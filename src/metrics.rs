@@ -0,0 +1,395 @@
+//! Blocker health metrics in the Prometheus text format
+//!
+//! The registry only counts what it is told; the window threads feed it and
+//! the rendered text is written to a file for node_exporter's textfile
+//! collector (or anything else that reads the exposition format).
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::audit::AuditAction;
+
+/// Prefix of every metric name
+const PREFIX: &str = "kakaotalk_adblock";
+
+/// Upper bounds of the tick duration buckets, in seconds
+const TICK_BUCKETS: [f64; 9] = [0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0];
+
+/// How often the metrics file is rewritten
+pub const EXPORT_INTERVAL: Duration = Duration::from_secs(15);
+
+/// A background loop whose ticks are timed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Tick {
+    WatchWindows,
+    RemoveAds,
+}
+
+impl Tick {
+    const ALL: [Tick; 2] = [Tick::WatchWindows, Tick::RemoveAds];
+
+    fn name(self) -> &'static str {
+        match self {
+            Tick::WatchWindows => "watch_windows",
+            Tick::RemoveAds => "remove_ads",
+        }
+    }
+}
+
+/// Result of the latest version check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionCheck {
+    UpToDate,
+    UpdateAvailable,
+    /// Offline mode is on; nothing was requested
+    Offline,
+    Failed,
+}
+
+impl VersionCheck {
+    const ALL: [VersionCheck; 4] = [
+        VersionCheck::UpToDate,
+        VersionCheck::UpdateAvailable,
+        VersionCheck::Offline,
+        VersionCheck::Failed,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            VersionCheck::UpToDate => "up-to-date",
+            VersionCheck::UpdateAvailable => "update-available",
+            VersionCheck::Offline => "offline",
+            VersionCheck::Failed => "failed",
+        }
+    }
+}
+
+/// Observations of a duration, counted per bucket (not cumulative)
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Histogram {
+    buckets: [u64; TICK_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        if let Some(i) = TICK_BUCKETS.iter().position(|&bound| seconds <= bound) {
+            self.buckets[i] += 1;
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+/// Counters, gauges and histograms since start
+#[derive(Debug, Default)]
+pub struct Metrics {
+    actions: HashMap<AuditAction, u64>,
+    main_windows: usize,
+    ad_candidates: usize,
+    enumeration_failures: u64,
+    ticks: HashMap<Tick, Histogram>,
+    /// Latest result and when it arrived
+    version_check: Option<(VersionCheck, SystemTime)>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count an action actually taken (not one skipped in dry-run mode)
+    pub fn record_action(&mut self, action: AuditAction) {
+        *self.actions.entry(action).or_default() += 1;
+    }
+
+    pub fn record_tick(&mut self, tick: Tick, duration: Duration) {
+        self.ticks.entry(tick).or_default().observe(duration);
+    }
+
+    pub fn record_enumeration_failure(&mut self) {
+        self.enumeration_failures += 1;
+    }
+
+    /// Set the numbers of tracked windows
+    pub fn set_windows(&mut self, main_windows: usize, ad_candidates: usize) {
+        self.main_windows = main_windows;
+        self.ad_candidates = ad_candidates;
+    }
+
+    pub fn set_version_check(&mut self, result: VersionCheck, at: SystemTime) {
+        self.version_check = Some((result, at));
+    }
+
+    /// Everything in the Prometheus text exposition format
+    pub fn render(&self, version: &str) -> String {
+        let mut out = String::new();
+
+        header(&mut out, "build_info", "gauge", "Running version");
+        sample(&mut out, "build_info", &[("version", version)], 1);
+
+        header(
+            &mut out,
+            "actions_total",
+            "counter",
+            "Actions taken on KakaoTalk windows",
+        );
+        for (action, name) in [
+            (AuditAction::Close, "close"),
+            (AuditAction::Resize, "resize"),
+            (AuditAction::Hide, "hide"),
+        ] {
+            let count = self.actions.get(&action).copied().unwrap_or(0);
+            sample(&mut out, "actions_total", &[("action", name)], count);
+        }
+
+        header(
+            &mut out,
+            "main_windows",
+            "gauge",
+            "Tracked KakaoTalk main windows",
+        );
+        sample(&mut out, "main_windows", &[], self.main_windows);
+        header(
+            &mut out,
+            "ad_candidates",
+            "gauge",
+            "Tracked ad window candidates",
+        );
+        sample(&mut out, "ad_candidates", &[], self.ad_candidates);
+
+        header(
+            &mut out,
+            "enumeration_failures_total",
+            "counter",
+            "Failed process scans",
+        );
+        sample(
+            &mut out,
+            "enumeration_failures_total",
+            &[],
+            self.enumeration_failures,
+        );
+
+        header(
+            &mut out,
+            "tick_duration_seconds",
+            "histogram",
+            "Duration of one pass of a background loop",
+        );
+        for tick in Tick::ALL {
+            let histogram = self.ticks.get(&tick).cloned().unwrap_or_default();
+            let mut cumulative = 0;
+            for (bound, count) in TICK_BUCKETS.iter().zip(histogram.buckets) {
+                cumulative += count;
+                let le = bound.to_string();
+                let labels = [("loop", tick.name()), ("le", le.as_str())];
+                sample(
+                    &mut out,
+                    "tick_duration_seconds_bucket",
+                    &labels,
+                    cumulative,
+                );
+            }
+            let labels = [("loop", tick.name()), ("le", "+Inf")];
+            sample(
+                &mut out,
+                "tick_duration_seconds_bucket",
+                &labels,
+                histogram.count,
+            );
+            let labels = [("loop", tick.name())];
+            sample(
+                &mut out,
+                "tick_duration_seconds_sum",
+                &labels,
+                histogram.sum,
+            );
+            sample(
+                &mut out,
+                "tick_duration_seconds_count",
+                &labels,
+                histogram.count,
+            );
+        }
+
+        header(
+            &mut out,
+            "version_check",
+            "gauge",
+            "Result of the latest version check (1 for the current result)",
+        );
+        let current = self.version_check.map(|(result, _)| result);
+        for result in VersionCheck::ALL {
+            let value = u8::from(current == Some(result));
+            sample(
+                &mut out,
+                "version_check",
+                &[("result", result.name())],
+                value,
+            );
+        }
+        if let Some((_, at)) = self.version_check {
+            let seconds = at.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+            header(
+                &mut out,
+                "version_check_timestamp_seconds",
+                "gauge",
+                "When the latest version check finished",
+            );
+            sample(&mut out, "version_check_timestamp_seconds", &[], seconds);
+        }
+
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {}_{} {}", PREFIX, name, help);
+    let _ = writeln!(out, "# TYPE {}_{} {}", PREFIX, name, kind);
+}
+
+fn sample(out: &mut String, name: &str, labels: &[(&str, &str)], value: impl std::fmt::Display) {
+    let _ = write!(out, "{}_{}", PREFIX, name);
+    if !labels.is_empty() {
+        let labels: Vec<String> = labels
+            .iter()
+            .map(|(key, value)| format!("{}=\"{}\"", key, escape(value)))
+            .collect();
+        let _ = write!(out, "{{{}}}", labels.join(","));
+    }
+    let _ = writeln!(out, " {}", value);
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Replace the metrics file, so readers never see it half-written
+pub fn write_file(path: &Path, text: &str) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    let temp = path.with_extension("tmp");
+    std::fs::write(&temp, text)
+        .map_err(|e| format!("Failed to write {}: {}", temp.display(), e))?;
+    std::fs::rename(&temp, path).map_err(|e| format!("Failed to replace {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    fn lines(metrics: &Metrics) -> Vec<String> {
+        metrics
+            .render("20250101-01")
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_counters_and_gauges() {
+        let mut metrics = Metrics::new();
+        metrics.record_action(AuditAction::Hide);
+        metrics.record_action(AuditAction::Hide);
+        metrics.record_action(AuditAction::Close);
+        metrics.record_enumeration_failure();
+        metrics.set_windows(2, 5);
+
+        let lines = lines(&metrics);
+        for expected in [
+            "kakaotalk_adblock_build_info{version=\"20250101-01\"} 1",
+            "kakaotalk_adblock_actions_total{action=\"close\"} 1",
+            "kakaotalk_adblock_actions_total{action=\"resize\"} 0",
+            "kakaotalk_adblock_actions_total{action=\"hide\"} 2",
+            "kakaotalk_adblock_main_windows 2",
+            "kakaotalk_adblock_ad_candidates 5",
+            "kakaotalk_adblock_enumeration_failures_total 1",
+        ] {
+            assert!(
+                lines.contains(&expected.to_string()),
+                "missing {}",
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_histogram_buckets_are_cumulative() {
+        let mut metrics = Metrics::new();
+        metrics.record_tick(Tick::RemoveAds, Duration::from_micros(500));
+        metrics.record_tick(Tick::RemoveAds, Duration::from_millis(20));
+        metrics.record_tick(Tick::RemoveAds, Duration::from_secs(2));
+
+        let lines = lines(&metrics);
+        let bucket = |le: &str| {
+            format!(
+                "kakaotalk_adblock_tick_duration_seconds_bucket{{loop=\"remove_ads\",le=\"{}\"}}",
+                le
+            )
+        };
+        for (le, count) in [
+            ("0.001", 1),
+            ("0.01", 1),
+            ("0.025", 2),
+            ("1", 2),
+            ("+Inf", 3),
+        ] {
+            assert!(lines.contains(&format!("{} {}", bucket(le), count)));
+        }
+        assert!(lines.contains(
+            &"kakaotalk_adblock_tick_duration_seconds_count{loop=\"remove_ads\"} 3".to_string()
+        ));
+        assert!(lines.contains(
+            &"kakaotalk_adblock_tick_duration_seconds_sum{loop=\"remove_ads\"} 2.0205".to_string()
+        ));
+        // Loops that never ran still export empty histograms
+        assert!(lines.contains(
+            &"kakaotalk_adblock_tick_duration_seconds_count{loop=\"watch_windows\"} 0".to_string()
+        ));
+    }
+
+    #[test]
+    fn test_version_check() {
+        let mut metrics = Metrics::new();
+        let text = metrics.render("1");
+        assert!(text.contains("kakaotalk_adblock_version_check{result=\"up-to-date\"} 0"));
+        assert!(!text.contains("version_check_timestamp_seconds"));
+
+        let at = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        metrics.set_version_check(VersionCheck::UpdateAvailable, at);
+        let lines = lines(&metrics);
+        assert!(lines.contains(
+            &"kakaotalk_adblock_version_check{result=\"update-available\"} 1".to_string()
+        ));
+        assert!(lines.contains(&"kakaotalk_adblock_version_check{result=\"failed\"} 0".to_string()));
+        assert!(lines
+            .contains(&"kakaotalk_adblock_version_check_timestamp_seconds 1700000000".to_string()));
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape(r#"a"b\c"#), r#"a\"b\\c"#);
+    }
+
+    #[test]
+    fn test_write_file() {
+        let dir = TestDir::new("metrics");
+        let path = dir.join("out").join("adblock.prom");
+        write_file(&path, "a 1\n").unwrap();
+        write_file(&path, "a 2\n").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a 2\n");
+        assert!(!path.with_extension("tmp").exists());
+    }
+}
@@ -0,0 +1,46 @@
+//! Per-user data locations
+
+use std::path::PathBuf;
+
+const APP_DIR_NAME: &str = "KakaoTalkAdBlock";
+
+/// Per-user, machine-local data directory (`%LOCALAPPDATA%\KakaoTalkAdBlock`)
+pub fn local_data_dir() -> PathBuf {
+    std::env::var_os("LOCALAPPDATA")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+        .join(APP_DIR_NAME)
+}
+
+/// Per-user roaming configuration directory (`%APPDATA%\KakaoTalkAdBlock`)
+pub fn config_dir() -> PathBuf {
+    std::env::var_os("APPDATA")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+        .join(APP_DIR_NAME)
+}
+
+/// The user's Startup folder (programs started at logon)
+pub fn startup_folder() -> PathBuf {
+    std::env::var_os("APPDATA")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+        .join(r"Microsoft\Windows\Start Menu\Programs\Startup")
+}
+
+/// Per-user program directory (`%LOCALAPPDATA%\Programs\KakaoTalkAdBlock`)
+pub fn program_dir() -> PathBuf {
+    std::env::var_os("LOCALAPPDATA")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+        .join("Programs")
+        .join(APP_DIR_NAME)
+}
+
+/// The user's Start menu programs folder
+pub fn start_menu_folder() -> PathBuf {
+    std::env::var_os("APPDATA")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+        .join(r"Microsoft\Windows\Start Menu\Programs")
+}
@@ -0,0 +1,55 @@
+//! Wildcard patterns
+//!
+//! Used for executable names in profiles and for host names in the system
+//! proxy bypass list. `*` matches any run of characters (including none), `?`
+//! matches exactly one character, and case is ignored.
+
+/// Whether all of `text` matches `pattern`
+pub fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+
+    // Greedy matching that backtracks to the last `*`
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("kakaotalk.exe", "KakaoTalk.exe"));
+        assert!(!wildcard_match("kakaotalk.exe", "kakaotalk.exe.bak"));
+        assert!(wildcard_match("chat*.exe", "chat.exe"));
+        assert!(wildcard_match("chat*.exe", "ChatClient64.exe"));
+        assert!(wildcard_match("chat?.exe", "chat2.exe"));
+        assert!(!wildcard_match("chat?.exe", "chat.exe"));
+        assert!(wildcard_match("*a*b*", "xxaxxbxx"));
+        assert!(!wildcard_match("*a*b", "xxbxxa"));
+        assert!(wildcard_match("*", ""));
+        assert!(!wildcard_match("", "a"));
+
+        assert!(wildcard_match("*.github.com", "api.GitHub.com"));
+        assert!(wildcard_match("10.*", "10.1.2.3"));
+        assert!(!wildcard_match("*.github.com", "github.com.evil"));
+        assert!(!wildcard_match("*.github.com", "github.com"));
+    }
+}
@@ -0,0 +1,467 @@
+//! Client version from the executable's version resource
+//!
+//! Reads the fixed file version out of the `VS_VERSIONINFO` resource of a PE
+//! file by walking the headers and the resource directory. No Win32 calls, so
+//! it works (and is tested) on any platform, and only the few parts needed
+//! are read from large executables.
+
+use std::fmt;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// `RT_VERSION` resource type
+const RT_VERSION: u32 = 16;
+/// `VS_FIXEDFILEINFO.dwSignature`
+const FIXED_FILE_INFO_SIGNATURE: u32 = 0xFEEF_04BD;
+/// Upper bound for the sizes read, against corrupt headers
+const MAX_READ: usize = 64 * 1024;
+/// PE files have at most 96 sections
+const MAX_SECTIONS: usize = 96;
+
+/// Fixed file version (`major.minor.build.revision`), stored in settings as
+/// an `a.b.c.d` string
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(try_from = "String", into = "String")]
+pub struct FileVersion {
+    pub major: u16,
+    pub minor: u16,
+    pub build: u16,
+    pub revision: u16,
+}
+
+impl FileVersion {
+    pub const fn new(major: u16, minor: u16, build: u16, revision: u16) -> Self {
+        Self {
+            major,
+            minor,
+            build,
+            revision,
+        }
+    }
+
+    /// Newest version matching `a.b.c.d` when missing trailing parts are
+    /// wildcards, so `4.3` is `4.3.65535.65535`
+    pub fn parse_upper_bound(s: &str) -> Result<Self, String> {
+        parse_parts(s, u16::MAX)
+    }
+
+    /// Inverse of [`FileVersion::parse_upper_bound`]
+    pub fn to_upper_bound_string(self) -> String {
+        let parts = [self.major, self.minor, self.build, self.revision];
+        let len = parts
+            .iter()
+            .rposition(|&n| n != u16::MAX)
+            .map_or(1, |i| i + 1);
+        let parts: Vec<String> = parts[..len].iter().map(u16::to_string).collect();
+        parts.join(".")
+    }
+}
+
+impl fmt::Display for FileVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}.{}.{}.{}",
+            self.major, self.minor, self.build, self.revision
+        )
+    }
+}
+
+impl FromStr for FileVersion {
+    type Err = String;
+
+    /// `a.b.c.d`; missing trailing parts are 0
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_parts(s, 0)
+    }
+}
+
+/// `a.b.c.d` with missing trailing parts set to `fill`
+fn parse_parts(s: &str, fill: u16) -> Result<FileVersion, String> {
+    let parts: Vec<&str> = s.trim().split('.').collect();
+    if parts.len() > 4 {
+        return Err(format!("Invalid version: {}", s));
+    }
+    let mut numbers = [fill; 4];
+    for (number, part) in numbers.iter_mut().zip(&parts) {
+        *number = part
+            .parse()
+            .map_err(|_| format!("Invalid version: {}", s))?;
+    }
+    let [major, minor, build, revision] = numbers;
+    Ok(FileVersion::new(major, minor, build, revision))
+}
+
+impl TryFrom<String> for FileVersion {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<FileVersion> for String {
+    fn from(version: FileVersion) -> Self {
+        version.to_string()
+    }
+}
+
+/// Read the file version of an executable
+pub fn read_file_version(path: &Path) -> Result<FileVersion, String> {
+    let mut file = std::fs::File::open(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    parse(&mut file).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Read the file version from a PE image
+pub fn parse<R: Read + Seek>(reader: &mut R) -> Result<FileVersion, String> {
+    let dos = read_at(reader, 0, 64)?;
+    if &dos[..2] != b"MZ" {
+        return Err("not an executable".to_string());
+    }
+    let pe_offset = u64::from(u32_at(&dos, 0x3C));
+
+    let coff = read_at(reader, pe_offset, 24)?;
+    if &coff[..4] != b"PE\0\0" {
+        return Err("missing PE signature".to_string());
+    }
+    let section_count = usize::from(u16_at(&coff, 6));
+    let optional_size = usize::from(u16_at(&coff, 20));
+    if section_count > MAX_SECTIONS {
+        return Err("too many sections".to_string());
+    }
+
+    let optional = read_at(reader, pe_offset + 24, optional_size)?;
+    let (count_offset, directories_offset) = match optional.get(..2).map(|m| u16_at(m, 0)) {
+        Some(0x10B) => (92, 96),   // PE32
+        Some(0x20B) => (108, 112), // PE32+
+        _ => return Err("unknown optional header".to_string()),
+    };
+    let resource_entry = directories_offset + 2 * 8;
+    if optional.len() < resource_entry + 8 || u32_at(&optional, count_offset) < 3 {
+        return Err("no resource directory".to_string());
+    }
+    let resource_rva = u32_at(&optional, resource_entry);
+    if resource_rva == 0 {
+        return Err("no resources".to_string());
+    }
+
+    let sections = read_at(
+        reader,
+        pe_offset + 24 + optional_size as u64,
+        section_count * 40,
+    )?;
+    let sections: Vec<Section> = sections.chunks_exact(40).map(Section::parse).collect();
+    let mut image = Image {
+        reader,
+        sections,
+        resource_rva,
+    };
+
+    // Type, then name (any), then language (any)
+    let version_entry = image
+        .find_entry(0, Some(RT_VERSION))?
+        .ok_or("no version resource")?;
+    let name_entry = image.first_subdirectory_entry(version_entry)?;
+    let data_entry = image.first_subdirectory_entry(name_entry)?;
+    if data_entry & 0x8000_0000 != 0 {
+        return Err("malformed version resource".to_string());
+    }
+    let entry = image.read_resource(data_entry, 8)?;
+    let (data_rva, data_size) = (u32_at(&entry, 0), u32_at(&entry, 4) as usize);
+    let data = image.read_rva(data_rva, data_size.min(MAX_READ))?;
+    parse_version_info(&data)
+}
+
+/// Fixed file info at the start of a `VS_VERSIONINFO` block
+fn parse_version_info(data: &[u8]) -> Result<FileVersion, String> {
+    const KEY: &str = "VS_VERSION_INFO";
+    // wLength, wValueLength, wType, then the key and padding to 32 bits
+    let key_end = 6 + (KEY.len() + 1) * 2;
+    let fixed = (key_end + 3) & !3;
+    if data.len() < fixed + 16 {
+        return Err("version resource is too short".to_string());
+    }
+    let key: Vec<u16> = data[6..key_end - 2]
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    if String::from_utf16_lossy(&key) != KEY || u32_at(data, fixed) != FIXED_FILE_INFO_SIGNATURE {
+        return Err("malformed version resource".to_string());
+    }
+    let (ms, ls) = (u32_at(data, fixed + 8), u32_at(data, fixed + 12));
+    Ok(FileVersion::new(
+        (ms >> 16) as u16,
+        ms as u16,
+        (ls >> 16) as u16,
+        ls as u16,
+    ))
+}
+
+struct Section {
+    virtual_address: u32,
+    virtual_size: u32,
+    raw_size: u32,
+    raw_offset: u32,
+}
+
+impl Section {
+    fn parse(header: &[u8]) -> Self {
+        Self {
+            virtual_size: u32_at(header, 8),
+            virtual_address: u32_at(header, 12),
+            raw_size: u32_at(header, 16),
+            raw_offset: u32_at(header, 20),
+        }
+    }
+}
+
+struct Image<'a, R> {
+    reader: &'a mut R,
+    sections: Vec<Section>,
+    resource_rva: u32,
+}
+
+impl<R: Read + Seek> Image<'_, R> {
+    /// Read `len` bytes at a virtual address
+    fn read_rva(&mut self, rva: u32, len: usize) -> Result<Vec<u8>, String> {
+        let section = self
+            .sections
+            .iter()
+            .find(|s| {
+                let size = s.virtual_size.max(s.raw_size);
+                rva >= s.virtual_address && rva - s.virtual_address < size
+            })
+            .ok_or("address outside all sections")?;
+        let offset = rva - section.virtual_address;
+        if u64::from(offset) + len as u64 > u64::from(section.raw_size) {
+            return Err("data outside its section".to_string());
+        }
+        read_at(
+            self.reader,
+            u64::from(section.raw_offset) + u64::from(offset),
+            len,
+        )
+    }
+
+    /// Read at an offset into the resource directory
+    fn read_resource(&mut self, offset: u32, len: usize) -> Result<Vec<u8>, String> {
+        let rva = self
+            .resource_rva
+            .checked_add(offset & 0x7FFF_FFFF)
+            .ok_or("malformed resource directory")?;
+        self.read_rva(rva, len)
+    }
+
+    /// `OffsetToData` of the entry with the given ID, or of the first entry
+    fn find_entry(&mut self, directory: u32, id: Option<u32>) -> Result<Option<u32>, String> {
+        let header = self.read_resource(directory, 16)?;
+        let count = usize::from(u16_at(&header, 12)) + usize::from(u16_at(&header, 14));
+        if count == 0 {
+            return Ok(None);
+        }
+        let entries = self.read_resource(directory + 16, (count * 8).min(MAX_READ))?;
+        Ok(entries
+            .chunks_exact(8)
+            .find(|entry| id.is_none_or(|id| u32_at(entry, 0) == id))
+            .map(|entry| u32_at(entry, 4)))
+    }
+
+    /// First entry of the subdirectory an entry points to
+    fn first_subdirectory_entry(&mut self, entry: u32) -> Result<u32, String> {
+        if entry & 0x8000_0000 == 0 {
+            return Err("malformed version resource".to_string());
+        }
+        self.find_entry(entry & 0x7FFF_FFFF, None)?
+            .ok_or_else(|| "empty version resource".to_string())
+    }
+}
+
+fn read_at<R: Read + Seek>(reader: &mut R, offset: u64, len: usize) -> Result<Vec<u8>, String> {
+    if len > MAX_READ {
+        return Err("header too large".to_string());
+    }
+    reader
+        .seek(SeekFrom::Start(offset))
+        .map_err(|e| e.to_string())?;
+    let mut buffer = vec![0u8; len];
+    reader
+        .read_exact(&mut buffer)
+        .map_err(|_| "file is truncated".to_string())?;
+    Ok(buffer)
+}
+
+fn u16_at(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+    use std::io::Cursor;
+
+    /// Resource section with one `RT_VERSION` resource (type, name, language)
+    fn resource_section(section_rva: u32, resource_type: u32, version: [u16; 4]) -> Vec<u8> {
+        let mut rsrc = Vec::new();
+        let directory = |rsrc: &mut Vec<u8>, id: u32, target: u32| {
+            rsrc.extend_from_slice(&[0; 12]);
+            rsrc.extend_from_slice(&0u16.to_le_bytes());
+            rsrc.extend_from_slice(&1u16.to_le_bytes());
+            rsrc.extend_from_slice(&id.to_le_bytes());
+            rsrc.extend_from_slice(&target.to_le_bytes());
+        };
+        // Each directory is 24 bytes: header plus one entry
+        directory(&mut rsrc, resource_type, 0x8000_0000 | 24);
+        directory(&mut rsrc, 1, 0x8000_0000 | 48);
+        directory(&mut rsrc, 0x0412, 72);
+
+        let mut info = Vec::new();
+        info.extend_from_slice(&[0; 6]);
+        for unit in "VS_VERSION_INFO\0".encode_utf16() {
+            info.extend_from_slice(&unit.to_le_bytes());
+        }
+        info.extend_from_slice(&[0; 2]); // padding to 32 bits
+        info.extend_from_slice(&FIXED_FILE_INFO_SIGNATURE.to_le_bytes());
+        info.extend_from_slice(&0x0001_0000u32.to_le_bytes());
+        let [a, b, c, d] = version.map(u32::from);
+        info.extend_from_slice(&(a << 16 | b).to_le_bytes());
+        info.extend_from_slice(&(c << 16 | d).to_le_bytes());
+        info.extend_from_slice(&[0; 36]); // rest of VS_FIXEDFILEINFO
+
+        let data_offset = 72 + 16;
+        rsrc.extend_from_slice(&(section_rva + data_offset).to_le_bytes());
+        rsrc.extend_from_slice(&(info.len() as u32).to_le_bytes());
+        rsrc.extend_from_slice(&[0; 8]);
+        rsrc.extend_from_slice(&info);
+        rsrc
+    }
+
+    /// Minimal PE image with a `.text` and a `.rsrc` section
+    fn sample_pe(pe32_plus: bool, resource_type: u32, version: [u16; 4]) -> Vec<u8> {
+        const RSRC_RVA: u32 = 0x2000;
+        const RSRC_OFFSET: u32 = 0x400;
+        let rsrc = resource_section(RSRC_RVA, resource_type, version);
+
+        let mut pe = vec![0u8; 0x80];
+        pe[..2].copy_from_slice(b"MZ");
+        pe[0x3C..0x40].copy_from_slice(&0x80u32.to_le_bytes());
+
+        let optional_size: u16 = if pe32_plus { 240 } else { 224 };
+        pe.extend_from_slice(b"PE\0\0");
+        pe.extend_from_slice(&0x8664u16.to_le_bytes());
+        pe.extend_from_slice(&2u16.to_le_bytes());
+        pe.extend_from_slice(&[0; 12]);
+        pe.extend_from_slice(&optional_size.to_le_bytes());
+        pe.extend_from_slice(&0x22u16.to_le_bytes());
+
+        let mut optional = vec![0u8; usize::from(optional_size)];
+        let magic: u16 = if pe32_plus { 0x20B } else { 0x10B };
+        optional[..2].copy_from_slice(&magic.to_le_bytes());
+        let (count, directories) = if pe32_plus { (108, 112) } else { (92, 96) };
+        optional[count..count + 4].copy_from_slice(&16u32.to_le_bytes());
+        let resource = directories + 16;
+        optional[resource..resource + 4].copy_from_slice(&RSRC_RVA.to_le_bytes());
+        optional[resource + 4..resource + 8].copy_from_slice(&(rsrc.len() as u32).to_le_bytes());
+        pe.extend_from_slice(&optional);
+
+        let section = |name: &[u8], rva: u32, size: u32, offset: u32| {
+            let mut header = [0u8; 40];
+            header[..name.len()].copy_from_slice(name);
+            header[8..12].copy_from_slice(&size.to_le_bytes());
+            header[12..16].copy_from_slice(&rva.to_le_bytes());
+            header[16..20].copy_from_slice(&size.to_le_bytes());
+            header[20..24].copy_from_slice(&offset.to_le_bytes());
+            header
+        };
+        pe.extend_from_slice(&section(b".text", 0x1000, 0x100, 0x300));
+        pe.extend_from_slice(&section(b".rsrc", RSRC_RVA, rsrc.len() as u32, RSRC_OFFSET));
+
+        pe.resize(RSRC_OFFSET as usize, 0);
+        pe.extend_from_slice(&rsrc);
+        pe
+    }
+
+    fn parse_bytes(bytes: Vec<u8>) -> Result<FileVersion, String> {
+        parse(&mut Cursor::new(bytes))
+    }
+
+    #[test]
+    fn test_parse_version_resource() {
+        let expected = FileVersion::new(25, 8, 2, 4567);
+        for pe32_plus in [false, true] {
+            let pe = sample_pe(pe32_plus, RT_VERSION, [25, 8, 2, 4567]);
+            assert_eq!(parse_bytes(pe), Ok(expected));
+        }
+    }
+
+    #[test]
+    fn test_rejects_invalid_files() {
+        assert_eq!(
+            parse_bytes(b"#!/bin/sh\n".repeat(10)).unwrap_err(),
+            "not an executable"
+        );
+        assert_eq!(
+            parse_bytes(sample_pe(true, 3, [1, 0, 0, 0])).unwrap_err(),
+            "no version resource"
+        );
+
+        let mut pe = sample_pe(true, RT_VERSION, [1, 0, 0, 0]);
+        pe.truncate(pe.len() - 20);
+        assert_eq!(parse_bytes(pe).unwrap_err(), "file is truncated");
+
+        let mut pe = sample_pe(true, RT_VERSION, [1, 0, 0, 0]);
+        pe[0x80] = b'X';
+        assert_eq!(parse_bytes(pe).unwrap_err(), "missing PE signature");
+
+        // Every byte cut off must give an error, not a panic
+        let pe = sample_pe(false, RT_VERSION, [1, 2, 3, 4]);
+        for len in 0..pe.len() {
+            assert!(parse_bytes(pe[..len].to_vec()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_read_file_version() {
+        let dir = TestDir::new("pe_version");
+        let path = dir.join("client.exe");
+        std::fs::write(&path, sample_pe(true, RT_VERSION, [4, 3, 0, 4261])).unwrap();
+        assert_eq!(
+            read_file_version(&path).map(|v| v.to_string()),
+            Ok("4.3.0.4261".to_string())
+        );
+        let _ = std::fs::remove_file(&path);
+        assert!(read_file_version(&path).is_err());
+    }
+
+    #[test]
+    fn test_file_version_order_and_parse() {
+        assert_eq!("25.8.2.4567".parse(), Ok(FileVersion::new(25, 8, 2, 4567)));
+        assert_eq!("25.8".parse(), Ok(FileVersion::new(25, 8, 0, 0)));
+        assert!("25.x".parse::<FileVersion>().is_err());
+        assert!("1.2.3.4.5".parse::<FileVersion>().is_err());
+        assert!(FileVersion::new(25, 8, 2, 0) < FileVersion::new(25, 10, 0, 0));
+        assert!(FileVersion::new(4, 3, 0, 4261) < FileVersion::new(25, 1, 0, 0));
+
+        let max = u16::MAX;
+        for (text, version, canonical) in [
+            ("4.3", FileVersion::new(4, 3, max, max), "4.3"),
+            ("4", FileVersion::new(4, max, max, max), "4"),
+            ("4.3.0.4261", FileVersion::new(4, 3, 0, 4261), "4.3.0.4261"),
+            ("4.3.0", FileVersion::new(4, 3, 0, max), "4.3.0"),
+        ] {
+            assert_eq!(FileVersion::parse_upper_bound(text), Ok(version));
+            assert_eq!(version.to_upper_bound_string(), canonical);
+        }
+        assert!(FileVersion::parse_upper_bound("4.x").is_err());
+    }
+}
@@ -1,25 +1,46 @@
-//! Process enumeration utilities for finding KakaoTalk process
+//! Process enumeration utilities for finding client processes
+//!
+//! Only processes of the current logon session are found, so each user on a
+//! shared PC blocks ads in (and only in) their own clients. Window
+//! enumeration follows from this, as windows are looked up by process.
 
 use windows::{
-    Win32::Foundation::CloseHandle,
+    core::PWSTR,
+    Win32::Foundation::{CloseHandle, WAIT_OBJECT_0},
     Win32::System::Diagnostics::ToolHelp::{
         CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W,
         TH32CS_SNAPPROCESS,
     },
+    Win32::System::RemoteDesktop::ProcessIdToSessionId,
+    Win32::System::Threading::{
+        OpenProcess, QueryFullProcessImageNameW, TerminateProcess, WaitForSingleObject,
+        PROCESS_NAME_WIN32, PROCESS_QUERY_LIMITED_INFORMATION, PROCESS_SYNCHRONIZE,
+        PROCESS_TERMINATE,
+    },
 };
 
-/// Target executable name (case-insensitive)
-pub const KAKAOTALK_EXE: &str = "kakaotalk.exe";
+/// Find all process IDs in this session whose lowercase executable name
+/// satisfies `matches`
+pub fn find_process_ids_where(matches: impl Fn(&str) -> bool) -> Vec<u32> {
+    try_find_process_ids_where(matches).unwrap_or_default()
+}
+
+fn try_find_process_ids_where(matches: impl Fn(&str) -> bool) -> Result<Vec<u32>, String> {
+    Ok(try_list_processes()?
+        .into_iter()
+        .filter(|(_, name)| matches(name))
+        .map(|(pid, _)| pid)
+        .collect())
+}
 
-/// Find all process IDs matching the given executable name
-pub fn find_process_ids(exe_name: &str) -> Vec<u32> {
-    let mut pids = Vec::new();
+/// All processes in this session, with their lowercase executable names
+pub fn try_list_processes() -> Result<Vec<(u32, String)>, String> {
+    let mut processes = Vec::new();
+    let session = current_session_id();
 
     unsafe {
-        let snapshot = match CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0) {
-            Ok(handle) => handle,
-            Err(_) => return pids,
-        };
+        let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0)
+            .map_err(|e| format!("Failed to list processes: {}", e))?;
 
         let mut entry = PROCESSENTRY32W {
             dwSize: std::mem::size_of::<PROCESSENTRY32W>() as u32,
@@ -32,8 +53,9 @@ pub fn find_process_ids(exe_name: &str) -> Vec<u32> {
                     .trim_matches('\0')
                     .to_lowercase();
 
-                if process_name == exe_name.to_lowercase() {
-                    pids.push(entry.th32ProcessID);
+                // Processes whose session cannot be read belong to other users
+                if session.is_none() || session_id(entry.th32ProcessID) == session {
+                    processes.push((entry.th32ProcessID, process_name));
                 }
 
                 if Process32NextW(snapshot, &mut entry).is_err() {
@@ -45,7 +67,68 @@ pub fn find_process_ids(exe_name: &str) -> Vec<u32> {
         let _ = CloseHandle(snapshot);
     }
 
-    pids
+    Ok(processes)
+}
+
+/// Logon session of this process
+pub fn current_session_id() -> Option<u32> {
+    session_id(std::process::id())
+}
+
+fn session_id(pid: u32) -> Option<u32> {
+    let mut session = 0;
+    unsafe { ProcessIdToSessionId(pid, &mut session) }
+        .ok()
+        .map(|_| session)
+}
+
+/// Get the full executable path of a process
+pub fn get_process_image_path(pid: u32) -> Option<String> {
+    unsafe {
+        let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid).ok()?;
+
+        let mut buffer = [0u16; 1024];
+        let mut size = buffer.len() as u32;
+        let result = QueryFullProcessImageNameW(
+            handle,
+            PROCESS_NAME_WIN32,
+            PWSTR(buffer.as_mut_ptr()),
+            &mut size,
+        );
+
+        let _ = CloseHandle(handle);
+
+        result
+            .ok()
+            .map(|_| String::from_utf16_lossy(&buffer[..size as usize]))
+    }
+}
+
+/// Wait up to `timeout` for a process to exit
+/// Returns true if it exited (or no longer exists)
+pub fn wait_for_exit(pid: u32, timeout: std::time::Duration) -> bool {
+    unsafe {
+        let handle = match OpenProcess(PROCESS_SYNCHRONIZE, false, pid) {
+            Ok(handle) => handle,
+            Err(_) => return true,
+        };
+
+        let result = WaitForSingleObject(handle, timeout.as_millis() as u32);
+        let _ = CloseHandle(handle);
+
+        result == WAIT_OBJECT_0
+    }
+}
+
+/// Forcefully end a process
+pub fn terminate(pid: u32) -> Result<(), String> {
+    unsafe {
+        let handle = OpenProcess(PROCESS_TERMINATE, false, pid)
+            .map_err(|e| format!("Failed to open process {}: {}", pid, e))?;
+        let result = TerminateProcess(handle, 0);
+        let _ = CloseHandle(handle);
+        result.map_err(|e| format!("Failed to stop process {}: {}", pid, e))
+    }
 }
 
 #[cfg(test)]
@@ -53,8 +136,16 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_find_process_ids() {
+    fn test_list_processes() {
         // Should not panic
-        let _ = find_process_ids("explorer.exe");
+        let _ = try_list_processes();
+    }
+
+    #[test]
+    fn test_finds_own_process_in_own_session() {
+        let exe = std::env::current_exe().unwrap();
+        let name = exe.file_name().unwrap().to_string_lossy().to_lowercase();
+        assert!(current_session_id().is_some());
+        assert!(find_process_ids_where(|n| n == name).contains(&std::process::id()));
     }
 }
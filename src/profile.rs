@@ -0,0 +1,321 @@
+//! Target profiles
+//!
+//! A profile says which processes to watch and how their windows look: an
+//! executable pattern, the [`RuleSet`] and the layout constants. KakaoTalk is
+//! the built-in profile; settings.json can add profiles for other clients
+//! built the same way, or turn the built-in one off by its name.
+//!
+//! Client releases change their windows, so a profile can carry variants of
+//! its rules and layout for ranges of client versions. Each process gets the
+//! set that matches its version best.
+
+use serde::{Deserialize, Serialize};
+
+use crate::pattern::wildcard_match;
+use crate::pe_version::FileVersion;
+use crate::rules::{RuleSet, VersionRange};
+
+/// Name of the built-in profile
+pub const KAKAOTALK: &str = "kakaotalk";
+
+/// Executable of the built-in profile
+pub const KAKAOTALK_EXE: &str = "kakaotalk.exe";
+
+/// Newest KakaoTalk release the built-in rules are verified with (any 25.8
+/// build); raise it after checking a new release
+const KAKAOTALK_VERIFIED: FileVersion = FileVersion::new(25, 8, u16::MAX, u16::MAX);
+
+/// Sizes used when shrinking content views over ad areas
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Layout {
+    /// Width of the window shadow kept free on the right
+    pub shadow_padding: i32,
+    /// Height of the banner area below the main view
+    pub main_view_padding: i32,
+}
+
+impl Default for Layout {
+    /// KakaoTalk's layout, as in the original Go implementation
+    fn default() -> Self {
+        Self {
+            shadow_padding: 2,
+            main_view_padding: 31,
+        }
+    }
+}
+
+impl Layout {
+    /// Size of the main view that hides the ad area below it
+    pub fn main_view_size(&self, width: i32, height: i32) -> Option<(i32, i32)> {
+        let height = height - self.main_view_padding;
+        (height >= 1).then_some((width - self.shadow_padding, height))
+    }
+
+    /// Size of the lock screen view that hides the ad area
+    pub fn lock_screen_size(&self, width: i32, height: i32) -> (i32, i32) {
+        (width - self.shadow_padding, height)
+    }
+}
+
+/// Rules and layout for a range of client versions
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Variant {
+    #[serde(flatten)]
+    pub versions: VersionRange,
+    pub rules: RuleSet,
+    pub layout: Layout,
+}
+
+/// One client to block ads in
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub name: String,
+    /// Executable file name, or full path if it contains a `\`; `*` and `?`
+    /// are wildcards and case is ignored
+    pub executable: String,
+    pub enabled: bool,
+    /// Client versions `rules` and `layout` are known to work with
+    #[serde(flatten)]
+    pub versions: VersionRange,
+    pub rules: RuleSet,
+    pub layout: Layout,
+    /// Rules and layouts for other client versions
+    pub variants: Vec<Variant>,
+}
+
+impl Default for Profile {
+    /// The built-in KakaoTalk profile
+    fn default() -> Self {
+        Self {
+            name: KAKAOTALK.to_string(),
+            executable: KAKAOTALK_EXE.to_string(),
+            enabled: true,
+            versions: VersionRange {
+                min_version: None,
+                max_version: Some(KAKAOTALK_VERIFIED),
+            },
+            rules: RuleSet::default(),
+            layout: Layout::default(),
+            variants: Vec::new(),
+        }
+    }
+}
+
+impl Profile {
+    /// Whether a process is this profile's client; the image path is only
+    /// looked up for path patterns
+    pub fn matches(&self, exe_name: &str, image_path: impl FnOnce() -> Option<String>) -> bool {
+        if self.executable.contains(['\\', '/']) {
+            image_path().is_some_and(|path| wildcard_match(&self.executable, &path))
+        } else {
+            wildcard_match(&self.executable, exe_name)
+        }
+    }
+
+    /// The version range, rules and layout of each rule set, the main one first
+    fn rule_sets(&self) -> impl DoubleEndedIterator<Item = (&VersionRange, &RuleSet, &Layout)> {
+        std::iter::once((&self.versions, &self.rules, &self.layout)).chain(
+            self.variants
+                .iter()
+                .map(|v| (&v.versions, &v.rules, &v.layout)),
+        )
+    }
+
+    /// Rules and layout for a client version: the matching set with the
+    /// highest minimum version, else the newest set that ends before the
+    /// client. The main set is used when nothing fits or the version is
+    /// unknown.
+    pub fn select(&self, version: Option<FileVersion>) -> (&RuleSet, &Layout) {
+        let Some(version) = version else {
+            return (&self.rules, &self.layout);
+        };
+        // `max_by_key` keeps the last of equal keys; reversing keeps the first
+        let best = self
+            .rule_sets()
+            .filter(|(range, _, _)| range.contains(version))
+            .rev()
+            .max_by_key(|(range, _, _)| range.min_version)
+            .or_else(|| {
+                self.rule_sets()
+                    .rev()
+                    .filter(|(range, _, _)| range.max_version.is_some_and(|max| max < version))
+                    .max_by_key(|(range, _, _)| (range.max_version, range.min_version))
+            });
+        best.map_or((&self.rules, &self.layout), |(_, rules, layout)| {
+            (rules, layout)
+        })
+    }
+
+    /// Newest version the rule sets are known to work with, if every set has
+    /// an upper bound
+    pub fn newest_known_version(&self) -> Option<FileVersion> {
+        self.rule_sets()
+            .map(|(range, _, _)| range.max_version)
+            .collect::<Option<Vec<_>>>()?
+            .into_iter()
+            .max()
+    }
+
+    /// Whether a client is newer than every rule set, so its ads may not be
+    /// recognised
+    pub fn is_newer_than_known(&self, version: FileVersion) -> bool {
+        self.newest_known_version()
+            .is_some_and(|newest| version > newest)
+    }
+}
+
+/// The enabled profiles: the built-in one unless replaced by a profile of the
+/// same name, followed by the configured ones
+pub fn active(configured: &[Profile]) -> Vec<Profile> {
+    let builtin = Profile::default();
+    let mut profiles = Vec::new();
+    if !configured.iter().any(|p| p.name == builtin.name) {
+        profiles.push(builtin);
+    }
+    profiles.extend(configured.iter().cloned());
+    profiles.retain(|p| p.enabled && !p.executable.is_empty());
+    profiles
+}
+
+/// A running client process and the index of its profile
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Target {
+    pub pid: u32,
+    pub profile: usize,
+    /// From the executable's version resource, once read
+    pub version: Option<FileVersion>,
+}
+
+/// Processes of this session that belong to one of `profiles`
+#[cfg(windows)]
+pub fn find_targets(profiles: &[Profile]) -> Result<Vec<Target>, String> {
+    let processes = crate::process::try_list_processes()?;
+    Ok(processes
+        .into_iter()
+        .filter_map(|(pid, name)| {
+            profiles
+                .iter()
+                .position(|p| p.matches(&name, || crate::process::get_process_image_path(pid)))
+                .map(|profile| Target {
+                    pid,
+                    profile,
+                    version: None,
+                })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_name_or_path() {
+        let mut profile = Profile {
+            name: "other".to_string(),
+            executable: "other.exe".to_string(),
+            ..Profile::default()
+        };
+        assert!(profile.matches("other.exe", || panic!("path not needed")));
+        assert!(!profile.matches("kakaotalk.exe", || None));
+
+        profile.executable = r"C:\Program Files\Other\*\other.exe".to_string();
+        assert!(profile.matches("other.exe", || Some(
+            r"C:\Program Files\Other\2.1\Other.exe".to_string()
+        )));
+        assert!(!profile.matches("other.exe", || Some(r"D:\other.exe".to_string())));
+        assert!(!profile.matches("other.exe", || None));
+    }
+
+    #[test]
+    fn test_active_profiles() {
+        let names = |profiles: Vec<Profile>| -> Vec<String> {
+            profiles.into_iter().map(|p| p.name).collect()
+        };
+        assert_eq!(names(active(&[])), vec![KAKAOTALK]);
+
+        let other = Profile {
+            name: "other".to_string(),
+            executable: "other.exe".to_string(),
+            ..Profile::default()
+        };
+        assert_eq!(
+            names(active(std::slice::from_ref(&other))),
+            vec![KAKAOTALK, "other"]
+        );
+
+        let kakaotalk_off = Profile {
+            enabled: false,
+            ..Profile::default()
+        };
+        assert_eq!(names(active(&[kakaotalk_off, other])), vec!["other"]);
+    }
+
+    #[test]
+    fn test_select_by_version() {
+        let profile: Profile = serde_json::from_str(
+            r#"{
+                "max_version": "4.3",
+                "variants": [
+                    {"min_version": "25.0", "max_version": "25.8", "layout": {"main_view_padding": 40}},
+                    {"min_version": "25.5", "max_version": "25.8", "layout": {"main_view_padding": 45}}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let padding = |version: Option<&str>| {
+            profile
+                .select(version.map(|v| v.parse().unwrap()))
+                .1
+                .main_view_padding
+        };
+        assert_eq!(padding(None), 31);
+        assert_eq!(padding(Some("4.3.0.4261")), 31);
+        assert_eq!(padding(Some("25.1")), 40);
+        assert_eq!(padding(Some("25.8.2.4567")), 45);
+        // Between sets, and newer than all of them
+        assert_eq!(padding(Some("24.1")), 31);
+        assert_eq!(padding(Some("26.1")), 45);
+
+        assert_eq!(
+            profile.newest_known_version(),
+            Some(FileVersion::new(25, 8, u16::MAX, u16::MAX))
+        );
+        assert!(!profile.is_newer_than_known("25.8.2.4567".parse().unwrap()));
+        assert!(profile.is_newer_than_known("26.1".parse().unwrap()));
+
+        // The built-in profile warns about releases after the verified one
+        let builtin = Profile::default();
+        assert!(!builtin.is_newer_than_known("25.8.2.4567".parse().unwrap()));
+        assert!(builtin.is_newer_than_known("25.9".parse().unwrap()));
+        assert_eq!(
+            builtin.select(Some("26.1".parse().unwrap())).0,
+            &builtin.rules
+        );
+
+        // Open-ended sets are never outgrown
+        let open = Profile {
+            versions: VersionRange::default(),
+            ..Profile::default()
+        };
+        assert!(!open.is_newer_than_known("99.0".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_partial_profile_uses_defaults() {
+        let profile: Profile = serde_json::from_str(
+            r#"{"name": "other", "executable": "other.exe", "layout": {"main_view_padding": 40}}"#,
+        )
+        .unwrap();
+        assert!(profile.enabled);
+        assert_eq!(profile.versions, VersionRange::default());
+        assert_eq!(profile.rules, RuleSet::default());
+        assert_eq!(profile.layout.shadow_padding, 2);
+        assert_eq!(profile.layout.main_view_size(400, 700), Some((398, 660)));
+        assert_eq!(profile.layout.main_view_size(400, 30), None);
+        assert_eq!(profile.layout.lock_screen_size(400, 700), (398, 700));
+    }
+}
@@ -0,0 +1,325 @@
+//! Small anti-aliased vector rasteriser for the icons
+//!
+//! Paths are flattened to polygons and filled with the nonzero winding rule.
+//! Coverage is exact horizontally and sampled on several sub-scanlines per
+//! pixel row, which is plenty for icon-sized shapes and cheap enough to run in
+//! `build.rs`. Like `icon.rs`, this module must not depend on other crates.
+
+use std::f32::consts::PI;
+
+/// Sub-scanlines per pixel row
+const SUBSAMPLES: usize = 16;
+
+/// Maximum distance between an arc and its flattened polygon, in pixels
+const FLATTEN_TOLERANCE: f32 = 0.01;
+
+/// Closed polygons; overlapping contours of the same orientation add up,
+/// opposite ones (see [`Path::hole`]) cut out
+#[derive(Debug, Clone, Default)]
+pub struct Path {
+    contours: Vec<Vec<(f32, f32)>>,
+}
+
+impl Path {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start a new contour
+    pub fn move_to(&mut self, x: f32, y: f32) -> &mut Self {
+        self.contours.push(vec![(x, y)]);
+        self
+    }
+
+    pub fn line_to(&mut self, x: f32, y: f32) -> &mut Self {
+        match self.contours.last_mut() {
+            Some(contour) => contour.push((x, y)),
+            None => self.contours.push(vec![(x, y)]),
+        }
+        self
+    }
+
+    /// Arc around (cx, cy) from angle `start` to `end` (radians, clockwise
+    /// on screen as y points down), joined to the current point by a line
+    pub fn arc(&mut self, cx: f32, cy: f32, radius: f32, start: f32, end: f32) -> &mut Self {
+        let segments = arc_segments(radius, (end - start).abs());
+        for i in 0..=segments {
+            let angle = start + (end - start) * i as f32 / segments as f32;
+            self.line_to(cx + radius * angle.cos(), cy + radius * angle.sin());
+        }
+        self
+    }
+
+    /// Polygon through `points`, oriented like every other filled shape
+    pub fn polygon(points: &[(f32, f32)]) -> Self {
+        let mut path = Self {
+            contours: vec![points.to_vec()],
+        };
+        path.orient(true);
+        path
+    }
+
+    pub fn rect(left: f32, top: f32, right: f32, bottom: f32) -> Self {
+        Self::polygon(&[(left, top), (right, top), (right, bottom), (left, bottom)])
+    }
+
+    pub fn circle(cx: f32, cy: f32, radius: f32) -> Self {
+        let mut path = Self::new();
+        path.move_to(cx + radius, cy)
+            .arc(cx, cy, radius, 0.0, 2.0 * PI);
+        path.orient(true);
+        path
+    }
+
+    /// The same outline with the opposite orientation, to cut it out of a
+    /// shape it is [`add`](Path::add)ed to
+    pub fn hole(mut self) -> Self {
+        self.orient(false);
+        self
+    }
+
+    /// Union of both paths' contours
+    pub fn add(mut self, other: Path) -> Self {
+        self.contours.extend(other.contours);
+        self
+    }
+
+    /// Move by (dx, dy) after scaling by `scale`
+    pub fn transform(mut self, scale: f32, dx: f32, dy: f32) -> Self {
+        for point in self.contours.iter_mut().flatten() {
+            *point = (point.0 * scale + dx, point.1 * scale + dy);
+        }
+        self
+    }
+
+    /// Give every contour a positive (or negative) signed area
+    fn orient(&mut self, positive: bool) {
+        for contour in &mut self.contours {
+            if (signed_area(contour) > 0.0) != positive {
+                contour.reverse();
+            }
+        }
+    }
+}
+
+fn signed_area(contour: &[(f32, f32)]) -> f32 {
+    let n = contour.len();
+    (0..n)
+        .map(|i| {
+            let (x0, y0) = contour[i];
+            let (x1, y1) = contour[(i + 1) % n];
+            x0 * y1 - x1 * y0
+        })
+        .sum::<f32>()
+        / 2.0
+}
+
+/// Segments keeping an arc within [`FLATTEN_TOLERANCE`] of the true curve
+fn arc_segments(radius: f32, sweep: f32) -> usize {
+    if radius <= FLATTEN_TOLERANCE {
+        return 1;
+    }
+    let step = 2.0 * (1.0 - FLATTEN_TOLERANCE / radius).acos();
+    ((sweep / step).ceil() as usize).max(1)
+}
+
+/// Fraction of each pixel covered by `path`, row by row
+fn coverage(path: &Path, width: usize, height: usize) -> Vec<f32> {
+    // Non-horizontal edges as (x0, y0, x1, y1, winding), top to bottom
+    let edges: Vec<(f32, f32, f32, f32, i32)> = path
+        .contours
+        .iter()
+        .flat_map(|contour| {
+            let n = contour.len();
+            (0..n).map(move |i| (contour[i], contour[(i + 1) % n]))
+        })
+        .filter(|((_, y0), (_, y1))| y0 != y1)
+        .map(|((x0, y0), (x1, y1))| {
+            if y0 < y1 {
+                (x0, y0, x1, y1, 1)
+            } else {
+                (x1, y1, x0, y0, -1)
+            }
+        })
+        .collect();
+
+    let mut cover = vec![0.0f32; width * height];
+    let weight = 1.0 / SUBSAMPLES as f32;
+    let mut crossings: Vec<(f32, i32)> = Vec::new();
+
+    for row in 0..height {
+        let row_cover = &mut cover[row * width..(row + 1) * width];
+        for sub in 0..SUBSAMPLES {
+            let y = row as f32 + (sub as f32 + 0.5) * weight;
+            crossings.clear();
+            crossings.extend(
+                edges
+                    .iter()
+                    .filter(|&&(_, y0, _, y1, _)| y0 <= y && y < y1)
+                    .map(|&(x0, y0, x1, y1, winding)| {
+                        (x0 + (y - y0) / (y1 - y0) * (x1 - x0), winding)
+                    }),
+            );
+            crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+            let mut winding = 0;
+            for pair in crossings.windows(2) {
+                winding += pair[0].1;
+                if winding != 0 {
+                    add_span(row_cover, pair[0].0, pair[1].0, weight);
+                }
+            }
+        }
+    }
+
+    for value in &mut cover {
+        *value = value.min(1.0);
+    }
+    cover
+}
+
+/// Add `weight` times the part of each pixel between x `from` and `to`
+fn add_span(row: &mut [f32], from: f32, to: f32, weight: f32) {
+    let width = row.len() as f32;
+    let (from, to) = (from.clamp(0.0, width), to.clamp(0.0, width));
+    if to <= from {
+        return;
+    }
+    let (first, last) = (from.floor() as usize, to.floor() as usize);
+    if first == last {
+        row[first] += (to - from) * weight;
+        return;
+    }
+    row[first] += (first as f32 + 1.0 - from) * weight;
+    for value in &mut row[first + 1..last] {
+        *value += weight;
+    }
+    if last < row.len() {
+        row[last] += (to - last as f32) * weight;
+    }
+}
+
+/// RGBA image that shapes are painted onto, back to front
+pub struct Canvas {
+    width: usize,
+    height: usize,
+    /// Premultiplied RGBA in 0..1
+    pixels: Vec<[f32; 4]>,
+}
+
+impl Canvas {
+    /// Transparent canvas
+    pub fn new(width: u32, height: u32) -> Self {
+        let (width, height) = (width as usize, height as usize);
+        Self {
+            width,
+            height,
+            pixels: vec![[0.0; 4]; width * height],
+        }
+    }
+
+    /// Paint `path` in `color` (straight RGBA) over what is already there
+    pub fn fill(&mut self, path: &Path, color: [u8; 4]) {
+        let alpha = color[3] as f32 / 255.0;
+        let source = [
+            color[0] as f32 / 255.0 * alpha,
+            color[1] as f32 / 255.0 * alpha,
+            color[2] as f32 / 255.0 * alpha,
+            alpha,
+        ];
+        let cover = coverage(path, self.width, self.height);
+        for (pixel, cover) in self.pixels.iter_mut().zip(cover) {
+            if cover <= 0.0 {
+                continue;
+            }
+            let keep = 1.0 - source[3] * cover;
+            for (channel, source) in pixel.iter_mut().zip(source) {
+                *channel = source * cover + *channel * keep;
+            }
+        }
+    }
+
+    /// Straight (non-premultiplied) RGBA bytes
+    pub fn to_rgba(&self) -> Vec<u8> {
+        let byte = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+        self.pixels
+            .iter()
+            .flat_map(|&[r, g, b, a]| {
+                if a <= 0.0 {
+                    [0; 4]
+                } else {
+                    [byte(r / a), byte(g / a), byte(b / a), byte(a)]
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alpha(canvas: &Canvas) -> Vec<u8> {
+        canvas.to_rgba().chunks(4).map(|p| p[3]).collect()
+    }
+
+    #[test]
+    fn test_pixel_aligned_rect_is_exact() {
+        let mut canvas = Canvas::new(4, 4);
+        canvas.fill(&Path::rect(1.0, 1.0, 3.0, 3.0), [0x10, 0x20, 0x30, 0xFF]);
+        let rgba = canvas.to_rgba();
+        assert_eq!(&rgba[(4 + 1) * 4..(4 + 2) * 4], &[0x10, 0x20, 0x30, 0xFF]);
+        assert_eq!(
+            alpha(&canvas),
+            vec![0, 0, 0, 0, 0, 255, 255, 0, 0, 255, 255, 0, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn test_partial_coverage_is_anti_aliased() {
+        // Half a pixel horizontally, a quarter vertically
+        let mut canvas = Canvas::new(2, 1);
+        canvas.fill(&Path::rect(0.5, 0.0, 2.0, 0.25), [0, 0, 0, 0xFF]);
+        assert_eq!(alpha(&canvas), vec![32, 64]);
+
+        // A circle covers about pi r^2 in total
+        let mut canvas = Canvas::new(20, 20);
+        canvas.fill(&Path::circle(10.0, 10.0, 6.0), [0, 0, 0, 0xFF]);
+        let area: f32 = alpha(&canvas).iter().map(|&a| a as f32 / 255.0).sum();
+        assert!((area - PI * 36.0).abs() < 0.5, "{}", area);
+    }
+
+    #[test]
+    fn test_holes_and_overlaps() {
+        // Overlapping shapes do not double up, holes cut out
+        let path = Path::rect(0.0, 0.0, 2.0, 1.0)
+            .add(Path::rect(1.0, 0.0, 4.0, 1.0))
+            .add(Path::rect(3.0, 0.0, 4.0, 1.0).hole());
+        let mut canvas = Canvas::new(4, 1);
+        canvas.fill(&path, [0, 0, 0, 0xFF]);
+        assert_eq!(alpha(&canvas), vec![255, 255, 255, 0]);
+
+        // A ring
+        let mut canvas = Canvas::new(9, 9);
+        canvas.fill(
+            &Path::circle(4.5, 4.5, 4.0).add(Path::circle(4.5, 4.5, 2.0).hole()),
+            [0, 0, 0, 0xFF],
+        );
+        let alpha = alpha(&canvas);
+        assert_eq!(alpha[4 * 9 + 4], 0);
+        assert_eq!(alpha[4 * 9 + 1], 255);
+    }
+
+    #[test]
+    fn test_source_over_blending() {
+        let mut canvas = Canvas::new(1, 1);
+        canvas.fill(&Path::rect(0.0, 0.0, 1.0, 1.0), [0xFF, 0x00, 0x00, 0xFF]);
+        canvas.fill(&Path::rect(0.0, 0.0, 0.5, 1.0), [0x00, 0x00, 0xFF, 0xFF]);
+        assert_eq!(canvas.to_rgba(), vec![0x80, 0x00, 0x80, 0xFF]);
+
+        // Paint outside the canvas is clipped
+        let mut canvas = Canvas::new(2, 2);
+        canvas.fill(&Path::circle(0.0, 0.0, 10.0), [0, 0, 0, 0xFF]);
+        assert_eq!(alpha(&canvas), vec![255; 4]);
+    }
+}
@@ -0,0 +1,436 @@
+//! Per-user registry access behind a trait
+//!
+//! Code that reads or writes `HKEY_CURRENT_USER` takes a `&dyn Registry`, so
+//! it can be tested against `MemoryRegistry` instead of the real registry.
+//! Key paths are relative to `HKEY_CURRENT_USER` and, like Windows, compare
+//! case-insensitively.
+
+use std::fmt;
+
+/// Why a registry operation failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegistryError {
+    /// The key is protected (e.g. by policy)
+    AccessDenied,
+    /// Any other Win32 error
+    Failed(String),
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegistryError::AccessDenied => write!(f, "access denied"),
+            RegistryError::Failed(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Values under `HKEY_CURRENT_USER`
+pub trait Registry {
+    /// Read a string value; `None` if the key or value does not exist
+    fn read_string(&self, key: &str, name: &str) -> Result<Option<String>, RegistryError>;
+
+    /// Read a DWORD value; `None` if the key or value does not exist
+    fn read_dword(&self, key: &str, name: &str) -> Result<Option<u32>, RegistryError>;
+
+    /// Write a string value, creating the key if needed
+    fn write_string(&self, key: &str, name: &str, value: &str) -> Result<(), RegistryError>;
+
+    /// Delete a value; a missing key or value is not an error
+    fn delete_value(&self, key: &str, name: &str) -> Result<(), RegistryError>;
+
+    /// Names of the values in a key; empty if the key does not exist
+    fn value_names(&self, key: &str) -> Result<Vec<String>, RegistryError>;
+}
+
+/// The real registry of the current user
+#[cfg(windows)]
+pub struct CurrentUser;
+
+#[cfg(windows)]
+mod win32 {
+    use windows::core::PCWSTR;
+    use windows::core::PWSTR;
+    use windows::Win32::Foundation::{
+        ERROR_ACCESS_DENIED, ERROR_FILE_NOT_FOUND, ERROR_NO_MORE_ITEMS, ERROR_SUCCESS, WIN32_ERROR,
+    };
+    use windows::Win32::System::Registry::{
+        RegCloseKey, RegCreateKeyExW, RegDeleteValueW, RegEnumValueW, RegGetValueW, RegOpenKeyExW,
+        RegSetValueExW, HKEY, HKEY_CURRENT_USER, KEY_QUERY_VALUE, KEY_SET_VALUE,
+        REG_OPTION_NON_VOLATILE, REG_ROUTINE_FLAGS, REG_SZ, RRF_RT_REG_DWORD, RRF_RT_REG_SZ,
+    };
+
+    use super::{CurrentUser, Registry, RegistryError};
+
+    fn to_wide(s: &str) -> Vec<u16> {
+        s.encode_utf16().chain(std::iter::once(0)).collect()
+    }
+
+    fn check(result: WIN32_ERROR, action: &str) -> Result<(), RegistryError> {
+        match result {
+            ERROR_SUCCESS => Ok(()),
+            ERROR_ACCESS_DENIED => Err(RegistryError::AccessDenied),
+            e => Err(RegistryError::Failed(format!(
+                "Failed to {}: {:?}",
+                action, e
+            ))),
+        }
+    }
+
+    /// `RegGetValueW` into `data`, returning the size written, or None if missing
+    fn get_value(
+        key: &str,
+        name: &str,
+        flags: REG_ROUTINE_FLAGS,
+        data: *mut std::ffi::c_void,
+        size: &mut u32,
+    ) -> Result<Option<u32>, RegistryError> {
+        let key = to_wide(key);
+        let name = to_wide(name);
+        let result = unsafe {
+            RegGetValueW(
+                HKEY_CURRENT_USER,
+                PCWSTR(key.as_ptr()),
+                PCWSTR(name.as_ptr()),
+                flags,
+                None,
+                (!data.is_null()).then_some(data),
+                Some(size),
+            )
+        };
+        if result == ERROR_FILE_NOT_FOUND {
+            return Ok(None);
+        }
+        check(result, "read registry value")?;
+        Ok(Some(*size))
+    }
+
+    impl Registry for CurrentUser {
+        fn read_string(&self, key: &str, name: &str) -> Result<Option<String>, RegistryError> {
+            // Ask for the size first; values can be longer than any fixed buffer
+            let mut size = 0u32;
+            if get_value(key, name, RRF_RT_REG_SZ, std::ptr::null_mut(), &mut size)?.is_none() {
+                return Ok(None);
+            }
+
+            let mut buffer = vec![0u16; (size as usize).div_ceil(2)];
+            let Some(size) = get_value(
+                key,
+                name,
+                RRF_RT_REG_SZ,
+                buffer.as_mut_ptr().cast(),
+                &mut size,
+            )?
+            else {
+                return Ok(None);
+            };
+            let len = (size as usize / 2).saturating_sub(1);
+            Ok(Some(String::from_utf16_lossy(&buffer[..len])))
+        }
+
+        fn read_dword(&self, key: &str, name: &str) -> Result<Option<u32>, RegistryError> {
+            let mut data = 0u32;
+            let mut size = std::mem::size_of::<u32>() as u32;
+            let found = get_value(
+                key,
+                name,
+                RRF_RT_REG_DWORD,
+                (&mut data as *mut u32).cast(),
+                &mut size,
+            )?;
+            Ok(found.map(|_| data))
+        }
+
+        fn write_string(&self, key: &str, name: &str, value: &str) -> Result<(), RegistryError> {
+            let key = to_wide(key);
+            let name = to_wide(name);
+            let value = to_wide(value);
+            let mut hkey = HKEY::default();
+
+            unsafe {
+                check(
+                    RegCreateKeyExW(
+                        HKEY_CURRENT_USER,
+                        PCWSTR(key.as_ptr()),
+                        0,
+                        PCWSTR::null(),
+                        REG_OPTION_NON_VOLATILE,
+                        KEY_SET_VALUE,
+                        None,
+                        &mut hkey,
+                        None,
+                    ),
+                    "open registry key",
+                )?;
+                let result = RegSetValueExW(
+                    hkey,
+                    PCWSTR(name.as_ptr()),
+                    0,
+                    REG_SZ,
+                    Some(std::slice::from_raw_parts(
+                        value.as_ptr().cast::<u8>(),
+                        value.len() * 2,
+                    )),
+                );
+                let _ = RegCloseKey(hkey);
+                check(result, "set registry value")
+            }
+        }
+
+        fn delete_value(&self, key: &str, name: &str) -> Result<(), RegistryError> {
+            let key = to_wide(key);
+            let name = to_wide(name);
+            let mut hkey = HKEY::default();
+
+            unsafe {
+                let result = RegOpenKeyExW(
+                    HKEY_CURRENT_USER,
+                    PCWSTR(key.as_ptr()),
+                    0,
+                    KEY_SET_VALUE,
+                    &mut hkey,
+                );
+                if result == ERROR_FILE_NOT_FOUND {
+                    return Ok(());
+                }
+                check(result, "open registry key")?;
+
+                let result = RegDeleteValueW(hkey, PCWSTR(name.as_ptr()));
+                let _ = RegCloseKey(hkey);
+                if result == ERROR_FILE_NOT_FOUND {
+                    return Ok(());
+                }
+                check(result, "delete registry value")
+            }
+        }
+
+        fn value_names(&self, key: &str) -> Result<Vec<String>, RegistryError> {
+            let key = to_wide(key);
+            let mut hkey = HKEY::default();
+
+            unsafe {
+                let result = RegOpenKeyExW(
+                    HKEY_CURRENT_USER,
+                    PCWSTR(key.as_ptr()),
+                    0,
+                    KEY_QUERY_VALUE,
+                    &mut hkey,
+                );
+                if result == ERROR_FILE_NOT_FOUND {
+                    return Ok(Vec::new());
+                }
+                check(result, "open registry key")?;
+
+                let mut names = Vec::new();
+                let mut result = ERROR_SUCCESS;
+                for index in 0.. {
+                    // Value names are limited to 16383 characters
+                    let mut buffer = vec![0u16; 16384];
+                    let mut len = buffer.len() as u32;
+                    result = RegEnumValueW(
+                        hkey,
+                        index,
+                        PWSTR(buffer.as_mut_ptr()),
+                        &mut len,
+                        None,
+                        None,
+                        None,
+                        None,
+                    );
+                    if result != ERROR_SUCCESS {
+                        break;
+                    }
+                    names.push(String::from_utf16_lossy(&buffer[..len as usize]));
+                }
+                let _ = RegCloseKey(hkey);
+
+                if result == ERROR_NO_MORE_ITEMS {
+                    Ok(names)
+                } else {
+                    check(result, "list registry values").map(|()| names)
+                }
+            }
+        }
+    }
+}
+
+/// In-memory registry for tests
+#[cfg(test)]
+pub use fake::MemoryRegistry;
+
+#[cfg(test)]
+mod fake {
+    use std::collections::{HashMap, HashSet};
+
+    use parking_lot::Mutex;
+
+    use super::{Registry, RegistryError};
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    enum Value {
+        String(String),
+        Dword(u32),
+    }
+
+    /// Lowercase value name -> (original name, data)
+    type Key = HashMap<String, (String, Value)>;
+
+    /// Keys map lowercase value names to the original name and data; keys in
+    /// `denied` refuse all access
+    #[derive(Default)]
+    pub struct MemoryRegistry {
+        keys: Mutex<HashMap<String, Key>>,
+        denied: Mutex<HashSet<String>>,
+    }
+
+    impl MemoryRegistry {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Make every operation on `key` fail with access denied
+        pub fn deny_access(&self, key: &str) {
+            self.denied.lock().insert(key.to_lowercase());
+        }
+
+        pub fn set_dword(&self, key: &str, name: &str, value: u32) {
+            self.keys
+                .lock()
+                .entry(key.to_lowercase())
+                .or_default()
+                .insert(name.to_lowercase(), (name.to_string(), Value::Dword(value)));
+        }
+
+        pub fn has_key(&self, key: &str) -> bool {
+            self.keys.lock().contains_key(&key.to_lowercase())
+        }
+
+        fn check_access(&self, key: &str) -> Result<(), RegistryError> {
+            if self.denied.lock().contains(&key.to_lowercase()) {
+                Err(RegistryError::AccessDenied)
+            } else {
+                Ok(())
+            }
+        }
+
+        fn read(&self, key: &str, name: &str) -> Result<Option<Value>, RegistryError> {
+            self.check_access(key)?;
+            Ok(self
+                .keys
+                .lock()
+                .get(&key.to_lowercase())
+                .and_then(|values| values.get(&name.to_lowercase()))
+                .map(|(_, value)| value.clone()))
+        }
+    }
+
+    fn wrong_type() -> RegistryError {
+        RegistryError::Failed("Unexpected registry value type".to_string())
+    }
+
+    impl Registry for MemoryRegistry {
+        fn read_string(&self, key: &str, name: &str) -> Result<Option<String>, RegistryError> {
+            match self.read(key, name)? {
+                Some(Value::String(value)) => Ok(Some(value)),
+                Some(Value::Dword(_)) => Err(wrong_type()),
+                None => Ok(None),
+            }
+        }
+
+        fn read_dword(&self, key: &str, name: &str) -> Result<Option<u32>, RegistryError> {
+            match self.read(key, name)? {
+                Some(Value::Dword(value)) => Ok(Some(value)),
+                Some(Value::String(_)) => Err(wrong_type()),
+                None => Ok(None),
+            }
+        }
+
+        fn write_string(&self, key: &str, name: &str, value: &str) -> Result<(), RegistryError> {
+            self.check_access(key)?;
+            self.keys
+                .lock()
+                .entry(key.to_lowercase())
+                .or_default()
+                .insert(
+                    name.to_lowercase(),
+                    (name.to_string(), Value::String(value.to_string())),
+                );
+            Ok(())
+        }
+
+        fn delete_value(&self, key: &str, name: &str) -> Result<(), RegistryError> {
+            self.check_access(key)?;
+            if let Some(values) = self.keys.lock().get_mut(&key.to_lowercase()) {
+                values.remove(&name.to_lowercase());
+            }
+            Ok(())
+        }
+
+        fn value_names(&self, key: &str) -> Result<Vec<String>, RegistryError> {
+            self.check_access(key)?;
+            let mut names: Vec<String> = self
+                .keys
+                .lock()
+                .get(&key.to_lowercase())
+                .map(|values| values.values().map(|(name, _)| name.clone()).collect())
+                .unwrap_or_default();
+            names.sort();
+            Ok(names)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = r"Software\KakaoTalkAdBlock\Test";
+
+    #[test]
+    fn test_memory_registry() {
+        let registry = MemoryRegistry::new();
+
+        // Missing keys read as absent and deleting from them succeeds
+        assert_eq!(registry.read_string(KEY, "Value"), Ok(None));
+        assert_eq!(registry.read_dword(KEY, "Value"), Ok(None));
+        assert_eq!(registry.delete_value(KEY, "Value"), Ok(()));
+        assert!(!registry.has_key(KEY));
+
+        registry.write_string(KEY, "Value", "data").unwrap();
+        assert_eq!(
+            registry.read_string(&KEY.to_uppercase(), "value"),
+            Ok(Some("data".to_string()))
+        );
+        assert!(registry.read_dword(KEY, "Value").is_err());
+
+        registry.set_dword(KEY, "Other", 1);
+        assert_eq!(
+            registry.value_names(KEY),
+            Ok(vec!["Other".to_string(), "Value".to_string()])
+        );
+
+        registry.delete_value(KEY, "VALUE").unwrap();
+        assert_eq!(registry.read_string(KEY, "Value"), Ok(None));
+        assert_eq!(registry.value_names("Software\\Missing"), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn test_access_denied() {
+        let registry = MemoryRegistry::new();
+        registry.deny_access(KEY);
+
+        assert_eq!(
+            registry.write_string(KEY, "Value", "data"),
+            Err(RegistryError::AccessDenied)
+        );
+        assert_eq!(
+            registry.read_string(KEY, "Value"),
+            Err(RegistryError::AccessDenied)
+        );
+        assert_eq!(
+            registry.delete_value(KEY, "Value"),
+            Err(RegistryError::AccessDenied)
+        );
+        assert_eq!(RegistryError::AccessDenied.to_string(), "access denied");
+    }
+}
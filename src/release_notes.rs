@@ -0,0 +1,321 @@
+//! Plain-text rendering of release notes
+//!
+//! GitHub release bodies are markdown. The debug window draws plain lines with
+//! a fixed-width font, so markup is stripped and lines are wrapped to a column
+//! count (counting wide CJK characters as two columns).
+
+use crate::update::Release;
+
+/// Convert markdown to readable plain text
+pub fn markdown_to_text(markdown: &str) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    let mut in_code_block = false;
+
+    for raw in markdown.lines() {
+        let line = raw.trim_end();
+        let trimmed = line.trim_start();
+
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+        if in_code_block {
+            lines.push(format!("  {}", line));
+            continue;
+        }
+        if trimmed.starts_with("<!--") && trimmed.ends_with("-->") {
+            continue;
+        }
+
+        let indent = &line[..line.len() - trimmed.len()];
+        let text = if let Some(heading) = strip_heading(trimmed) {
+            inline_to_text(heading)
+        } else if is_rule(trimmed) {
+            "─".repeat(20)
+        } else if let Some(item) = ["- ", "* ", "+ "]
+            .iter()
+            .find_map(|marker| trimmed.strip_prefix(marker))
+        {
+            format!("{}• {}", indent, inline_to_text(item))
+        } else if let Some(quote) = trimmed.strip_prefix('>') {
+            format!("  {}", inline_to_text(quote.trim_start()))
+        } else {
+            format!("{}{}", indent, inline_to_text(trimmed))
+        };
+
+        // Collapse runs of blank lines
+        if text.trim().is_empty() {
+            if lines.last().is_some_and(|l| !l.is_empty()) {
+                lines.push(String::new());
+            }
+        } else {
+            lines.push(text);
+        }
+    }
+
+    while lines.last().is_some_and(|l| l.is_empty()) {
+        lines.pop();
+    }
+    lines.join("\n")
+}
+
+/// Lines shown in the release notes viewer
+pub fn preview_lines(release: &Release, columns: usize) -> Vec<String> {
+    let mut lines = vec![format!("Release {}", release.tag_name)];
+    if let Some(date) = release.published_at.as_deref().and_then(published_date) {
+        lines.push(format!("Published {}", date));
+    }
+    lines.push(String::new());
+
+    let text = markdown_to_text(release.body.as_deref().unwrap_or_default());
+    if text.is_empty() {
+        lines.push("(No release notes)".to_string());
+    }
+    for line in text.lines() {
+        lines.extend(wrap(line, columns));
+    }
+    lines
+}
+
+/// Date part of an ISO 8601 timestamp (`2025-01-02T03:04:05Z` -> `2025-01-02`)
+fn published_date(published_at: &str) -> Option<&str> {
+    let date = published_at.get(..10)?;
+    let valid = date.bytes().enumerate().all(|(i, b)| {
+        if i == 4 || i == 7 {
+            b == b'-'
+        } else {
+            b.is_ascii_digit()
+        }
+    });
+    valid.then_some(date)
+}
+
+/// Word-wrap a line, continuing with the indentation of its text
+/// (so wrapped bullet items hang under their first word)
+pub fn wrap(line: &str, columns: usize) -> Vec<String> {
+    if text_width(line) <= columns {
+        return vec![line.to_string()];
+    }
+
+    let body = line.trim_start();
+    let indent = &line[..line.len() - body.len()];
+    let hanging = match body.strip_prefix("• ") {
+        Some(_) => format!("{}  ", indent),
+        None => indent.to_string(),
+    };
+    // Never let the indentation eat the whole line
+    let hanging = if text_width(&hanging) * 2 > columns {
+        String::new()
+    } else {
+        hanging
+    };
+
+    let mut lines = Vec::new();
+    let mut current = indent.to_string();
+    let mut current_has_word = false;
+
+    for word in body.split_whitespace() {
+        let separator = usize::from(current_has_word);
+        if current_has_word && text_width(&current) + separator + text_width(word) > columns {
+            lines.push(std::mem::replace(&mut current, hanging.clone()));
+            current_has_word = false;
+        }
+        if current_has_word {
+            current.push(' ');
+        }
+
+        // Split words that cannot fit on a line of their own
+        for c in word.chars() {
+            if text_width(&current) + char_width(c) > columns && !current.trim().is_empty() {
+                lines.push(std::mem::replace(&mut current, hanging.clone()));
+            }
+            current.push(c);
+        }
+        current_has_word = true;
+    }
+
+    if current_has_word {
+        lines.push(current);
+    }
+    lines
+}
+
+fn strip_heading(line: &str) -> Option<&str> {
+    let hashes = line.bytes().take_while(|&b| b == b'#').count();
+    if (1..=6).contains(&hashes) {
+        let rest = &line[hashes..];
+        if rest.is_empty() || rest.starts_with(' ') {
+            return Some(rest.trim().trim_end_matches('#').trim_end());
+        }
+    }
+    None
+}
+
+fn is_rule(line: &str) -> bool {
+    let compact: String = line.chars().filter(|c| !c.is_whitespace()).collect();
+    compact.len() >= 3
+        && ["-", "*", "_"]
+            .iter()
+            .any(|m| compact.chars().all(|c| c.to_string() == *m))
+}
+
+/// Strip inline markup: emphasis, code spans, links and images
+fn inline_to_text(line: &str) -> String {
+    let mut out = String::new();
+    let mut rest = line;
+
+    while let Some(c) = rest.chars().next() {
+        if c == '\\' {
+            if let Some(next) = rest[1..].chars().next().filter(char::is_ascii_punctuation) {
+                out.push(next);
+                rest = &rest[1 + next.len_utf8()..];
+                continue;
+            }
+        }
+        if rest.starts_with("**") || rest.starts_with("__") || rest.starts_with("~~") {
+            rest = &rest[2..];
+            continue;
+        }
+        if c == '`' {
+            rest = &rest[1..];
+            continue;
+        }
+
+        let image = rest.starts_with("![");
+        if c == '[' || image {
+            let start = usize::from(image);
+            if let Some((text, url, len)) = parse_link(&rest[start..]) {
+                let text = inline_to_text(text);
+                out.push_str(&text);
+                if !image && !url.is_empty() && url != text {
+                    out.push_str(&format!(" ({})", url));
+                }
+                rest = &rest[start + len..];
+                continue;
+            }
+        }
+
+        out.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    out
+}
+
+/// Parse `[text](url)` at the start of `s`, returning (text, url, length)
+fn parse_link(s: &str) -> Option<(&str, &str, usize)> {
+    let close = s.find("](")?;
+    let text = &s[1..close];
+    if text.contains(']') {
+        return None;
+    }
+    let after = &s[close + 2..];
+    let end = after.find(')')?;
+    Some((text, after[..end].trim(), close + 2 + end + 1))
+}
+
+fn text_width(s: &str) -> usize {
+    s.chars().map(char_width).sum()
+}
+
+/// Display columns of a character in a fixed-width font
+fn char_width(c: char) -> usize {
+    match c {
+        '\u{1100}'..='\u{115F}'
+        | '\u{2E80}'..='\u{A4CF}'
+        | '\u{AC00}'..='\u{D7A3}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{FF00}'..='\u{FF60}'
+        | '\u{FFE0}'..='\u{FFE6}' => 2,
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_markdown_to_text() {
+        let markdown = "## What's Changed\r\n\
+            * **Fix** lock screen ad by @someone in https://github.com/o/r/pull/1\r\n\
+            * Support `KakaoTalk 4.x` ([#2](https://github.com/o/r/pull/2))\r\n\
+            \r\n\
+            \r\n\
+            <!-- generated -->\r\n\
+            ---\r\n\
+            ```\r\n\
+            code **stays**\r\n\
+            ```\r\n\
+            ![screenshot](https://example.invalid/a.png)\r\n\
+            **Full Changelog**: [20250101-01...20250102-01](https://github.com/o/r/compare/x)\r\n";
+
+        assert_eq!(
+            markdown_to_text(markdown),
+            "What's Changed\n\
+             • Fix lock screen ad by @someone in https://github.com/o/r/pull/1\n\
+             • Support KakaoTalk 4.x (#2 (https://github.com/o/r/pull/2))\n\
+             \n\
+             ────────────────────\n  \
+             code **stays**\n\
+             screenshot\n\
+             Full Changelog: 20250101-01...20250102-01 (https://github.com/o/r/compare/x)"
+        );
+    }
+
+    #[test]
+    fn test_inline_edge_cases() {
+        assert_eq!(
+            inline_to_text(r"snake_case and 2 \* 3"),
+            "snake_case and 2 * 3"
+        );
+        assert_eq!(inline_to_text("[x] done (really)"), "[x] done (really)");
+        assert_eq!(
+            inline_to_text("[https://a.invalid](https://a.invalid)"),
+            "https://a.invalid"
+        );
+    }
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(wrap("short", 10), vec!["short"]);
+        assert_eq!(
+            wrap("• one two three four", 10),
+            vec!["• one two", "  three", "  four"]
+        );
+        assert_eq!(wrap("abcdefghijkl", 5), vec!["abcde", "fghij", "kl"]);
+        // Hangul syllables take two columns each
+        assert_eq!(wrap("광고 차단 개선", 8), vec!["광고", "차단", "개선"]);
+    }
+
+    #[test]
+    fn test_preview_lines() {
+        let release = Release {
+            tag_name: "20250102-01".to_string(),
+            body: Some("### Fixes\n- Banner".to_string()),
+            published_at: Some("2025-01-02T03:04:05Z".to_string()),
+            prerelease: false,
+            draft: false,
+            assets: Vec::new(),
+        };
+        assert_eq!(
+            preview_lines(&release, 40),
+            vec![
+                "Release 20250102-01",
+                "Published 2025-01-02",
+                "",
+                "Fixes",
+                "• Banner"
+            ]
+        );
+
+        let empty = Release {
+            body: None,
+            published_at: Some("garbage".to_string()),
+            ..release
+        };
+        assert_eq!(
+            preview_lines(&empty, 40),
+            vec!["Release 20250102-01", "", "(No release notes)"]
+        );
+    }
+}
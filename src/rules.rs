@@ -0,0 +1,295 @@
+//! Ad detection rules
+//!
+//! Pure classification of a client's windows from their class name, text and
+//! parent relationship. Kept free of Win32 calls so the same decisions can be
+//! shown in the debug window and tested without a running client. The class
+//! names and texts come from a [`RuleSet`], which defaults to KakaoTalk's.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::pe_version::FileVersion;
+use crate::window::{class_names, window_texts};
+
+/// A rule that matched a window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Rule {
+    /// Top-level EVA_Window_Dblclk with a title
+    MainWindow,
+    /// Untitled EVA_Window_Dblclk parented to a main window
+    EmbeddedAd,
+    /// Untitled top-level EVA_Window (popup ad)
+    PopupAd,
+    /// Untitled EVA_ChildWindow directly under a main window (bottom banner)
+    BannerChild,
+    /// OnlineMainView child that is shrunk to hide the banner area
+    MainViewArea,
+    /// LockModeView child that is shrunk to hide the lock screen ad area
+    LockScreenArea,
+    /// Chosen by a user script (see `scripting.rs`)
+    Script,
+}
+
+impl Rule {
+    /// Short stable name used in the debug window and bug reports
+    pub fn name(self) -> &'static str {
+        match self {
+            Rule::MainWindow => "main-window",
+            Rule::EmbeddedAd => "embedded-ad",
+            Rule::PopupAd => "popup-ad",
+            Rule::BannerChild => "banner-child",
+            Rule::MainViewArea => "main-view-area",
+            Rule::LockScreenArea => "lock-screen-area",
+            Rule::Script => "script",
+        }
+    }
+}
+
+/// Client versions something applies to; both ends are inclusive and a
+/// missing end is open
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VersionRange {
+    pub min_version: Option<FileVersion>,
+    /// Missing trailing parts are wildcards: `4.3` includes every `4.3.x.y`
+    #[serde(with = "upper_bound")]
+    pub max_version: Option<FileVersion>,
+}
+
+/// Serde for [`VersionRange::max_version`]
+mod upper_bound {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::pe_version::FileVersion;
+
+    pub fn serialize<S: Serializer>(
+        version: &Option<FileVersion>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match version {
+            Some(version) => serializer.serialize_some(&version.to_upper_bound_string()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<FileVersion>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|s| FileVersion::parse_upper_bound(&s))
+            .transpose()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl VersionRange {
+    pub fn contains(&self, version: FileVersion) -> bool {
+        self.min_version.is_none_or(|min| version >= min)
+            && self.max_version.is_none_or(|max| version <= max)
+    }
+}
+
+/// Window classes and texts the rules look for in one client
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuleSet {
+    /// Class of main windows and of ads embedded in them
+    pub main_class: String,
+    /// Class of popup ads
+    pub popup_class: String,
+    /// Class of main window children (banners and content views)
+    pub child_class: String,
+    /// Text prefix of the main view child
+    pub main_view_prefix: String,
+    /// Text prefix of the lock screen child
+    pub lock_view_prefix: String,
+    /// Class prefix of custom scroll bars, which mark content (not ad) views
+    pub scroll_class_prefix: String,
+    /// Client versions individual rules are limited to
+    pub rule_versions: BTreeMap<Rule, VersionRange>,
+}
+
+impl Default for RuleSet {
+    /// KakaoTalk's EVA windows
+    fn default() -> Self {
+        Self {
+            main_class: class_names::EVA_WINDOW_DBLCLK.to_string(),
+            popup_class: class_names::EVA_WINDOW.to_string(),
+            child_class: class_names::EVA_CHILD_WINDOW.to_string(),
+            main_view_prefix: window_texts::ONLINE_MAIN_VIEW.to_string(),
+            lock_view_prefix: window_texts::LOCK_MODE_VIEW.to_string(),
+            scroll_class_prefix: "_EVA_".to_string(),
+            rule_versions: BTreeMap::new(),
+        }
+    }
+}
+
+impl RuleSet {
+    /// Whether a rule applies to a client version; every rule applies when
+    /// the version is unknown
+    pub fn allows(&self, rule: Rule, version: Option<FileVersion>) -> bool {
+        match (self.rule_versions.get(&rule), version) {
+            (Some(range), Some(version)) => range.contains(version),
+            _ => true,
+        }
+    }
+
+    /// Classify a top-level window (as returned by `EnumWindows`)
+    pub fn classify_top_level(
+        &self,
+        class_name: &str,
+        text: &str,
+        has_parent: bool,
+        parent_is_main: bool,
+    ) -> Option<Rule> {
+        if class_name == self.main_class {
+            if !text.is_empty() && !has_parent {
+                Some(Rule::MainWindow)
+            } else if text.is_empty() && has_parent && parent_is_main {
+                Some(Rule::EmbeddedAd)
+            } else {
+                None
+            }
+        } else if class_name == self.popup_class && text.is_empty() && !has_parent {
+            Some(Rule::PopupAd)
+        } else {
+            None
+        }
+    }
+
+    /// Classify a direct child of a main window
+    pub fn classify_main_child(
+        &self,
+        class_name: &str,
+        text: &str,
+        parent_text: &str,
+    ) -> Option<Rule> {
+        if class_name == self.child_class && text.is_empty() && !parent_text.is_empty() {
+            Some(Rule::BannerChild)
+        } else if text.starts_with(&self.main_view_prefix) {
+            Some(Rule::MainViewArea)
+        } else if text.starts_with(&self.lock_view_prefix) {
+            Some(Rule::LockScreenArea)
+        } else {
+            None
+        }
+    }
+
+    /// Whether a main window child is a content view (main view or lock screen)
+    pub fn is_content_view(&self, class_name: &str, text: &str) -> bool {
+        class_name == self.child_class
+            && (text.starts_with(&self.main_view_prefix)
+                || text.starts_with(&self.lock_view_prefix))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classify_top_level(
+        class_name: &str,
+        text: &str,
+        has_parent: bool,
+        parent_is_main: bool,
+    ) -> Option<Rule> {
+        RuleSet::default().classify_top_level(class_name, text, has_parent, parent_is_main)
+    }
+
+    fn classify_main_child(class_name: &str, text: &str, parent_text: &str) -> Option<Rule> {
+        RuleSet::default().classify_main_child(class_name, text, parent_text)
+    }
+
+    #[test]
+    fn test_classify_top_level() {
+        assert_eq!(
+            classify_top_level("EVA_Window_Dblclk", "카카오톡", false, false),
+            Some(Rule::MainWindow)
+        );
+        assert_eq!(
+            classify_top_level("EVA_Window_Dblclk", "", true, true),
+            Some(Rule::EmbeddedAd)
+        );
+        assert_eq!(
+            classify_top_level("EVA_Window_Dblclk", "", true, false),
+            None
+        );
+        assert_eq!(
+            classify_top_level("EVA_Window", "", false, false),
+            Some(Rule::PopupAd)
+        );
+        assert_eq!(classify_top_level("EVA_Window", "Chat", false, false), None);
+        assert_eq!(classify_top_level("Other", "", false, false), None);
+    }
+
+    #[test]
+    fn test_classify_main_child() {
+        assert_eq!(
+            classify_main_child("EVA_ChildWindow", "", "카카오톡"),
+            Some(Rule::BannerChild)
+        );
+        assert_eq!(classify_main_child("EVA_ChildWindow", "", ""), None);
+        assert_eq!(
+            classify_main_child("EVA_ChildWindow", "OnlineMainView_0x1234", "카카오톡"),
+            Some(Rule::MainViewArea)
+        );
+        assert_eq!(
+            classify_main_child("EVA_ChildWindow", "LockModeView_0x1234", "카카오톡"),
+            Some(Rule::LockScreenArea)
+        );
+    }
+
+    #[test]
+    fn test_other_client() {
+        let rules = RuleSet {
+            main_class: "Chat_Main".to_string(),
+            popup_class: "Chat_Popup".to_string(),
+            child_class: "Chat_Child".to_string(),
+            main_view_prefix: "Home".to_string(),
+            ..RuleSet::default()
+        };
+        assert_eq!(
+            rules.classify_top_level("Chat_Main", "Chat", false, false),
+            Some(Rule::MainWindow)
+        );
+        assert_eq!(
+            rules.classify_top_level("Chat_Popup", "", false, false),
+            Some(Rule::PopupAd)
+        );
+        assert_eq!(
+            rules.classify_top_level("EVA_Window", "", false, false),
+            None
+        );
+        assert_eq!(
+            rules.classify_main_child("Chat_Child", "Home_1", "Chat"),
+            Some(Rule::MainViewArea)
+        );
+        assert!(rules.is_content_view("Chat_Child", "Home_1"));
+        assert!(!rules.is_content_view("EVA_ChildWindow", "Home_1"));
+    }
+
+    #[test]
+    fn test_rule_versions() {
+        let rules: RuleSet = serde_json::from_str(
+            r#"{"rule_versions": {"lock-screen-area": {"max_version": "4.3"}, "popup-ad": {"min_version": "25.1"}}}"#,
+        )
+        .unwrap();
+        let old = Some(FileVersion::new(4, 2, 0, 0));
+        let new = Some(FileVersion::new(25, 8, 2, 4567));
+
+        assert!(rules.allows(Rule::LockScreenArea, old));
+        assert!(!rules.allows(Rule::LockScreenArea, new));
+        // Every 4.3 build is within a `4.3` maximum
+        assert!(rules.allows(Rule::LockScreenArea, Some(FileVersion::new(4, 3, 0, 4261))));
+        assert!(!rules.allows(Rule::LockScreenArea, Some(FileVersion::new(4, 4, 0, 0))));
+        let json = serde_json::to_string(&rules).unwrap();
+        assert!(json.contains(r#""max_version":"4.3""#), "{}", json);
+        assert_eq!(serde_json::from_str::<RuleSet>(&json).unwrap(), rules);
+        assert!(!rules.allows(Rule::PopupAd, old));
+        assert!(rules.allows(Rule::PopupAd, new));
+        assert!(rules.allows(Rule::BannerChild, new));
+        assert!(rules.allows(Rule::PopupAd, None));
+    }
+}
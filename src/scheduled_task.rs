@@ -0,0 +1,311 @@
+//! Per-user "at logon" scheduled task
+//!
+//! Task Scheduler is an alternative to the `Run` key on machines where policy
+//! blocks or purges it. The task is registered with `schtasks.exe` from a
+//! generated task definition, runs with the user's normal (non-elevated)
+//! rights and can start after a delay.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[cfg(windows)]
+use parking_lot::Mutex;
+
+/// Result of the last `query`, kept until the app changes the task itself
+/// (each query starts `schtasks.exe`)
+#[cfg(windows)]
+static REGISTERED: Mutex<Option<Option<(PathBuf, String)>>> = Mutex::new(None);
+
+/// Task name for a user (task names are machine-wide)
+pub fn task_name(user: &str) -> String {
+    format!("KakaoTalkAdBlock ({})", user)
+}
+
+/// Task Scheduler XML starting `exe` with `arguments` when `user_id` logs on
+pub fn task_xml(exe: &Path, arguments: &str, user_id: &str, delay: Duration) -> String {
+    let user_id = escape_xml(user_id);
+    let delay = if delay.is_zero() {
+        String::new()
+    } else {
+        format!("\n      <Delay>{}</Delay>", iso8601_duration(delay))
+    };
+    let working_dir = exe
+        .parent()
+        .map(|dir| {
+            format!(
+                "\n      <WorkingDirectory>{}</WorkingDirectory>",
+                escape_xml(&dir.to_string_lossy())
+            )
+        })
+        .unwrap_or_default();
+    let arguments = if arguments.is_empty() {
+        String::new()
+    } else {
+        format!("\n      <Arguments>{}</Arguments>", escape_xml(arguments))
+    };
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-16"?>
+<Task version="1.2" xmlns="http://schemas.microsoft.com/windows/2004/02/mit/task">
+  <RegistrationInfo>
+    <Description>Start KakaoTalkAdBlock at logon</Description>
+  </RegistrationInfo>
+  <Triggers>
+    <LogonTrigger>
+      <Enabled>true</Enabled>
+      <UserId>{user_id}</UserId>{delay}
+    </LogonTrigger>
+  </Triggers>
+  <Principals>
+    <Principal id="Author">
+      <UserId>{user_id}</UserId>
+      <LogonType>InteractiveToken</LogonType>
+      <RunLevel>LeastPrivilege</RunLevel>
+    </Principal>
+  </Principals>
+  <Settings>
+    <MultipleInstancesPolicy>IgnoreNew</MultipleInstancesPolicy>
+    <DisallowStartIfOnBatteries>false</DisallowStartIfOnBatteries>
+    <StopIfGoingOnBatteries>false</StopIfGoingOnBatteries>
+    <ExecutionTimeLimit>PT0S</ExecutionTimeLimit>
+    <Enabled>true</Enabled>
+  </Settings>
+  <Actions Context="Author">
+    <Exec>
+      <Command>{command}</Command>{arguments}{working_dir}
+    </Exec>
+  </Actions>
+</Task>
+"#,
+        command = escape_xml(&exe.to_string_lossy()),
+    )
+    .replace('\n', "\r\n")
+}
+
+/// Executable and arguments of the first action in a task definition
+pub fn parse_task_command(xml: &str) -> Option<(PathBuf, String)> {
+    let exec = element_text(xml, "Exec")?;
+    let command = unescape_xml(element_text(exec, "Command")?.trim());
+    let arguments = element_text(exec, "Arguments")
+        .map(|a| unescape_xml(a.trim()))
+        .unwrap_or_default();
+    // The command may itself be quoted
+    let command = command.trim_matches('"');
+    (!command.is_empty()).then(|| (PathBuf::from(command), arguments))
+}
+
+/// `PT1H2M3S`-style duration
+fn iso8601_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+    let mut out = "PT".to_string();
+    if hours > 0 {
+        out.push_str(&format!("{}H", hours));
+    }
+    if minutes > 0 {
+        out.push_str(&format!("{}M", minutes));
+    }
+    if seconds > 0 || out == "PT" {
+        out.push_str(&format!("{}S", seconds));
+    }
+    out
+}
+
+/// Text between the first `<tag>` (or `<tag attr>`) and its closing tag
+fn element_text<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let open = format!("<{}", tag);
+    let mut search = 0;
+    let start = loop {
+        let pos = search + xml[search..].find(&open)?;
+        let after = &xml[pos + open.len()..];
+        // Skip tags that merely start with the same name
+        if after.starts_with('>') || after.starts_with(char::is_whitespace) {
+            break pos + open.len() + after.find('>')? + 1;
+        }
+        search = pos + open.len();
+    };
+    let end = start + xml[start..].find(&format!("</{}>", tag))?;
+    Some(&xml[start..end])
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn unescape_xml(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// `DOMAIN\user` of the current user
+#[cfg(windows)]
+pub fn current_user_id() -> String {
+    let user = std::env::var("USERNAME").unwrap_or_default();
+    match std::env::var("USERDOMAIN") {
+        Ok(domain) if !domain.is_empty() => format!("{}\\{}", domain, user),
+        _ => user,
+    }
+}
+
+#[cfg(windows)]
+fn current_task_name() -> String {
+    task_name(&std::env::var("USERNAME").unwrap_or_default())
+}
+
+/// Run schtasks.exe without a console window and return its output
+#[cfg(windows)]
+fn schtasks(args: &[&str]) -> Result<String, String> {
+    use std::os::windows::process::CommandExt;
+
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    let output = std::process::Command::new("schtasks.exe")
+        .args(args)
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+        .map_err(|e| format!("Failed to run schtasks: {}", e))?;
+
+    let stdout = decode_console_output(&output.stdout);
+    if output.status.success() {
+        Ok(stdout)
+    } else {
+        let stderr = decode_console_output(&output.stderr);
+        Err(format!("schtasks failed: {}", stderr.trim()))
+    }
+}
+
+/// Console programs write piped output in the OEM code page
+#[cfg(windows)]
+fn decode_console_output(bytes: &[u8]) -> String {
+    use windows::Win32::Globalization::{
+        MultiByteToWideChar, CP_OEMCP, MULTI_BYTE_TO_WIDE_CHAR_FLAGS,
+    };
+
+    if bytes.is_empty() {
+        return String::new();
+    }
+    let flags = MULTI_BYTE_TO_WIDE_CHAR_FLAGS(0);
+    let len = unsafe { MultiByteToWideChar(CP_OEMCP, flags, bytes, None) };
+    if len <= 0 {
+        return String::from_utf8_lossy(bytes).into_owned();
+    }
+    let mut wide = vec![0u16; len as usize];
+    unsafe { MultiByteToWideChar(CP_OEMCP, flags, bytes, Some(&mut wide)) };
+    String::from_utf16_lossy(&wide)
+}
+
+/// Register (or replace) the logon task for the current user
+#[cfg(windows)]
+pub fn create(exe: &Path, arguments: &str, delay: Duration) -> Result<(), String> {
+    let xml = task_xml(exe, arguments, &current_user_id(), delay);
+
+    // schtasks reads the definition from a UTF-16 file, as declared in the XML
+    let path =
+        std::env::temp_dir().join(format!("KakaoTalkAdBlock-task-{}.xml", std::process::id()));
+    let bytes: Vec<u8> = std::iter::once(0xFEFF)
+        .chain(xml.encode_utf16())
+        .flat_map(u16::to_le_bytes)
+        .collect();
+    std::fs::write(&path, bytes)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+
+    let result = schtasks(&[
+        "/Create",
+        "/TN",
+        &current_task_name(),
+        "/XML",
+        &path.to_string_lossy(),
+        "/F",
+    ]);
+    let _ = std::fs::remove_file(&path);
+    *REGISTERED.lock() = None;
+    result.map(|_| ())
+}
+
+/// Remove the logon task, if any
+#[cfg(windows)]
+pub fn delete() -> Result<(), String> {
+    if query().is_none() {
+        return Ok(());
+    }
+    let result = schtasks(&["/Delete", "/TN", &current_task_name(), "/F"]);
+    *REGISTERED.lock() = None;
+    result.map(|_| ())
+}
+
+/// Executable and arguments of the registered logon task
+#[cfg(windows)]
+pub fn query() -> Option<(PathBuf, String)> {
+    REGISTERED
+        .lock()
+        .get_or_insert_with(|| {
+            let xml = schtasks(&["/Query", "/TN", &current_task_name(), "/XML", "ONE"]).ok()?;
+            parse_task_command(&xml)
+        })
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXE: &str = r"C:\Users\Kim & Lee\AppData\Local\KakaoTalkAdBlock\KakaoTalkAdBlock.exe";
+
+    #[test]
+    fn test_task_xml() {
+        let xml = task_xml(
+            Path::new(EXE),
+            "--minimized",
+            r"DESKTOP-1\Kim",
+            Duration::from_secs(90),
+        );
+
+        assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-16\"?>\r\n"));
+        assert!(!xml.replace("\r\n", "").contains('\n'));
+        assert!(xml.contains("<UserId>DESKTOP-1\\Kim</UserId>"));
+        assert!(xml.contains("<Delay>PT1M30S</Delay>"));
+        assert!(xml.contains("<LogonType>InteractiveToken</LogonType>"));
+        assert!(xml.contains("<RunLevel>LeastPrivilege</RunLevel>"));
+        assert!(xml.contains("Kim &amp; Lee"));
+        assert_eq!(
+            parse_task_command(&xml),
+            Some((PathBuf::from(EXE), "--minimized".to_string()))
+        );
+
+        let xml = task_xml(Path::new(EXE), "", "Kim", Duration::ZERO);
+        assert!(!xml.contains("<Delay>"));
+        assert!(!xml.contains("<Arguments>"));
+    }
+
+    #[test]
+    fn test_parse_task_command() {
+        // As printed by `schtasks /Query /XML`, with a quoted command
+        let xml = "<Task><Actions Context=\"Author\"><Exec>\
+                   <Command>\"C:\\Tools\\blocker.exe\"</Command>\
+                   </Exec></Actions></Task>";
+        assert_eq!(
+            parse_task_command(xml),
+            Some((PathBuf::from(r"C:\Tools\blocker.exe"), String::new()))
+        );
+        assert_eq!(parse_task_command("<Task></Task>"), None);
+        assert_eq!(
+            element_text("<Execute>no</Execute><Exec>yes</Exec>", "Exec"),
+            Some("yes")
+        );
+    }
+
+    #[test]
+    fn test_iso8601_duration() {
+        assert_eq!(iso8601_duration(Duration::ZERO), "PT0S");
+        assert_eq!(iso8601_duration(Duration::from_secs(30)), "PT30S");
+        assert_eq!(iso8601_duration(Duration::from_secs(3600)), "PT1H");
+        assert_eq!(iso8601_duration(Duration::from_secs(3725)), "PT1H2M5S");
+    }
+}
@@ -0,0 +1,597 @@
+//! User detection scripts
+//!
+//! Layouts that the built-in rules can't express are handled by Rhai scripts
+//! in `%LOCALAPPDATA%\KakaoTalkAdBlock\scripts`. Each script defines
+//! `fn detect(tree)`, gets a copy of the window tree (so it cannot change
+//! anything) and returns the actions to take:
+//!
+//! ```text
+//! fn detect(tree) {
+//!     let actions = [];
+//!     for main in tree {
+//!         if main.rule != "main-window" { continue; }
+//!         for child in main.children {
+//!             if child.class == "EVA_ChildWindow" && child.height >= 60
+//!                 && child.height <= 120 && child.bottom == main.bottom {
+//!                 actions.push(#{ hwnd: child.hwnd, action: "hide" });
+//!             }
+//!         }
+//!     }
+//!     actions
+//! }
+//! ```
+//!
+//! Scripts have no file, module, `eval` or `sleep` access, are stopped after a
+//! time and operation budget, and may only act on windows in the tree they were
+//! given. Nothing here calls Win32, so scripts can be run against fake trees.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Map, Scope, AST};
+
+use crate::window_tree::WindowNode;
+
+/// Wall-clock budget of one `detect` call
+pub const TIME_LIMIT: Duration = Duration::from_millis(50);
+
+/// Operation budget of one `detect` call (a backstop for the time limit)
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// Printed lines kept from one run of all scripts
+const MAX_OUTPUT_LINES: usize = 50;
+
+/// Function every script must define
+const ENTRY_POINT: &str = "detect";
+
+/// File extension of scripts
+const EXTENSION: &str = "rhai";
+
+/// What a script asked for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptAction {
+    Hide,
+    /// Resize to 0x0 and send WM_CLOSE
+    Close,
+    Resize {
+        width: i32,
+        height: i32,
+    },
+}
+
+/// An action on one window, with the script that asked for it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decision {
+    pub script: String,
+    pub hwnd: isize,
+    pub action: ScriptAction,
+}
+
+/// A script that failed to load or run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptError {
+    pub script: String,
+    pub message: String,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.script, self.message)
+    }
+}
+
+/// Outcome of running every script once
+#[derive(Debug, Default)]
+pub struct RunResult {
+    pub decisions: Vec<Decision>,
+    pub errors: Vec<ScriptError>,
+    /// What the scripts printed, as `script: text`
+    pub output: Vec<String>,
+}
+
+struct Script {
+    name: String,
+    ast: AST,
+}
+
+/// Sandboxed engine and the loaded scripts
+pub struct ScriptHost {
+    engine: Engine,
+    scripts: Vec<Script>,
+    /// When the running call started, for the time limit
+    started: Arc<Mutex<Instant>>,
+    /// Lines printed by the running call; never logged, as scripts can print
+    /// window titles
+    printed: Arc<Mutex<Vec<String>>>,
+}
+
+impl ScriptHost {
+    pub fn new(time_limit: Duration) -> Self {
+        let started = Arc::new(Mutex::new(Instant::now()));
+        let printed = Arc::new(Mutex::new(Vec::new()));
+
+        let mut engine = Engine::new();
+        engine
+            .set_module_resolver(rhai::module_resolvers::DummyModuleResolver::new())
+            .disable_symbol("eval")
+            .set_max_operations(MAX_OPERATIONS)
+            .set_max_call_levels(32)
+            .set_max_expr_depths(64, 32)
+            .set_max_string_size(64 * 1024)
+            .set_max_array_size(10_000)
+            .set_max_map_size(1_000);
+        let output = Arc::clone(&printed);
+        engine.on_print(move |text| print_line(&output, text));
+        let output = Arc::clone(&printed);
+        engine.on_debug(move |text, _, _| print_line(&output, text));
+        // A sleeping script can't be stopped, so the built-in `sleep` is replaced
+        engine
+            .register_fn("sleep", |_: rhai::INT| sleep_unavailable())
+            .register_fn("sleep", |_: rhai::FLOAT| sleep_unavailable());
+
+        let clock = Arc::clone(&started);
+        engine.on_progress(move |_| {
+            (clock.lock().elapsed() > time_limit).then(|| Dynamic::from("time limit exceeded"))
+        });
+
+        Self {
+            engine,
+            scripts: Vec::new(),
+            started,
+            printed,
+        }
+    }
+
+    /// Load every script in `dir`, in name order; a missing directory has none
+    pub fn load_dir(&mut self, dir: &Path) -> Vec<ScriptError> {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return Vec::new();
+        };
+        let mut paths: Vec<_> = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == EXTENSION))
+            .collect();
+        paths.sort();
+
+        let mut errors = Vec::new();
+        for path in paths {
+            let name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let result = std::fs::read_to_string(&path)
+                .map_err(|e| ScriptError {
+                    script: name.clone(),
+                    message: e.to_string(),
+                })
+                .and_then(|source| self.load(&name, &source));
+            if let Err(e) = result {
+                errors.push(e);
+            }
+        }
+        errors
+    }
+
+    /// Compile a script and add it
+    pub fn load(&mut self, name: &str, source: &str) -> Result<(), ScriptError> {
+        let error = |message: String| ScriptError {
+            script: name.to_string(),
+            message,
+        };
+        let ast = self
+            .engine
+            .compile(source)
+            .map_err(|e| error(e.to_string()))?;
+        if !ast.iter_functions().any(|f| f.name == ENTRY_POINT) {
+            return Err(error(format!("no `fn {}(tree)` defined", ENTRY_POINT)));
+        }
+        self.scripts.push(Script {
+            name: name.to_string(),
+            ast,
+        });
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scripts.is_empty()
+    }
+
+    /// Run every script against `tree`
+    pub fn run(&self, tree: &[WindowNode]) -> RunResult {
+        let mut known = HashSet::new();
+        let roots: Array = tree
+            .iter()
+            .map(|node| to_dynamic(node, &mut known))
+            .collect();
+
+        let mut result = RunResult::default();
+        for script in &self.scripts {
+            match self.run_script(script, &roots, &known) {
+                Ok(decisions) => result.decisions.extend(decisions),
+                Err(message) => result.errors.push(ScriptError {
+                    script: script.name.clone(),
+                    message,
+                }),
+            }
+            let printed = std::mem::take(&mut *self.printed.lock());
+            result.output.extend(
+                printed
+                    .into_iter()
+                    .map(|text| format!("{}: {}", script.name, text)),
+            );
+            result.output.truncate(MAX_OUTPUT_LINES);
+        }
+        result
+    }
+
+    fn run_script(
+        &self,
+        script: &Script,
+        roots: &Array,
+        known: &HashSet<isize>,
+    ) -> Result<Vec<Decision>, String> {
+        *self.started.lock() = Instant::now();
+        let returned = self
+            .engine
+            .call_fn::<Dynamic>(
+                &mut Scope::new(),
+                &script.ast,
+                ENTRY_POINT,
+                (roots.clone(),),
+            )
+            .map_err(|e| match *e {
+                EvalAltResult::ErrorTerminated(reason, _) => reason.to_string(),
+                e => e.to_string(),
+            })?;
+
+        if returned.is_unit() {
+            return Ok(Vec::new());
+        }
+        let actions = returned
+            .try_cast::<Array>()
+            .ok_or("`detect` must return an array of actions")?;
+        actions
+            .into_iter()
+            .map(|action| {
+                let (hwnd, action) = parse_action(action)?;
+                if !known.contains(&hwnd) {
+                    return Err(format!("window 0x{:08X} is not in the tree", hwnd));
+                }
+                Ok(Decision {
+                    script: script.name.clone(),
+                    hwnd,
+                    action,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Keep a printed line, up to [`MAX_OUTPUT_LINES`] per call
+fn print_line(printed: &Mutex<Vec<String>>, text: &str) {
+    let mut printed = printed.lock();
+    if printed.len() < MAX_OUTPUT_LINES {
+        printed.push(text.to_string());
+    }
+}
+
+/// Stands in for Rhai's `sleep`
+fn sleep_unavailable() -> Result<(), Box<EvalAltResult>> {
+    Err("sleep is not available in scripts".into())
+}
+
+/// Script view of a window; `known` collects every handle in the tree
+fn to_dynamic(node: &WindowNode, known: &mut HashSet<isize>) -> Dynamic {
+    known.insert(node.hwnd);
+    let children: Array = node
+        .children
+        .iter()
+        .map(|child| to_dynamic(child, known))
+        .collect();
+
+    let mut map = Map::new();
+    map.insert("hwnd".into(), Dynamic::from_int(node.hwnd as i64));
+    map.insert("class".into(), node.class_name.clone().into());
+    map.insert("text".into(), node.text.clone().into());
+    map.insert("left".into(), Dynamic::from_int(node.rect.left.into()));
+    map.insert("top".into(), Dynamic::from_int(node.rect.top.into()));
+    map.insert("right".into(), Dynamic::from_int(node.rect.right.into()));
+    map.insert("bottom".into(), Dynamic::from_int(node.rect.bottom.into()));
+    map.insert("width".into(), Dynamic::from_int(node.rect.width().into()));
+    map.insert(
+        "height".into(),
+        Dynamic::from_int(node.rect.height().into()),
+    );
+    map.insert("visible".into(), node.visible.into());
+    map.insert(
+        "rule".into(),
+        node.rule.map(|r| r.name()).unwrap_or_default().into(),
+    );
+    map.insert("children".into(), children.into());
+    map.into()
+}
+
+/// `#{ hwnd: .., action: "hide" | "close" | "resize", width: .., height: .. }`
+fn parse_action(value: Dynamic) -> Result<(isize, ScriptAction), String> {
+    let map = value
+        .try_cast::<Map>()
+        .ok_or("each action must be a map like #{ hwnd: .., action: \"hide\" }")?;
+    let int = |key: &str| {
+        map.get(key)
+            .and_then(|v| v.as_int().ok())
+            .ok_or(format!("action needs an integer `{}`", key))
+    };
+    let hwnd = int("hwnd")? as isize;
+    let name = map
+        .get("action")
+        .and_then(|v| v.clone().into_string().ok())
+        .ok_or("action needs an `action` name")?;
+    let action = match name.as_str() {
+        "hide" => ScriptAction::Hide,
+        "close" => ScriptAction::Close,
+        "resize" => {
+            let size = |key| {
+                int(key).and_then(|v| {
+                    i32::try_from(v).map_err(|_| format!("`{}` is out of range", key))
+                })
+            };
+            ScriptAction::Resize {
+                width: size("width")?,
+                height: size("height")?,
+            }
+        }
+        other => return Err(format!("unknown action \"{}\"", other)),
+    };
+    Ok((hwnd, action))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Rule;
+    use crate::test_dir::TestDir;
+    use crate::window_tree::NodeRect;
+
+    fn node(hwnd: isize, class_name: &str, top: i32, bottom: i32) -> WindowNode {
+        WindowNode {
+            hwnd,
+            class_name: class_name.to_string(),
+            text: String::new(),
+            rect: NodeRect {
+                left: 0,
+                top,
+                right: 400,
+                bottom,
+            },
+            visible: true,
+            rule: None,
+            children: Vec::new(),
+        }
+    }
+
+    fn tree() -> Vec<WindowNode> {
+        let mut main = node(0x100, "EVA_Window_Dblclk", 0, 700);
+        main.text = "KakaoTalk".to_string();
+        main.rule = Some(Rule::MainWindow);
+        main.children = vec![
+            node(0x101, "EVA_ChildWindow", 0, 610),
+            // Bottom banner, 90 px high
+            node(0x102, "EVA_ChildWindow", 610, 700),
+            node(0x103, "EVA_ChildWindow", 0, 40),
+        ];
+        vec![main, node(0x200, "EVA_Window", 0, 300)]
+    }
+
+    const BOTTOM_BANNER: &str = r#"
+        fn detect(tree) {
+            let actions = [];
+            for main in tree {
+                if main.rule != "main-window" { continue; }
+                for child in main.children {
+                    if child.class == "EVA_ChildWindow" && child.height >= 60
+                        && child.height <= 120 && child.bottom == main.bottom {
+                        actions.push(#{ hwnd: child.hwnd, action: "hide" });
+                    }
+                }
+            }
+            actions
+        }
+    "#;
+
+    fn run(source: &str) -> RunResult {
+        let mut host = ScriptHost::new(TIME_LIMIT);
+        host.load("test.rhai", source).unwrap();
+        host.run(&tree())
+    }
+
+    #[test]
+    fn test_detects_from_tree() {
+        let result = run(BOTTOM_BANNER);
+        assert_eq!(result.errors, Vec::new());
+        assert_eq!(
+            result.decisions,
+            vec![Decision {
+                script: "test.rhai".to_string(),
+                hwnd: 0x102,
+                action: ScriptAction::Hide,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_actions() {
+        let result = run(r#"
+            fn detect(tree) {
+                [
+                    #{ hwnd: tree[1].hwnd, action: "close" },
+                    #{ hwnd: 0x101, action: "resize", width: 400, height: 0 },
+                ]
+            }
+        "#);
+        let actions: Vec<_> = result
+            .decisions
+            .iter()
+            .map(|d| (d.hwnd, d.action))
+            .collect();
+        assert_eq!(
+            actions,
+            vec![
+                (0x200, ScriptAction::Close),
+                (
+                    0x101,
+                    ScriptAction::Resize {
+                        width: 400,
+                        height: 0
+                    }
+                ),
+            ]
+        );
+        assert!(run("fn detect(tree) {}").decisions.is_empty());
+    }
+
+    #[test]
+    fn test_tree_is_read_only() {
+        let mut host = ScriptHost::new(TIME_LIMIT);
+        host.load(
+            "a.rhai",
+            r#"fn detect(tree) { tree[0].children = []; tree.clear(); }"#,
+        )
+        .unwrap();
+        host.load("b.rhai", BOTTOM_BANNER).unwrap();
+        let result = host.run(&tree());
+        assert!(result.errors.is_empty());
+        assert_eq!(result.decisions.len(), 1);
+    }
+
+    #[test]
+    fn test_invalid_results_are_reported() {
+        for (source, expected) in [
+            ("fn detect(tree) { 1 }", "must return an array"),
+            (
+                r#"fn detect(tree) { [#{ hwnd: 0x999, action: "hide" }] }"#,
+                "not in the tree",
+            ),
+            (
+                r#"fn detect(tree) { [#{ hwnd: 0x101, action: "delete" }] }"#,
+                "unknown action",
+            ),
+            (
+                r#"fn detect(tree) { [#{ hwnd: 0x101, action: "resize" }] }"#,
+                "`width`",
+            ),
+            ("fn detect(tree) { tree[0].missing.x }", ""),
+        ] {
+            let result = run(source);
+            assert!(result.decisions.is_empty(), "{}", source);
+            assert_eq!(result.errors.len(), 1, "{}", source);
+            assert!(
+                result.errors[0].message.contains(expected),
+                "{:?}",
+                result.errors
+            );
+            assert_eq!(result.errors[0].script, "test.rhai");
+        }
+    }
+
+    #[test]
+    fn test_limits() {
+        // A budget that is already spent stops the script at its first step
+        let mut host = ScriptHost::new(Duration::ZERO);
+        host.load("loop.rhai", "fn detect(tree) { loop {} }")
+            .unwrap();
+        let result = host.run(&tree());
+        assert_eq!(result.errors[0].message, "time limit exceeded");
+
+        // With time to spare, the operation budget runs out instead
+        let mut host = ScriptHost::new(Duration::from_secs(3600));
+        host.load("loop.rhai", "fn detect(tree) { loop {} }")
+            .unwrap();
+        let result = host.run(&tree());
+        assert_eq!(result.errors.len(), 1);
+        assert_ne!(result.errors[0].message, "time limit exceeded");
+    }
+
+    #[test]
+    fn test_sleep_is_unavailable() {
+        for source in [
+            "fn detect(tree) { sleep(3600); [] }",
+            "fn detect(tree) { sleep(3600.0); [] }",
+        ] {
+            let started = Instant::now();
+            let result = run(source);
+            assert!(started.elapsed() < TIME_LIMIT);
+            assert_eq!(result.errors.len(), 1, "{}", source);
+            assert!(
+                result.errors[0].message.contains("sleep is not available"),
+                "{:?}",
+                result.errors
+            );
+        }
+    }
+
+    #[test]
+    fn test_output_is_returned_not_logged() {
+        let mut host = ScriptHost::new(TIME_LIMIT);
+        let mut tree = tree();
+        tree[0].text = "Secret chat room 4821".to_string();
+        host.load(
+            "print.rhai",
+            r#"fn detect(tree) { print(tree[0].text); debug(1); for i in 0..100 { print(i) } [] }"#,
+        )
+        .unwrap();
+
+        let result = host.run(&tree);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output.len(), MAX_OUTPUT_LINES);
+        assert_eq!(result.output[0], "print.rhai: Secret chat room 4821");
+        assert_eq!(result.output[1], "print.rhai: 1");
+        assert!(!crate::logger::recent()
+            .iter()
+            .any(|line| line.contains("Secret chat room 4821")));
+
+        // Each run starts over
+        assert_eq!(host.run(&tree).output.len(), MAX_OUTPUT_LINES);
+    }
+
+    #[test]
+    fn test_sandbox() {
+        let mut host = ScriptHost::new(TIME_LIMIT);
+        assert!(host
+            .load("eval.rhai", r#"fn detect(tree) { eval("1") }"#)
+            .is_err());
+        assert!(host
+            .load("missing.rhai", "fn check(tree) { [] }")
+            .unwrap_err()
+            .message
+            .contains("fn detect"));
+
+        host.load(
+            "import.rhai",
+            r#"import "other" as o; fn detect(tree) { [] }"#,
+        )
+        .unwrap();
+        assert_eq!(host.run(&tree()).errors.len(), 1);
+    }
+
+    #[test]
+    fn test_load_dir() {
+        let dir = TestDir::new("scripts");
+        std::fs::write(dir.join("b.rhai"), BOTTOM_BANNER).unwrap();
+        std::fs::write(dir.join("a.rhai"), "fn detect(tree) {").unwrap();
+        std::fs::write(dir.join("notes.txt"), "not a script").unwrap();
+
+        let mut host = ScriptHost::new(TIME_LIMIT);
+        let errors = host.load_dir(&dir);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].script, "a.rhai");
+        assert_eq!(host.run(&tree()).decisions.len(), 1);
+
+        assert!(ScriptHost::new(TIME_LIMIT)
+            .load_dir(&dir.join("missing"))
+            .is_empty());
+    }
+}
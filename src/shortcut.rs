@@ -0,0 +1,322 @@
+//! Windows shortcut (.lnk) files
+//!
+//! Shortcuts are written directly in the Shell Link binary format
+//! ([MS-SHLLINK]) instead of through the `IShellLink` COM interface, so they
+//! can be generated and checked without Windows. The target is stored as a
+//! local path in the LinkInfo structure (in Unicode, for non-ASCII profile
+//! paths); the shell fills in the rest when the shortcut is first used.
+//!
+//! [MS-SHLLINK]: https://learn.microsoft.com/openspecs/windows_protocols/ms-shllink
+
+use std::path::{Path, PathBuf};
+
+const HEADER_SIZE: u32 = 0x4C;
+/// `00021401-0000-0000-C000-000000000046`
+const LINK_CLSID: [u8; 16] = [
+    0x01, 0x14, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0xC0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46,
+];
+
+// LinkFlags
+const HAS_LINK_TARGET_ID_LIST: u32 = 0x01;
+const HAS_LINK_INFO: u32 = 0x02;
+const HAS_NAME: u32 = 0x04;
+const HAS_RELATIVE_PATH: u32 = 0x08;
+const HAS_WORKING_DIR: u32 = 0x10;
+const HAS_ARGUMENTS: u32 = 0x20;
+const IS_UNICODE: u32 = 0x80;
+
+const FILE_ATTRIBUTE_NORMAL: u32 = 0x80;
+const SW_SHOWNORMAL: u32 = 1;
+const VOLUME_ID_AND_LOCAL_BASE_PATH: u32 = 0x01;
+const DRIVE_FIXED: u32 = 3;
+/// LinkInfo header size when the Unicode path offsets are present
+const LINK_INFO_HEADER_SIZE: u32 = 0x24;
+
+/// What a shortcut starts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shortcut {
+    pub target: PathBuf,
+    pub arguments: String,
+    pub working_dir: Option<PathBuf>,
+    /// Tooltip shown by Explorer
+    pub description: Option<String>,
+}
+
+impl Shortcut {
+    /// A shortcut to `target`, working in the target's directory
+    pub fn new(target: &Path, arguments: &str) -> Self {
+        Self {
+            target: target.to_path_buf(),
+            arguments: arguments.to_string(),
+            working_dir: target.parent().map(Path::to_path_buf),
+            description: None,
+        }
+    }
+
+    /// Encode as a .lnk file
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut flags = HAS_LINK_INFO | IS_UNICODE;
+        if self.description.is_some() {
+            flags |= HAS_NAME;
+        }
+        if self.working_dir.is_some() {
+            flags |= HAS_WORKING_DIR;
+        }
+        if !self.arguments.is_empty() {
+            flags |= HAS_ARGUMENTS;
+        }
+
+        let mut out = Vec::new();
+        put_u32(&mut out, HEADER_SIZE);
+        out.extend_from_slice(&LINK_CLSID);
+        put_u32(&mut out, flags);
+        put_u32(&mut out, FILE_ATTRIBUTE_NORMAL);
+        out.extend_from_slice(&[0; 24]); // creation, access and write times
+        put_u32(&mut out, 0); // file size
+        put_u32(&mut out, 0); // icon index
+        put_u32(&mut out, SW_SHOWNORMAL);
+        out.extend_from_slice(&[0; 12]); // hotkey and reserved fields
+
+        out.extend_from_slice(&link_info(&self.target.to_string_lossy()));
+
+        // StringData, in the order the format requires
+        if let Some(description) = &self.description {
+            put_counted_string(&mut out, description);
+        }
+        if let Some(dir) = &self.working_dir {
+            put_counted_string(&mut out, &dir.to_string_lossy());
+        }
+        if !self.arguments.is_empty() {
+            put_counted_string(&mut out, &self.arguments);
+        }
+
+        put_u32(&mut out, 0); // terminal block
+        out
+    }
+
+    /// Decode the target, arguments and working directory of a .lnk file
+    pub fn parse(bytes: &[u8]) -> Result<Self, String> {
+        let invalid = || "Not a shortcut file".to_string();
+        if read_u32(bytes, 0) != Some(HEADER_SIZE) || bytes.get(4..20) != Some(&LINK_CLSID[..]) {
+            return Err(invalid());
+        }
+        let flags = read_u32(bytes, 20).ok_or_else(invalid)?;
+        let mut pos = HEADER_SIZE as usize;
+
+        if flags & HAS_LINK_TARGET_ID_LIST != 0 {
+            let size = read_u16(bytes, pos).ok_or_else(invalid)?;
+            pos += 2 + size as usize;
+        }
+
+        let mut target = None;
+        if flags & HAS_LINK_INFO != 0 {
+            let size = read_u32(bytes, pos).ok_or_else(invalid)? as usize;
+            let info = bytes.get(pos..pos + size).ok_or_else(invalid)?;
+            target = parse_link_info(info);
+            pos += size;
+        }
+
+        let unicode = flags & IS_UNICODE != 0;
+        let mut strings = Vec::new();
+        for flag in [HAS_NAME, HAS_RELATIVE_PATH, HAS_WORKING_DIR, HAS_ARGUMENTS] {
+            if flags & flag != 0 {
+                let (value, len) = read_counted_string(bytes, pos, unicode).ok_or_else(invalid)?;
+                strings.push((flag, value));
+                pos += len;
+            }
+        }
+        let take = |flag| {
+            strings
+                .iter()
+                .find(|(f, _)| *f == flag)
+                .map(|(_, value)| value.clone())
+        };
+
+        Ok(Self {
+            target: target.ok_or("Shortcut has no local target path")?,
+            arguments: take(HAS_ARGUMENTS).unwrap_or_default(),
+            working_dir: take(HAS_WORKING_DIR).map(PathBuf::from),
+            description: take(HAS_NAME),
+        })
+    }
+}
+
+/// LinkInfo for a file on a fixed local drive
+fn link_info(path: &str) -> Vec<u8> {
+    let mut volume_id = Vec::new();
+    put_u32(&mut volume_id, 0x11); // size: header and empty label
+    put_u32(&mut volume_id, DRIVE_FIXED);
+    put_u32(&mut volume_id, 0); // serial number
+    put_u32(&mut volume_id, 0x10); // label offset
+    volume_id.push(0);
+
+    // ANSI copy for old readers; the Unicode copy is authoritative
+    let ansi: Vec<u8> = path
+        .chars()
+        .map(|c| if c.is_ascii() { c as u8 } else { b'?' })
+        .chain(std::iter::once(0))
+        .collect();
+    let unicode: Vec<u8> = path
+        .encode_utf16()
+        .chain(std::iter::once(0))
+        .flat_map(u16::to_le_bytes)
+        .collect();
+
+    let volume_id_offset = LINK_INFO_HEADER_SIZE;
+    let local_base_path_offset = volume_id_offset + volume_id.len() as u32;
+    let suffix_offset = local_base_path_offset + ansi.len() as u32;
+    let unicode_offset = suffix_offset + 1;
+    let unicode_suffix_offset = unicode_offset + unicode.len() as u32;
+    let size = unicode_suffix_offset + 2;
+
+    let mut out = Vec::new();
+    put_u32(&mut out, size);
+    put_u32(&mut out, LINK_INFO_HEADER_SIZE);
+    put_u32(&mut out, VOLUME_ID_AND_LOCAL_BASE_PATH);
+    put_u32(&mut out, volume_id_offset);
+    put_u32(&mut out, local_base_path_offset);
+    put_u32(&mut out, 0); // no network location
+    put_u32(&mut out, suffix_offset);
+    put_u32(&mut out, unicode_offset);
+    put_u32(&mut out, unicode_suffix_offset);
+    out.extend_from_slice(&volume_id);
+    out.extend_from_slice(&ansi);
+    out.push(0); // empty common path suffix
+    out.extend_from_slice(&unicode);
+    out.extend_from_slice(&[0, 0]); // empty Unicode suffix
+    out
+}
+
+/// Local target path of a LinkInfo structure, preferring the Unicode copy
+fn parse_link_info(info: &[u8]) -> Option<PathBuf> {
+    let header_size = read_u32(info, 4)?;
+    let flags = read_u32(info, 8)?;
+    if flags & VOLUME_ID_AND_LOCAL_BASE_PATH == 0 {
+        return None;
+    }
+
+    let (path, suffix) = if header_size >= LINK_INFO_HEADER_SIZE {
+        let path = read_utf16z(info, read_u32(info, 28)? as usize)?;
+        let suffix = read_utf16z(info, read_u32(info, 32)? as usize)?;
+        (path, suffix)
+    } else {
+        let path = read_ansiz(info, read_u32(info, 16)? as usize)?;
+        let suffix = read_ansiz(info, read_u32(info, 24)? as usize)?;
+        (path, suffix)
+    };
+    Some(PathBuf::from(path + suffix.as_str()))
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+/// StringData entry: character count, then UTF-16 without terminator
+fn put_counted_string(out: &mut Vec<u8>, value: &str) {
+    let units: Vec<u16> = value.encode_utf16().collect();
+    out.extend_from_slice(&(units.len() as u16).to_le_bytes());
+    for unit in units {
+        out.extend_from_slice(&unit.to_le_bytes());
+    }
+}
+
+fn read_u16(bytes: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        bytes.get(pos..pos + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32(bytes: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        bytes.get(pos..pos + 4)?.try_into().ok()?,
+    ))
+}
+
+/// A StringData entry and its encoded length
+fn read_counted_string(bytes: &[u8], pos: usize, unicode: bool) -> Option<(String, usize)> {
+    let count = read_u16(bytes, pos)? as usize;
+    if unicode {
+        let data = bytes.get(pos + 2..pos + 2 + count * 2)?;
+        let units: Vec<u16> = data
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect();
+        Some((String::from_utf16_lossy(&units), 2 + count * 2))
+    } else {
+        let data = bytes.get(pos + 2..pos + 2 + count)?;
+        Some((String::from_utf8_lossy(data).into_owned(), 2 + count))
+    }
+}
+
+fn read_utf16z(bytes: &[u8], pos: usize) -> Option<String> {
+    let units: Vec<u16> = bytes
+        .get(pos..)?
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .take_while(|&unit| unit != 0)
+        .collect();
+    Some(String::from_utf16_lossy(&units))
+}
+
+fn read_ansiz(bytes: &[u8], pos: usize) -> Option<String> {
+    let data: Vec<u8> = bytes
+        .get(pos..)?
+        .iter()
+        .copied()
+        .take_while(|&b| b != 0)
+        .collect();
+    Some(String::from_utf8_lossy(&data).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let shortcut = Shortcut {
+            description: Some("KakaoTalk 광고 차단".to_string()),
+            ..Shortcut::new(
+                Path::new(
+                    r"C:\Users\사용자\AppData\Local\Programs\KakaoTalkAdBlock\KakaoTalkAdBlock.exe",
+                ),
+                "--minimized",
+            )
+        };
+        let bytes = shortcut.to_bytes();
+
+        assert_eq!(&bytes[..4], &[0x4C, 0, 0, 0]);
+        assert_eq!(&bytes[4..20], &LINK_CLSID);
+        assert_eq!(bytes[bytes.len() - 4..], [0, 0, 0, 0]);
+        assert_eq!(Shortcut::parse(&bytes), Ok(shortcut));
+
+        let plain = Shortcut::new(Path::new(r"C:\Tools\app.exe"), "");
+        assert_eq!(Shortcut::parse(&plain.to_bytes()), Ok(plain));
+    }
+
+    #[test]
+    fn test_link_info_layout() {
+        let info = link_info(r"C:\a.exe");
+        assert_eq!(read_u32(&info, 0), Some(info.len() as u32));
+        assert_eq!(read_u32(&info, 4), Some(LINK_INFO_HEADER_SIZE));
+        // ANSI and Unicode copies of the path
+        assert_eq!(
+            read_ansiz(&info, read_u32(&info, 16).unwrap() as usize).as_deref(),
+            Some(r"C:\a.exe")
+        );
+        assert_eq!(
+            read_utf16z(&info, read_u32(&info, 28).unwrap() as usize).as_deref(),
+            Some(r"C:\a.exe")
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_other_files() {
+        assert!(Shortcut::parse(b"").is_err());
+        assert!(Shortcut::parse(b"MZ\x90\x00 not a shortcut at all, just bytes").is_err());
+
+        // Header only, cut off before the LinkInfo it announces
+        let bytes = Shortcut::new(Path::new(r"C:\a.exe"), "").to_bytes();
+        assert!(Shortcut::parse(&bytes[..80]).is_err());
+    }
+}
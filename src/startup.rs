@@ -1,114 +1,542 @@
-//! Windows startup management via registry
-
-use std::ffi::OsStr;
-use std::os::windows::ffi::OsStrExt;
-use windows::{
-    core::PCWSTR,
-    Win32::Foundation::WIN32_ERROR,
-    Win32::System::Registry::{
-        RegCloseKey, RegDeleteValueW, RegOpenKeyExW, RegQueryValueExW, RegSetValueExW, HKEY,
-        HKEY_CURRENT_USER, KEY_QUERY_VALUE, KEY_SET_VALUE, REG_SZ,
-    },
-};
-
-const STARTUP_KEY: &str = r"SOFTWARE\Microsoft\Windows\CurrentVersion\Run";
+//! Windows startup management
+//!
+//! The app can start at logon through the `Run` registry value, a per-user
+//! scheduled task, or a shortcut in the Startup folder (for machines where
+//! policy blocks or purges the `Run` key). Each stores a command line: the
+//! quoted executable path followed by launch arguments. On every launch the
+//! stored command is compared with the running executable so moved or updated
+//! copies can be repaired.
+
+use std::path::{Path, PathBuf};
+#[cfg(windows)]
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(windows)]
+use crate::registry::CurrentUser;
+use crate::registry::Registry;
+use crate::shortcut::Shortcut;
+#[cfg(windows)]
+use crate::{logger, paths, scheduled_task};
+
+/// Per-user `Run` key (relative to `HKEY_CURRENT_USER`)
+pub const STARTUP_KEY: &str = r"SOFTWARE\Microsoft\Windows\CurrentVersion\Run";
 const APP_NAME: &str = "KakaoTalkAdBlock";
 
-/// Convert a Rust string to a wide string (null-terminated UTF-16)
-fn to_wide(s: &str) -> Vec<u16> {
-    OsStr::new(s)
-        .encode_wide()
-        .chain(std::iter::once(0))
-        .collect()
+/// Argument marking a launch at logon
+pub const MINIMIZED_ARG: &str = "--minimized";
+
+/// Arguments passed when started at logon
+const STARTUP_ARGS: &[&str] = &[MINIMIZED_ARG];
+
+/// Shortcut file name in the Startup folder
+const SHORTCUT_NAME: &str = "KakaoTalkAdBlock.lnk";
+
+/// How the app is started at logon
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StartupMethod {
+    /// `HKCU\...\Run` value
+    #[default]
+    Registry,
+    /// Task Scheduler task triggered at logon
+    ScheduledTask,
+    /// Shortcut in the user's Startup folder
+    StartupFolder,
 }
 
-/// Check if the application is set to run at startup
-pub fn is_startup_enabled() -> bool {
-    unsafe {
-        let key_path = to_wide(STARTUP_KEY);
-        let mut hkey = HKEY::default();
+impl StartupMethod {
+    /// All methods, in the order they are detected and tried as fallbacks
+    pub const ALL: [StartupMethod; 3] = [
+        StartupMethod::Registry,
+        StartupMethod::ScheduledTask,
+        StartupMethod::StartupFolder,
+    ];
+}
 
-        let result = RegOpenKeyExW(
-            HKEY_CURRENT_USER,
-            PCWSTR(key_path.as_ptr()),
-            0,
-            KEY_QUERY_VALUE,
-            &mut hkey,
-        );
+/// What the stored startup command means for this executable
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StartupState {
+    /// No startup entry
+    Disabled,
+    /// Starts this executable with the expected arguments
+    Enabled,
+    /// Starts this executable, but in an older format (unquoted, other arguments)
+    Outdated,
+    /// Starts a different (possibly missing) executable
+    Stale(PathBuf),
+}
 
-        if result != WIN32_ERROR(0) {
-            return false;
-        }
+/// Command line stored in the `Run` value
+pub fn command_line(exe: &Path, args: &[&str]) -> String {
+    let mut command = format!("\"{}\"", exe.display());
+    for arg in args {
+        command.push(' ');
+        command.push_str(arg);
+    }
+    command
+}
 
-        let value_name = to_wide(APP_NAME);
-        let result = RegQueryValueExW(hkey, PCWSTR(value_name.as_ptr()), None, None, None, None);
+/// Split a stored command line into the executable and its arguments
+///
+/// Older versions stored the path unquoted, so an unquoted value runs up to
+/// the first `.exe`.
+pub fn parse_command_line(command: &str) -> Option<(PathBuf, String)> {
+    let command = command.trim();
+    let (exe, args) = if let Some(quoted) = command.strip_prefix('"') {
+        let end = quoted.find('"')?;
+        (&quoted[..end], &quoted[end + 1..])
+    } else {
+        let end = command
+            .to_ascii_lowercase()
+            .find(".exe")
+            .map(|i| i + ".exe".len())
+            .unwrap_or_else(|| command.find(' ').unwrap_or(command.len()));
+        command.split_at(end)
+    };
 
-        let _ = RegCloseKey(hkey);
+    if exe.is_empty() {
+        return None;
+    }
+    Some((PathBuf::from(exe), args.trim().to_string()))
+}
 
-        result == WIN32_ERROR(0)
+/// Compare a stored startup command with the running executable
+pub fn check_command(command: Option<&str>, current_exe: &Path) -> StartupState {
+    let Some(command) = command.filter(|c| !c.trim().is_empty()) else {
+        return StartupState::Disabled;
+    };
+    let Some((exe, _)) = parse_command_line(command) else {
+        return StartupState::Stale(PathBuf::from(command));
+    };
+
+    if !same_path(&exe, current_exe) {
+        StartupState::Stale(exe)
+    } else if command.trim() == command_line(current_exe, STARTUP_ARGS) {
+        StartupState::Enabled
+    } else {
+        StartupState::Outdated
     }
 }
 
-/// Enable or disable running at startup
-pub fn set_startup_enabled(enable: bool) -> Result<(), String> {
-    unsafe {
-        let key_path = to_wide(STARTUP_KEY);
-        let mut hkey = HKEY::default();
+/// Windows paths compare case-insensitively and accept either separator
+pub fn same_path(a: &Path, b: &Path) -> bool {
+    let normalize = |p: &Path| p.to_string_lossy().replace('/', "\\").to_lowercase();
+    normalize(a) == normalize(b)
+}
 
-        let result = RegOpenKeyExW(
-            HKEY_CURRENT_USER,
-            PCWSTR(key_path.as_ptr()),
-            0,
-            KEY_SET_VALUE,
-            &mut hkey,
-        );
+/// Whether launch arguments mark a start at logon
+pub fn is_startup_launch(mut args: impl Iterator<Item = String>) -> bool {
+    args.any(|arg| arg == MINIMIZED_ARG)
+}
+
+/// What to do about the startup entry at launch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Repair {
+    /// Nothing to repair, or the user already kept this entry
+    Nothing,
+    /// Rewrite this executable's entry in the current format
+    Rewrite(StartupMethod),
+    /// Ask before replacing the entry for another executable
+    Ask(StartupMethod, PathBuf),
+}
 
-        if result != WIN32_ERROR(0) {
-            return Err(format!("Failed to open registry key: {:?}", result));
+/// Decide how to repair `entry`; `declined` is the executable of a stale
+/// entry the user chose to keep, which is not asked about again
+pub fn plan_repair(
+    entry: Option<(StartupMethod, StartupState)>,
+    declined: Option<&Path>,
+) -> Repair {
+    match entry {
+        Some((method, StartupState::Outdated)) => Repair::Rewrite(method),
+        Some((method, StartupState::Stale(exe))) => {
+            if declined.is_some_and(|declined| same_path(declined, &exe)) {
+                Repair::Nothing
+            } else {
+                Repair::Ask(method, exe)
+            }
         }
+        _ => Repair::Nothing,
+    }
+}
+
+/// Command line for an executable and an argument string
+fn join_command(exe: &Path, arguments: &str) -> String {
+    if arguments.is_empty() {
+        command_line(exe, &[])
+    } else {
+        command_line(exe, &[arguments])
+    }
+}
+
+/// The method whose entry matters most: one starting this executable
+/// correctly, otherwise the first one with any entry
+pub fn detect(
+    commands: &[(StartupMethod, Option<String>)],
+    current_exe: &Path,
+) -> Option<(StartupMethod, StartupState)> {
+    let states: Vec<(StartupMethod, StartupState)> = commands
+        .iter()
+        .map(|(method, command)| (*method, check_command(command.as_deref(), current_exe)))
+        .filter(|(_, state)| *state != StartupState::Disabled)
+        .collect();
 
-        let value_name = to_wide(APP_NAME);
-
-        let result = if enable {
-            // Get current executable path
-            let exe_path = std::env::current_exe()
-                .map(|p| p.to_string_lossy().to_string())
-                .unwrap_or_default();
-
-            let exe_path_wide = to_wide(&exe_path);
-            let data_len = (exe_path_wide.len() * 2) as u32;
-
-            RegSetValueExW(
-                hkey,
-                PCWSTR(value_name.as_ptr()),
-                0,
-                REG_SZ,
-                Some(std::slice::from_raw_parts(
-                    exe_path_wide.as_ptr() as *const u8,
-                    data_len as usize,
-                )),
+    states
+        .iter()
+        .find(|(_, state)| *state == StartupState::Enabled)
+        .or_else(|| states.first())
+        .cloned()
+}
+
+/// Stored `Run` command in `registry`; an unreadable entry counts as missing
+fn registry_command(registry: &dyn Registry) -> Option<String> {
+    registry.read_string(STARTUP_KEY, APP_NAME).ok().flatten()
+}
+
+/// Point the `Run` value at `current_exe`, or remove it
+fn set_registry_entry(
+    registry: &dyn Registry,
+    current_exe: &Path,
+    enable: bool,
+) -> Result<(), String> {
+    if enable {
+        registry
+            .write_string(
+                STARTUP_KEY,
+                APP_NAME,
+                &command_line(current_exe, STARTUP_ARGS),
             )
-        } else {
-            RegDeleteValueW(hkey, PCWSTR(value_name.as_ptr()))
+            .map_err(|e| format!("Failed to write the startup entry: {}", e))
+    } else {
+        registry
+            .delete_value(STARTUP_KEY, APP_NAME)
+            .map_err(|e| format!("Failed to remove the startup entry: {}", e))
+    }
+}
+
+/// Command of our shortcut in `folder`, if there is one
+fn shortcut_command(folder: &Path) -> Option<String> {
+    let bytes = std::fs::read(folder.join(SHORTCUT_NAME)).ok()?;
+    let shortcut = Shortcut::parse(&bytes).ok()?;
+    Some(join_command(&shortcut.target, &shortcut.arguments))
+}
+
+/// Create our shortcut to `current_exe` in `folder`, or remove it
+fn set_shortcut(folder: &Path, current_exe: &Path, enable: bool) -> Result<(), String> {
+    let path = folder.join(SHORTCUT_NAME);
+    if enable {
+        std::fs::create_dir_all(folder)
+            .map_err(|e| format!("Failed to create {}: {}", folder.display(), e))?;
+        let shortcut = Shortcut {
+            description: Some("KakaoTalkAdBlock".to_string()),
+            ..Shortcut::new(current_exe, &STARTUP_ARGS.join(" "))
         };
+        std::fs::write(&path, shortcut.to_bytes())
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    } else {
+        match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(format!("Failed to remove {}: {}", path.display(), e))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Stored command of every method, in detection order
+#[cfg(windows)]
+fn stored_commands() -> Vec<(StartupMethod, Option<String>)> {
+    StartupMethod::ALL
+        .iter()
+        .map(|&method| {
+            let command = match method {
+                StartupMethod::Registry => registry_command(&CurrentUser),
+                StartupMethod::ScheduledTask => {
+                    scheduled_task::query().map(|(exe, args)| join_command(&exe, &args))
+                }
+                StartupMethod::StartupFolder => shortcut_command(&paths::startup_folder()),
+            };
+            (method, command)
+        })
+        .collect()
+}
+
+/// The startup method in effect and what its entry means for this executable
+#[cfg(windows)]
+pub fn current_entry() -> Option<(StartupMethod, StartupState)> {
+    let current_exe = std::env::current_exe().unwrap_or_default();
+    detect(&stored_commands(), &current_exe)
+}
+
+#[cfg(windows)]
+fn set_method_enabled(
+    exe: &Path,
+    method: StartupMethod,
+    enable: bool,
+    delay: Duration,
+) -> Result<(), String> {
+    match (method, enable) {
+        (StartupMethod::Registry, _) => set_registry_entry(&CurrentUser, exe, enable),
+        (StartupMethod::ScheduledTask, true) => {
+            scheduled_task::create(exe, &STARTUP_ARGS.join(" "), delay)
+        }
+        (StartupMethod::ScheduledTask, false) => scheduled_task::delete(),
+        (StartupMethod::StartupFolder, _) => set_shortcut(&paths::startup_folder(), exe, enable),
+    }
+}
 
-        let _ = RegCloseKey(hkey);
+/// Start at logon using `preferred`, falling back to the other methods if it
+/// fails; returns the method that worked
+///
+/// Entries of the other methods are removed so the app starts only once.
+#[cfg(windows)]
+pub fn enable(preferred: StartupMethod, delay: Duration) -> Result<StartupMethod, String> {
+    let current_exe =
+        std::env::current_exe().map_err(|e| format!("Failed to locate executable: {}", e))?;
+    enable_for(&current_exe, preferred, delay)
+}
+
+/// Like [`enable`], but starting `exe` instead of this executable
+#[cfg(windows)]
+pub fn enable_for(
+    exe: &Path,
+    preferred: StartupMethod,
+    delay: Duration,
+) -> Result<StartupMethod, String> {
+    let order = std::iter::once(preferred)
+        .chain(StartupMethod::ALL.into_iter().filter(|&m| m != preferred));
 
-        if result == WIN32_ERROR(0) {
-            Ok(())
-        } else {
-            Err(format!("Failed to set registry value: {:?}", result))
+    let mut errors = Vec::new();
+    for method in order {
+        match set_method_enabled(exe, method, true, delay) {
+            Ok(()) => {
+                for other in StartupMethod::ALL.into_iter().filter(|&m| m != method) {
+                    if let Err(e) = set_method_enabled(exe, other, false, delay) {
+                        logger::log(e);
+                    }
+                }
+                return Ok(method);
+            }
+            Err(e) => {
+                logger::log(format!("Startup via {:?} failed: {}", method, e));
+                errors.push(e);
+            }
         }
     }
+    Err(errors.join("; "))
+}
+
+/// Remove the startup entries of every method
+#[cfg(windows)]
+pub fn disable() -> Result<(), String> {
+    // Removing an entry does not depend on the executable it starts
+    let exe = Path::new("");
+    let errors: Vec<String> = StartupMethod::ALL
+        .into_iter()
+        .filter_map(|method| set_method_enabled(exe, method, false, Duration::ZERO).err())
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("; "))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::registry::MemoryRegistry;
+    use crate::test_args::args;
+    use crate::test_dir::TestDir;
+
+    const EXE: &str = r"C:\Program Files\KakaoTalkAdBlock\KakaoTalkAdBlock.exe";
 
     #[test]
-    fn test_startup_check() {
-        // Should not panic
-        let _ = is_startup_enabled();
+    fn test_command_line_round_trip() {
+        let command = command_line(Path::new(EXE), STARTUP_ARGS);
+        assert_eq!(command, format!("\"{}\" --minimized", EXE));
+        assert_eq!(
+            parse_command_line(&command),
+            Some((PathBuf::from(EXE), "--minimized".to_string()))
+        );
+
+        // Unquoted values from older versions, even with spaces
+        assert_eq!(
+            parse_command_line(EXE),
+            Some((PathBuf::from(EXE), String::new()))
+        );
+        assert_eq!(
+            parse_command_line(r"C:\Tools\blocker --flag"),
+            Some((PathBuf::from(r"C:\Tools\blocker"), "--flag".to_string()))
+        );
+        assert_eq!(parse_command_line("\"unterminated"), None);
+        assert_eq!(parse_command_line("\"\" --minimized"), None);
+    }
+
+    #[test]
+    fn test_check_command() {
+        let exe = Path::new(EXE);
+        assert_eq!(check_command(None, exe), StartupState::Disabled);
+        assert_eq!(check_command(Some(" "), exe), StartupState::Disabled);
+        assert_eq!(
+            check_command(Some(&command_line(exe, STARTUP_ARGS)), exe),
+            StartupState::Enabled
+        );
+
+        // Same executable in the old format
+        assert_eq!(check_command(Some(EXE), exe), StartupState::Outdated);
+        assert_eq!(
+            check_command(Some(&EXE.to_uppercase()), exe),
+            StartupState::Outdated
+        );
+
+        // Moved or replaced executable
+        assert_eq!(
+            check_command(Some(r#""C:\Old\KakaoTalkAdBlock.exe" --minimized"#), exe),
+            StartupState::Stale(PathBuf::from(r"C:\Old\KakaoTalkAdBlock.exe"))
+        );
+    }
+
+    #[test]
+    fn test_is_startup_launch() {
+        assert!(is_startup_launch(args(&["app.exe", "--minimized"])));
+        assert!(!is_startup_launch(args(&["app.exe"])));
+    }
+
+    #[test]
+    fn test_enable_and_disable() {
+        let registry = MemoryRegistry::new();
+        let exe = Path::new(EXE);
+
+        let state = |registry: &MemoryRegistry, exe: &Path| {
+            check_command(registry_command(registry).as_deref(), exe)
+        };
+
+        // The Run key may be missing entirely
+        assert_eq!(state(&registry, exe), StartupState::Disabled);
+        assert_eq!(set_registry_entry(&registry, exe, false), Ok(()));
+
+        set_registry_entry(&registry, exe, true).unwrap();
+        assert_eq!(state(&registry, exe), StartupState::Enabled);
+        assert_eq!(
+            registry.read_string(STARTUP_KEY, APP_NAME),
+            Ok(Some(format!("\"{}\" --minimized", EXE)))
+        );
+        assert_eq!(
+            state(&registry, Path::new(r"D:\Moved\KakaoTalkAdBlock.exe")),
+            StartupState::Stale(PathBuf::from(EXE))
+        );
+
+        set_registry_entry(&registry, exe, false).unwrap();
+        assert_eq!(state(&registry, exe), StartupState::Disabled);
+    }
+
+    #[test]
+    fn test_access_denied() {
+        let registry = MemoryRegistry::new();
+        registry
+            .write_string(
+                STARTUP_KEY,
+                APP_NAME,
+                &command_line(Path::new(EXE), STARTUP_ARGS),
+            )
+            .unwrap();
+        registry.deny_access(STARTUP_KEY);
+
+        assert_eq!(registry_command(&registry), None);
+        assert_eq!(
+            set_registry_entry(&registry, Path::new(EXE), true),
+            Err("Failed to write the startup entry: access denied".to_string())
+        );
+        assert_eq!(
+            set_registry_entry(&registry, Path::new(EXE), false),
+            Err("Failed to remove the startup entry: access denied".to_string())
+        );
+    }
+
+    #[test]
+    fn test_declined_repair_is_not_asked_again() {
+        let registry = MemoryRegistry::new();
+        let exe = Path::new(EXE);
+        let old = Path::new(r"C:\Old\KakaoTalkAdBlock.exe");
+        let entry = |registry: &MemoryRegistry| {
+            detect(
+                &[(StartupMethod::Registry, registry_command(registry))],
+                exe,
+            )
+        };
+
+        set_registry_entry(&registry, old, true).unwrap();
+        assert_eq!(
+            plan_repair(entry(&registry), None),
+            Repair::Ask(StartupMethod::Registry, old.to_path_buf())
+        );
+        // The user said no
+        assert_eq!(plan_repair(entry(&registry), Some(old)), Repair::Nothing);
+
+        // Another executable is asked about again
+        let other = Path::new(r"D:\Other\KakaoTalkAdBlock.exe");
+        set_registry_entry(&registry, other, true).unwrap();
+        assert_eq!(
+            plan_repair(entry(&registry), Some(old)),
+            Repair::Ask(StartupMethod::Registry, other.to_path_buf())
+        );
+
+        // This executable's own entry is always brought up to date
+        registry.write_string(STARTUP_KEY, APP_NAME, EXE).unwrap();
+        assert_eq!(
+            plan_repair(entry(&registry), Some(old)),
+            Repair::Rewrite(StartupMethod::Registry)
+        );
+        set_registry_entry(&registry, exe, true).unwrap();
+        assert_eq!(plan_repair(entry(&registry), Some(old)), Repair::Nothing);
+    }
+
+    #[test]
+    fn test_startup_folder_shortcut() {
+        let dir = TestDir::new("startup_folder");
+        let folder = dir.join("Startup");
+        let exe = Path::new(EXE);
+
+        assert_eq!(shortcut_command(&folder), None);
+        assert_eq!(set_shortcut(&folder, exe, false), Ok(()));
+
+        set_shortcut(&folder, exe, true).unwrap();
+        assert_eq!(
+            check_command(shortcut_command(&folder).as_deref(), exe),
+            StartupState::Enabled
+        );
+
+        set_shortcut(&folder, exe, false).unwrap();
+        assert_eq!(shortcut_command(&folder), None);
+    }
+
+    #[test]
+    fn test_detect_method_in_effect() {
+        let exe = Path::new(EXE);
+        let current = command_line(exe, STARTUP_ARGS);
+        let stale = r#""C:\Old\KakaoTalkAdBlock.exe""#.to_string();
+
+        assert_eq!(detect(&[(StartupMethod::Registry, None)], exe), None);
+        // A working entry wins over a stale one listed earlier
+        assert_eq!(
+            detect(
+                &[
+                    (StartupMethod::Registry, Some(stale.clone())),
+                    (StartupMethod::ScheduledTask, None),
+                    (StartupMethod::StartupFolder, Some(current)),
+                ],
+                exe
+            ),
+            Some((StartupMethod::StartupFolder, StartupState::Enabled))
+        );
+        assert_eq!(
+            detect(&[(StartupMethod::ScheduledTask, Some(stale))], exe),
+            Some((
+                StartupMethod::ScheduledTask,
+                StartupState::Stale(PathBuf::from(r"C:\Old\KakaoTalkAdBlock.exe"))
+            ))
+        );
     }
 }
@@ -0,0 +1,9 @@
+//! Command lines for tests
+
+/// `list` as the iterator `std::env::args` would return
+pub fn args(list: &[&str]) -> impl Iterator<Item = String> {
+    list.iter()
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>()
+        .into_iter()
+}
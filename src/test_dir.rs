@@ -0,0 +1,50 @@
+//! Temporary directories for tests
+//!
+//! Each directory is unique to the process and the call, starts out empty and
+//! is removed again when dropped, even if the test fails.
+
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+/// An empty directory under the system temp directory
+pub struct TestDir {
+    path: PathBuf,
+}
+
+impl TestDir {
+    /// Create a fresh directory; `name` only makes it easier to recognise
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "kakaotalk_adblock_test_{}_{}_{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed),
+            name
+        ));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        Self { path }
+    }
+}
+
+impl Deref for TestDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl AsRef<Path> for TestDir {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
@@ -0,0 +1,778 @@
+//! Periodic update checks against the GitHub releases API
+//!
+//! The releases list is fetched and the newest release matching the update
+//! channel is chosen using the build scheme's version ordering.
+//!
+//! The last response's ETag is cached on disk so re-checks are conditional
+//! requests (`If-None-Match`), which GitHub does not count against the
+//! unauthenticated rate limit when they return 304. Rate-limit responses
+//! (403/429) and other failures back off instead of retrying immediately.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::network::{NetworkConfig, RequestError};
+use crate::version::{self, Version};
+use crate::{logger, paths};
+
+/// Default endpoint for the releases list
+///
+/// Endpoints returning a single release (like `/releases/latest`) also work.
+pub const DEFAULT_ENDPOINT: &str =
+    "https://api.github.com/repos/ssut/kakaotalk-adblock-rs/releases?per_page=50";
+
+const USER_AGENT: &str = "KakaoTalkAdBlock";
+const CACHE_FILE: &str = "update_cache.json";
+
+/// Time between successful checks
+const CHECK_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+/// First retry delay after a failure; doubles per consecutive failure
+const RETRY_DELAY: Duration = Duration::from_secs(5 * 60);
+/// Shortest wait after a rate-limit response
+const MIN_RATE_LIMIT_DELAY: Duration = Duration::from_secs(60);
+
+/// The parts of a GitHub release we use
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    /// Release notes (markdown)
+    #[serde(default)]
+    pub body: Option<String>,
+    /// ISO 8601 publish time
+    #[serde(default)]
+    pub published_at: Option<String>,
+    #[serde(default)]
+    pub prerelease: bool,
+    #[serde(default)]
+    pub draft: bool,
+    #[serde(default)]
+    pub assets: Vec<Asset>,
+}
+
+/// Body of a releases endpoint: the list, or a single release for mirrors of
+/// `/releases/latest`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ReleasesResponse {
+    List(Vec<Release>),
+    Single(Release),
+}
+
+/// Which releases the update check considers
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UpdateChannel {
+    /// Regular releases only
+    #[default]
+    Stable,
+    /// Also pre-releases, including published dev builds
+    Prerelease,
+    /// Exactly this release tag, even if older than the running build
+    Pinned(String),
+}
+
+/// A downloadable file attached to a release
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Asset {
+    pub name: String,
+    pub browser_download_url: String,
+    #[serde(default)]
+    pub size: u64,
+}
+
+/// Result of a successful check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckOutcome {
+    /// The latest release is not newer than the running build
+    UpToDate(String),
+    /// A newer release exists
+    UpdateAvailable(Release),
+}
+
+/// Why a check failed (as opposed to finding no update)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckError {
+    /// HTTP 403/429; `retry_after` comes from `Retry-After` or `X-RateLimit-Reset`
+    RateLimited { retry_after: Option<Duration> },
+    /// Any other non-success HTTP status
+    Http(u16),
+    /// Connection, DNS or TLS failure
+    Network(String),
+    /// The response could not be understood
+    InvalidResponse(String),
+    /// No published release matches the update channel
+    NoMatchingRelease,
+    /// Offline mode is on; nothing was requested
+    Offline,
+}
+
+impl fmt::Display for CheckError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckError::RateLimited { .. } => write!(f, "rate limited"),
+            CheckError::Http(status) => write!(f, "HTTP {}", status),
+            CheckError::Network(e) => write!(f, "network error ({})", e),
+            CheckError::InvalidResponse(e) => write!(f, "invalid response ({})", e),
+            CheckError::NoMatchingRelease => write!(f, "no matching release"),
+            CheckError::Offline => write!(f, "offline mode"),
+        }
+    }
+}
+
+/// Last successful response, persisted between runs
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+struct UpdateCache {
+    /// Endpoint the cached response came from (ETags are per-URL)
+    endpoint: Option<String>,
+    etag: Option<String>,
+    releases: Option<Vec<Release>>,
+}
+
+/// Checks one endpoint and tracks failures for backoff
+pub struct UpdateChecker {
+    network: NetworkConfig,
+    endpoint: String,
+    channel: UpdateChannel,
+    cache_path: PathBuf,
+    cache: UpdateCache,
+    failures: u32,
+    retry_after: Option<Duration>,
+}
+
+impl UpdateChecker {
+    /// Create a checker using the on-disk cache in the local data directory
+    pub fn new(endpoint: &str, channel: UpdateChannel, network: NetworkConfig) -> Self {
+        let mut checker =
+            Self::with_cache_path(endpoint, channel, paths::local_data_dir().join(CACHE_FILE));
+        checker.network = network;
+        checker
+    }
+
+    fn with_cache_path(endpoint: &str, channel: UpdateChannel, cache_path: PathBuf) -> Self {
+        Self {
+            network: NetworkConfig::default(),
+            endpoint: endpoint.to_string(),
+            channel,
+            cache: load_cache(&cache_path),
+            cache_path,
+            failures: 0,
+            retry_after: None,
+        }
+    }
+
+    /// Switch channels; takes effect on the next check
+    pub fn set_channel(&mut self, channel: UpdateChannel) {
+        self.channel = channel;
+    }
+
+    /// Replace the proxy, certificate and offline settings
+    pub fn set_network(&mut self, network: NetworkConfig) {
+        self.network = network;
+    }
+
+    /// Fetch the releases and compare the channel's pick with `current_version`
+    pub fn check(&mut self, current_version: &str) -> Result<CheckOutcome, CheckError> {
+        // Being offline by choice is not a failure to back off from
+        if self.network.offline {
+            return Err(CheckError::Offline);
+        }
+
+        let result = self.fetch_releases();
+
+        match &result {
+            Ok(_) => {
+                self.failures = 0;
+                self.retry_after = None;
+            }
+            Err(e) => {
+                self.failures = self.failures.saturating_add(1);
+                self.retry_after = match e {
+                    CheckError::RateLimited { retry_after } => {
+                        Some(retry_after.unwrap_or(RETRY_DELAY).max(MIN_RATE_LIMIT_DELAY))
+                    }
+                    _ => None,
+                };
+            }
+        }
+
+        let releases = result?;
+        let release = select_release(&releases, &self.channel)
+            .ok_or(CheckError::NoMatchingRelease)?
+            .clone();
+
+        if is_update(&self.channel, current_version, &releases, &release) {
+            Ok(CheckOutcome::UpdateAvailable(release))
+        } else {
+            Ok(CheckOutcome::UpToDate(release.tag_name))
+        }
+    }
+
+    /// Delay before the next check, based on the last result
+    pub fn next_delay(&self) -> Duration {
+        if self.failures == 0 {
+            return CHECK_INTERVAL;
+        }
+
+        let backoff = RETRY_DELAY
+            .saturating_mul(2u32.saturating_pow(self.failures - 1))
+            .min(CHECK_INTERVAL);
+        match self.retry_after {
+            Some(retry_after) => retry_after.max(backoff),
+            None => backoff,
+        }
+    }
+
+    /// Whether the last check was rejected by rate limiting
+    pub fn is_rate_limited(&self) -> bool {
+        self.retry_after.is_some()
+    }
+
+    fn fetch_releases(&mut self) -> Result<Vec<Release>, CheckError> {
+        let mut headers = vec![
+            ("User-Agent", USER_AGENT),
+            ("Accept", "application/vnd.github+json"),
+        ];
+
+        // Only send the ETag if we still have the response it belongs to
+        let cache_valid = self.cache.endpoint.as_deref() == Some(self.endpoint.as_str())
+            && self.cache.releases.is_some();
+        if cache_valid {
+            if let Some(etag) = &self.cache.etag {
+                headers.push(("If-None-Match", etag));
+            }
+        }
+
+        let response = match self.network.get(&self.endpoint, &headers) {
+            Ok(response) => response,
+            Err(RequestError::Setup(e)) => return Err(CheckError::Network(e)),
+            Err(RequestError::Http(e)) => match *e {
+                ureq::Error::Status(403 | 429, response) => {
+                    return Err(CheckError::RateLimited {
+                        retry_after: rate_limit_delay(&response, SystemTime::now()),
+                    });
+                }
+                ureq::Error::Status(status, _) => return Err(CheckError::Http(status)),
+                ureq::Error::Transport(e) => return Err(CheckError::Network(e.to_string())),
+            },
+        };
+
+        if response.status() == 304 {
+            return match (&self.cache.releases, cache_valid) {
+                (Some(releases), true) => Ok(releases.clone()),
+                _ => Err(CheckError::InvalidResponse(
+                    "304 without a cached response".to_string(),
+                )),
+            };
+        }
+
+        let etag = response.header("ETag").map(String::from);
+        let releases = match response
+            .into_json()
+            .map_err(|e| CheckError::InvalidResponse(e.to_string()))?
+        {
+            ReleasesResponse::List(releases) => releases,
+            ReleasesResponse::Single(release) => vec![release],
+        };
+
+        self.cache = UpdateCache {
+            endpoint: Some(self.endpoint.clone()),
+            etag,
+            releases: Some(releases.clone()),
+        };
+        if let Err(e) = save_cache(&self.cache_path, &self.cache) {
+            logger::log(format!("Failed to save update cache: {}", e));
+        }
+
+        Ok(releases)
+    }
+}
+
+/// Requests to the background checker
+pub enum CheckerCommand {
+    /// Check now
+    Recheck,
+    /// Switch channels and check now
+    SetChannel(UpdateChannel),
+    /// Apply new network settings and check now
+    SetNetwork(NetworkConfig),
+}
+
+/// Pick the newest release on `channel`
+///
+/// Drafts are ignored. Releases are ordered by their version; dev-tagged
+/// pre-releases have no version order, so they are compared by publish time
+/// and win over the newest versioned release only if published after it.
+pub fn select_release<'a>(releases: &'a [Release], channel: &UpdateChannel) -> Option<&'a Release> {
+    let allow_prerelease = match channel {
+        UpdateChannel::Pinned(tag) => {
+            return releases.iter().find(|r| !r.draft && &r.tag_name == tag);
+        }
+        UpdateChannel::Stable => false,
+        UpdateChannel::Prerelease => true,
+    };
+    let candidates: Vec<(&Release, Version)> = releases
+        .iter()
+        .filter(|r| !r.draft && (allow_prerelease || !r.prerelease))
+        .filter_map(|r| Some((r, Version::parse(&r.tag_name)?)))
+        .collect();
+
+    let newest_versioned = candidates
+        .iter()
+        .filter(|(_, v)| !v.is_dev())
+        .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal))
+        .map(|&(r, _)| r);
+    let newest_dev = candidates
+        .iter()
+        .filter(|(_, v)| allow_prerelease && v.is_dev())
+        .max_by_key(|(r, _)| r.published_at.as_deref())
+        .map(|&(r, _)| r);
+
+    match (newest_versioned, newest_dev) {
+        (Some(versioned), Some(dev)) if dev.published_at > versioned.published_at => Some(dev),
+        (Some(versioned), _) => Some(versioned),
+        (None, dev) => dev,
+    }
+}
+
+/// Whether the channel's pick should be offered over the running build
+///
+/// Dev builds have no version order, so when either side is one the pick must
+/// have been published after the running build's own release. A running build
+/// missing from `releases` (such as a local build) is never replaced that way.
+fn is_update(
+    channel: &UpdateChannel,
+    current_version: &str,
+    releases: &[Release],
+    pick: &Release,
+) -> bool {
+    match channel {
+        UpdateChannel::Stable => version::has_new_release(current_version, &pick.tag_name),
+        UpdateChannel::Prerelease => {
+            match (
+                Version::parse(current_version),
+                Version::parse(&pick.tag_name),
+            ) {
+                (Some(current), Some(latest)) if !current.is_dev() && !latest.is_dev() => {
+                    latest > current
+                }
+                (Some(_), Some(_)) => {
+                    let current_published = releases
+                        .iter()
+                        .find(|r| !r.draft && r.tag_name == current_version)
+                        .and_then(|r| r.published_at.as_deref());
+                    match (current_published, pick.published_at.as_deref()) {
+                        (Some(current), Some(latest)) => latest > current,
+                        _ => false,
+                    }
+                }
+                _ => false,
+            }
+        }
+        // Pinning may move to an older release
+        UpdateChannel::Pinned(tag) => current_version != tag,
+    }
+}
+
+/// Check now, then keep re-checking in the background
+///
+/// A command on `commands` triggers an early check, unless the server asked us
+/// to back off. Returns when either channel is closed.
+pub fn run_periodic(
+    mut checker: UpdateChecker,
+    current_version: &str,
+    commands: Receiver<CheckerCommand>,
+    results: Sender<Result<CheckOutcome, CheckError>>,
+) {
+    loop {
+        let result = checker.check(current_version);
+        match &result {
+            Ok(_) | Err(CheckError::Offline) => {}
+            Err(e) => logger::log(format!("Update check failed: {}", e)),
+        }
+        if results.send(result).is_err() {
+            return;
+        }
+
+        let deadline = Instant::now() + checker.next_delay();
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match commands.recv_timeout(timeout) {
+                Ok(CheckerCommand::SetChannel(channel)) => {
+                    checker.set_channel(channel);
+                    if !checker.is_rate_limited() {
+                        break;
+                    }
+                }
+                Ok(CheckerCommand::SetNetwork(network)) => {
+                    checker.set_network(network);
+                    if !checker.is_rate_limited() {
+                        break;
+                    }
+                }
+                Ok(CheckerCommand::Recheck) if checker.is_rate_limited() => continue,
+                Ok(CheckerCommand::Recheck) | Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+    }
+}
+
+/// How long a rate-limited response asks us to wait
+fn rate_limit_delay(response: &ureq::Response, now: SystemTime) -> Option<Duration> {
+    if let Some(seconds) = response
+        .header("Retry-After")
+        .and_then(|v| v.trim().parse::<u64>().ok())
+    {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    // GitHub reports the primary rate limit window as an epoch timestamp
+    if response.header("X-RateLimit-Remaining").map(str::trim) == Some("0") {
+        let reset = response
+            .header("X-RateLimit-Reset")
+            .and_then(|v| v.trim().parse::<u64>().ok())?;
+        let now = now.duration_since(UNIX_EPOCH).ok()?.as_secs();
+        return Some(Duration::from_secs(reset.saturating_sub(now)));
+    }
+
+    None
+}
+
+fn load_cache(path: &Path) -> UpdateCache {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn save_cache(path: &Path, cache: &UpdateCache) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    let json = serde_json::to_string_pretty(cache)
+        .map_err(|e| format!("Failed to serialize update cache: {}", e))?;
+    std::fs::write(path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+    use crate::test_server::{response, TestServer};
+
+    const RELEASE_JSON: &[u8] = br#"{"tag_name":"20250102-01","name":"ignored"}"#;
+
+    #[test]
+    fn test_etag_is_cached_and_sent() {
+        let dir = TestDir::new("update_etag");
+        let cache_path = dir.join(CACHE_FILE);
+        let server = TestServer::start(vec![
+            response("200 OK", &[("ETag", "\"abc\"")], RELEASE_JSON),
+            response("304 Not Modified", &[("ETag", "\"abc\"")], b""),
+        ]);
+        let endpoint = format!("{}/releases/latest", server.url);
+
+        let mut checker =
+            UpdateChecker::with_cache_path(&endpoint, UpdateChannel::Stable, cache_path.clone());
+        match checker.check("20250101-01") {
+            Ok(CheckOutcome::UpdateAvailable(release)) => {
+                assert_eq!(release.tag_name, "20250102-01")
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        // A fresh checker picks the ETag up from disk
+        let mut checker =
+            UpdateChecker::with_cache_path(&endpoint, UpdateChannel::Stable, cache_path.clone());
+        assert_eq!(
+            checker.check("20250102-01"),
+            Ok(CheckOutcome::UpToDate("20250102-01".to_string()))
+        );
+        assert_eq!(checker.next_delay(), CHECK_INTERVAL);
+
+        let requests = server.requests();
+        assert!(!requests[0].to_lowercase().contains("if-none-match"));
+        assert!(requests[1]
+            .to_lowercase()
+            .contains("if-none-match: \"abc\""));
+    }
+
+    #[test]
+    fn test_etag_not_reused_for_other_endpoint() {
+        let dir = TestDir::new("update_other_endpoint");
+        let cache_path = dir.join(CACHE_FILE);
+        save_cache(
+            &cache_path,
+            &UpdateCache {
+                endpoint: Some("https://mirror.invalid/latest".to_string()),
+                etag: Some("\"abc\"".to_string()),
+                releases: Some(vec![release("20250101-01", false, None)]),
+            },
+        )
+        .unwrap();
+        let server = TestServer::start(vec![response("200 OK", &[], RELEASE_JSON)]);
+
+        let mut checker =
+            UpdateChecker::with_cache_path(&server.url, UpdateChannel::Stable, cache_path.clone());
+        assert!(checker.check("20250101-01").is_ok());
+        assert!(!server.requests()[0]
+            .to_lowercase()
+            .contains("if-none-match"));
+    }
+
+    #[test]
+    fn test_rate_limit_backs_off() {
+        let dir = TestDir::new("update_rate_limit");
+        let cache_path = dir.join(CACHE_FILE);
+        let server = TestServer::start(vec![
+            response("429 Too Many Requests", &[("Retry-After", "7200")], b""),
+            response(
+                "403 Forbidden",
+                &[("X-RateLimit-Remaining", "0"), ("X-RateLimit-Reset", "1")],
+                b"",
+            ),
+        ]);
+
+        let mut checker =
+            UpdateChecker::with_cache_path(&server.url, UpdateChannel::Stable, cache_path.clone());
+        assert_eq!(
+            checker.check("20250101-01"),
+            Err(CheckError::RateLimited {
+                retry_after: Some(Duration::from_secs(7200))
+            })
+        );
+        assert!(checker.is_rate_limited());
+        assert_eq!(checker.next_delay(), Duration::from_secs(7200));
+
+        // A reset time in the past still waits a minimum delay
+        assert_eq!(
+            checker.check("20250101-01"),
+            Err(CheckError::RateLimited {
+                retry_after: Some(Duration::ZERO)
+            })
+        );
+        assert!(checker.next_delay() >= MIN_RATE_LIMIT_DELAY);
+        server.requests();
+    }
+
+    #[test]
+    fn test_errors_are_distinguished() {
+        let dir = TestDir::new("update_errors");
+        let cache_path = dir.join(CACHE_FILE);
+        let server = TestServer::start(vec![
+            response("500 Internal Server Error", &[], b""),
+            response("200 OK", &[], b"not json"),
+        ]);
+
+        let mut checker =
+            UpdateChecker::with_cache_path(&server.url, UpdateChannel::Stable, cache_path.clone());
+        assert_eq!(checker.check("20250101-01"), Err(CheckError::Http(500)));
+        assert_eq!(checker.next_delay(), RETRY_DELAY);
+        assert!(matches!(
+            checker.check("20250101-01"),
+            Err(CheckError::InvalidResponse(_))
+        ));
+        assert_eq!(checker.next_delay(), RETRY_DELAY * 2);
+        assert!(!checker.is_rate_limited());
+        server.requests();
+
+        // Nothing listens on the server's port any more
+        let mut checker = UpdateChecker::with_cache_path(
+            &server_url_closed(),
+            UpdateChannel::Stable,
+            cache_path.clone(),
+        );
+        assert!(matches!(
+            checker.check("20250101-01"),
+            Err(CheckError::Network(_))
+        ));
+    }
+
+    fn release(tag: &str, prerelease: bool, published_at: Option<&str>) -> Release {
+        Release {
+            tag_name: tag.to_string(),
+            body: None,
+            published_at: published_at.map(String::from),
+            prerelease,
+            draft: false,
+            assets: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_select_release_by_channel() {
+        let mut draft = release("20250301-01", false, Some("2025-03-01T00:00:00Z"));
+        draft.draft = true;
+        let releases = vec![
+            release("v2.2.3", false, Some("2024-06-01T00:00:00Z")),
+            release("20250102-01", false, Some("2025-01-02T00:00:00Z")),
+            release("20250101-02", false, Some("2025-01-03T00:00:00Z")),
+            release("20250110-01", true, Some("2025-01-10T00:00:00Z")),
+            release("dev-abc1234", true, Some("2025-01-05T00:00:00Z")),
+            draft,
+        ];
+        let pick = |channel: UpdateChannel| {
+            select_release(&releases, &channel).map(|r| r.tag_name.as_str())
+        };
+
+        // Version order, not list or publish order
+        assert_eq!(pick(UpdateChannel::Stable), Some("20250102-01"));
+        assert_eq!(pick(UpdateChannel::Prerelease), Some("20250110-01"));
+        assert_eq!(
+            pick(UpdateChannel::Pinned("v2.2.3".to_string())),
+            Some("v2.2.3")
+        );
+        assert_eq!(pick(UpdateChannel::Pinned("20250301-01".to_string())), None);
+
+        // A dev build wins only if published after the newest versioned release
+        let mut with_new_dev = releases.clone();
+        with_new_dev.push(release("dev-def5678", true, Some("2025-02-01T00:00:00Z")));
+        assert_eq!(
+            select_release(&with_new_dev, &UpdateChannel::Prerelease).map(|r| &r.tag_name[..]),
+            Some("dev-def5678")
+        );
+        assert_eq!(
+            select_release(&with_new_dev, &UpdateChannel::Stable).map(|r| &r.tag_name[..]),
+            Some("20250102-01")
+        );
+    }
+
+    #[test]
+    fn test_is_update_by_channel() {
+        let releases = vec![
+            release("20250101-01", false, Some("2025-01-01T00:00:00Z")),
+            release("20250102-01", false, Some("2025-01-02T00:00:00Z")),
+            release("dev-abc1234", true, Some("2025-01-05T00:00:00Z")),
+            release("20250110-01", true, Some("2025-01-10T00:00:00Z")),
+            release("dev-def5678", true, Some("2025-02-01T00:00:00Z")),
+        ];
+        let update = |channel: &UpdateChannel, current: &str, tag: &str| {
+            let pick = releases.iter().find(|r| r.tag_name == tag).unwrap();
+            is_update(channel, current, &releases, pick)
+        };
+
+        assert!(update(&UpdateChannel::Stable, "20250101-01", "20250102-01"));
+        assert!(!update(
+            &UpdateChannel::Stable,
+            "dev-abc1234",
+            "20250102-01"
+        ));
+
+        let prerelease = UpdateChannel::Prerelease;
+        assert!(!update(&prerelease, "20250102-01", "20250101-01"));
+        assert!(update(&prerelease, "dev-abc1234", "dev-def5678"));
+        assert!(!update(&prerelease, "dev-def5678", "dev-abc1234"));
+        assert!(!update(&prerelease, "dev-abc1234", "dev-abc1234"));
+
+        // A dev build on either side is ordered by publish time
+        assert!(update(&prerelease, "dev-abc1234", "20250110-01"));
+        assert!(!update(&prerelease, "dev-def5678", "20250110-01"));
+        assert!(update(&prerelease, "20250102-01", "dev-abc1234"));
+        assert!(!update(&prerelease, "20250110-01", "dev-abc1234"));
+
+        // Builds that were never published are left alone
+        assert!(!update(&prerelease, "dev", "20250110-01"));
+        assert!(!update(&prerelease, "dev-0000000", "dev-def5678"));
+
+        let pinned = UpdateChannel::Pinned("20250101-01".to_string());
+        assert!(update(&pinned, "20250102-01", "20250101-01"));
+        assert!(!update(&pinned, "20250101-01", "20250101-01"));
+    }
+
+    #[test]
+    fn test_channel_from_releases_list() {
+        let dir = TestDir::new("update_list");
+        let cache_path = dir.join(CACHE_FILE);
+        let list = br#"[
+            {"tag_name": "20250103-01", "prerelease": true},
+            {"tag_name": "20250102-01", "prerelease": false},
+            {"tag_name": "20250101-01", "prerelease": false}
+        ]"#;
+        let server = TestServer::start(vec![response("200 OK", &[], list); 3]);
+
+        let mut checker =
+            UpdateChecker::with_cache_path(&server.url, UpdateChannel::Stable, cache_path.clone());
+        assert_eq!(
+            checker.check("20250102-01"),
+            Ok(CheckOutcome::UpToDate("20250102-01".to_string()))
+        );
+
+        checker.set_channel(UpdateChannel::Prerelease);
+        match checker.check("20250102-01") {
+            Ok(CheckOutcome::UpdateAvailable(release)) => {
+                assert_eq!(release.tag_name, "20250103-01")
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        checker.set_channel(UpdateChannel::Pinned("20240101-01".to_string()));
+        assert_eq!(
+            checker.check("20250102-01"),
+            Err(CheckError::NoMatchingRelease)
+        );
+        // Selection failures are not network failures and do not back off
+        assert_eq!(checker.next_delay(), CHECK_INTERVAL);
+        server.requests();
+    }
+
+    #[test]
+    fn test_offline_and_proxied_checks() {
+        let dir = TestDir::new("update_proxy");
+        let cache_path = dir.join(CACHE_FILE);
+        let proxy = TestServer::start(vec![response("200 OK", &[], RELEASE_JSON)]);
+
+        let mut checker = UpdateChecker::with_cache_path(
+            "http://updates.invalid/releases",
+            UpdateChannel::Stable,
+            cache_path.clone(),
+        );
+        checker.set_network(NetworkConfig {
+            offline: true,
+            proxy: Some(proxy.url.clone()),
+            ..Default::default()
+        });
+        assert_eq!(checker.check("20250101-01"), Err(CheckError::Offline));
+        assert_eq!(checker.next_delay(), CHECK_INTERVAL);
+
+        checker.set_network(NetworkConfig {
+            proxy: Some(proxy.url.clone()),
+            ..Default::default()
+        });
+        assert!(matches!(
+            checker.check("20250101-01"),
+            Ok(CheckOutcome::UpdateAvailable(_))
+        ));
+
+        // Only the online check reached the proxy
+        let requests = proxy.requests();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].starts_with("GET http://updates.invalid/releases "));
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let dir = TestDir::new("update_cap");
+        let mut checker = UpdateChecker::with_cache_path(
+            "http://unused",
+            UpdateChannel::Stable,
+            dir.join(CACHE_FILE),
+        );
+        checker.failures = 30;
+        assert_eq!(checker.next_delay(), CHECK_INTERVAL);
+    }
+
+    /// URL of a port that was bound and released, so connections are refused
+    fn server_url_closed() -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+        url
+    }
+}
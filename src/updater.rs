@@ -0,0 +1,389 @@
+//! In-app updater
+//!
+//! Downloads the release executable to a temp file, verifies it against the
+//! SHA-256 checksum published next to it, and swaps it in place of the running
+//! executable. Windows allows renaming a running exe, so the old file is moved
+//! aside (`*.old`) and the new one takes its exact path; the startup registry
+//! entry, which stores that path, stays valid. The relaunched process waits for
+//! this one to exit (`--wait-for-pid`) and removes the `*.old` file.
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::network::NetworkConfig;
+use crate::update::{Asset, Release};
+
+/// Argument passed to the relaunched process with the previous process ID
+pub const WAIT_FOR_PID_ARG: &str = "--wait-for-pid";
+
+/// Suffix of the checksum file published next to each release asset
+const CHECKSUM_SUFFIX: &str = ".sha256";
+/// Suffix of the previous executable after a swap
+const OLD_EXE_SUFFIX: &str = ".old";
+/// Refuse checksum files larger than this (they are a single line)
+const MAX_CHECKSUM_SIZE: u64 = 4096;
+
+/// Executable asset of a release and its checksum file
+#[derive(Debug, PartialEq, Eq)]
+pub struct UpdateAssets<'a> {
+    pub executable: &'a Asset,
+    pub checksum: &'a Asset,
+}
+
+/// Pick the executable and checksum assets from a release
+///
+/// Prefers `KakaoTalkAdBlock-{tag}.exe`, the name the release workflow uses.
+pub fn select_assets(release: &Release) -> Result<UpdateAssets<'_>, String> {
+    let expected_name = format!("KakaoTalkAdBlock-{}.exe", release.tag_name);
+    let executable = release
+        .assets
+        .iter()
+        .find(|a| a.name.eq_ignore_ascii_case(&expected_name))
+        .or_else(|| {
+            release
+                .assets
+                .iter()
+                .find(|a| a.name.to_ascii_lowercase().ends_with(".exe"))
+        })
+        .ok_or_else(|| format!("Release {} has no executable", release.tag_name))?;
+
+    let checksum_name = format!("{}{}", executable.name, CHECKSUM_SUFFIX);
+    let checksum = release
+        .assets
+        .iter()
+        .find(|a| a.name.eq_ignore_ascii_case(&checksum_name))
+        .ok_or_else(|| {
+            format!(
+                "Release {} has no checksum for {}",
+                release.tag_name, executable.name
+            )
+        })?;
+
+    Ok(UpdateAssets {
+        executable,
+        checksum,
+    })
+}
+
+/// Download the release executable into `dir` and verify its checksum
+///
+/// Returns the path of the verified file. Nothing is left behind on failure.
+pub fn download_verified(
+    network: &NetworkConfig,
+    release: &Release,
+    dir: &Path,
+) -> Result<PathBuf, String> {
+    let assets = select_assets(release)?;
+
+    let checksum_text = fetch_checksum(network, assets.checksum)?;
+    let expected = parse_checksum(&checksum_text, &assets.executable.name)
+        .ok_or_else(|| format!("Invalid checksum file {}", assets.checksum.name))?;
+
+    std::fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let path = dir.join(format!("{}.download", assets.executable.name));
+
+    let result =
+        download_to(network, &assets.executable.browser_download_url, &path).and_then(|actual| {
+            if actual == expected {
+                Ok(())
+            } else {
+                Err(format!(
+                    "Checksum mismatch for {}: expected {}, got {}",
+                    assets.executable.name,
+                    to_hex(&expected),
+                    to_hex(&actual)
+                ))
+            }
+        });
+
+    match result {
+        Ok(()) => Ok(path),
+        Err(e) => {
+            let _ = std::fs::remove_file(&path);
+            Err(e)
+        }
+    }
+}
+
+/// Replace `current_exe` with `new_exe`, keeping the previous file as `*.old`
+///
+/// Rolls back if the new file cannot be moved into place.
+pub fn install(new_exe: &Path, current_exe: &Path) -> Result<(), String> {
+    let old_exe = old_exe_path(current_exe);
+    let _ = std::fs::remove_file(&old_exe);
+
+    std::fs::rename(current_exe, &old_exe)
+        .map_err(|e| format!("Failed to move {} aside: {}", current_exe.display(), e))?;
+
+    // The download may live on another volume, where rename fails
+    let moved = std::fs::rename(new_exe, current_exe).or_else(|_| {
+        std::fs::copy(new_exe, current_exe)?;
+        std::fs::remove_file(new_exe)
+    });
+
+    if let Err(e) = moved {
+        let _ = std::fs::remove_file(current_exe);
+        let _ = std::fs::rename(&old_exe, current_exe);
+        return Err(format!("Failed to install {}: {}", new_exe.display(), e));
+    }
+
+    Ok(())
+}
+
+/// Remove the executable left behind by a previous update, if any
+pub fn remove_previous_exe(current_exe: &Path) {
+    let _ = std::fs::remove_file(old_exe_path(current_exe));
+}
+
+/// Start the (updated) executable, telling it to wait for this process to exit
+pub fn relaunch(current_exe: &Path) -> Result<(), String> {
+    std::process::Command::new(current_exe)
+        .arg(WAIT_FOR_PID_ARG)
+        .arg(std::process::id().to_string())
+        .spawn()
+        .map(|_| ())
+        .map_err(|e| format!("Failed to start {}: {}", current_exe.display(), e))
+}
+
+/// Process ID passed via `--wait-for-pid`, if any
+pub fn wait_for_pid_arg(mut args: impl Iterator<Item = String>) -> Option<u32> {
+    args.find(|arg| arg == WAIT_FOR_PID_ARG)?;
+    args.next()?.parse().ok()
+}
+
+fn old_exe_path(current_exe: &Path) -> PathBuf {
+    let mut name = current_exe.as_os_str().to_owned();
+    name.push(OLD_EXE_SUFFIX);
+    PathBuf::from(name)
+}
+
+fn fetch_checksum(network: &NetworkConfig, asset: &Asset) -> Result<String, String> {
+    let response = network
+        .get(&asset.browser_download_url, &[])
+        .map_err(|e| format!("Failed to download {}: {}", asset.name, e))?;
+
+    let mut text = String::new();
+    response
+        .into_reader()
+        .take(MAX_CHECKSUM_SIZE)
+        .read_to_string(&mut text)
+        .map_err(|e| format!("Failed to read {}: {}", asset.name, e))?;
+    Ok(text)
+}
+
+/// Stream `url` into `path`, returning the SHA-256 of the content
+fn download_to(network: &NetworkConfig, url: &str, path: &Path) -> Result<[u8; 32], String> {
+    let response = network
+        .get(url, &[])
+        .map_err(|e| format!("Failed to download {}: {}", url, e))?;
+
+    let mut file =
+        File::create(path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    let mut reader = response.into_reader();
+    let mut hasher = Sha256::new();
+    let mut buf = [0u8; 64 * 1024];
+
+    loop {
+        let n = reader
+            .read(&mut buf)
+            .map_err(|e| format!("Failed to download {}: {}", url, e))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        file.write_all(&buf[..n])
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    }
+
+    file.sync_all()
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(hasher.finalize().into())
+}
+
+/// Parse `sha256sum` output (`<hex>  <name>`) or a bare hex digest
+fn parse_checksum(text: &str, file_name: &str) -> Option<[u8; 32]> {
+    text.lines().find_map(|line| {
+        let mut parts = line.split_whitespace();
+        let hex = parts.next()?;
+        // sha256sum marks binary mode with a leading '*'
+        match parts.next().map(|name| name.trim_start_matches('*')) {
+            Some(name) if !name.eq_ignore_ascii_case(file_name) => None,
+            _ => from_hex(hex),
+        }
+    })
+}
+
+fn from_hex(hex: &str) -> Option<[u8; 32]> {
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+    let mut bytes = [0u8; 32];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(bytes)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_args::args;
+    use crate::test_dir::TestDir;
+    use crate::test_server::{response, TestServer};
+
+    const EXE_CONTENT: &[u8] = b"MZ pretend this is the new executable";
+
+    fn asset(name: &str, url: String) -> Asset {
+        Asset {
+            name: name.to_string(),
+            browser_download_url: url,
+            size: 0,
+        }
+    }
+
+    fn release(base_url: &str) -> Release {
+        Release {
+            tag_name: "20250102-01".to_string(),
+            body: None,
+            published_at: None,
+            prerelease: false,
+            draft: false,
+            assets: vec![
+                asset(
+                    "KakaoTalkAdBlock-20250102-01.exe.sha256",
+                    format!("{}/checksum", base_url),
+                ),
+                asset(
+                    "KakaoTalkAdBlock-20250102-01.exe",
+                    format!("{}/exe", base_url),
+                ),
+            ],
+        }
+    }
+
+    fn checksum_file(content: &[u8]) -> Vec<u8> {
+        format!(
+            "{}  KakaoTalkAdBlock-20250102-01.exe\n",
+            to_hex(&Sha256::digest(content))
+        )
+        .into_bytes()
+    }
+
+    #[test]
+    fn test_select_assets_from_release_json() {
+        let release: Release = serde_json::from_str(
+            r#"{
+                "tag_name": "20250102-01",
+                "body": "ignored",
+                "assets": [
+                    {"name": "notes.txt", "browser_download_url": "https://example.invalid/notes.txt", "size": 1},
+                    {"name": "KakaoTalkAdBlock-20250102-01.exe.sha256", "browser_download_url": "https://example.invalid/sum", "size": 99},
+                    {"name": "KakaoTalkAdBlock-20250102-01.exe", "browser_download_url": "https://example.invalid/exe", "size": 1234}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let assets = select_assets(&release).unwrap();
+        assert_eq!(
+            assets.executable.browser_download_url,
+            "https://example.invalid/exe"
+        );
+        assert_eq!(
+            assets.checksum.browser_download_url,
+            "https://example.invalid/sum"
+        );
+
+        let unsigned = Release {
+            assets: vec![release.assets[2].clone()],
+            ..release.clone()
+        };
+        assert!(select_assets(&unsigned).is_err());
+    }
+
+    #[test]
+    fn test_parse_checksum() {
+        let hex = "00".repeat(31) + "ff";
+        let mut expected = [0u8; 32];
+        expected[31] = 0xff;
+
+        assert_eq!(parse_checksum(&hex, "a.exe"), Some(expected));
+        assert_eq!(
+            parse_checksum(&format!("{} *a.exe", hex), "a.exe"),
+            Some(expected)
+        );
+        assert_eq!(parse_checksum(&format!("{}  b.exe", hex), "a.exe"), None);
+        assert_eq!(parse_checksum("not a checksum", "a.exe"), None);
+    }
+
+    #[test]
+    fn test_download_good_asset() {
+        let dir = TestDir::new("updater_good");
+        let server = TestServer::start(vec![
+            response("200 OK", &[], &checksum_file(EXE_CONTENT)),
+            response("200 OK", &[], EXE_CONTENT),
+        ]);
+
+        let path =
+            download_verified(&NetworkConfig::default(), &release(&server.url), &dir).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), EXE_CONTENT);
+
+        let requests = server.requests();
+        assert!(requests[0].starts_with("GET /checksum "));
+        assert!(requests[1].starts_with("GET /exe "));
+    }
+
+    #[test]
+    fn test_download_tampered_asset() {
+        let dir = TestDir::new("updater_tampered");
+        let server = TestServer::start(vec![
+            response("200 OK", &[], &checksum_file(EXE_CONTENT)),
+            response("200 OK", &[], b"MZ something else entirely"),
+        ]);
+
+        let error =
+            download_verified(&NetworkConfig::default(), &release(&server.url), &dir).unwrap_err();
+        assert!(error.contains("Checksum mismatch"), "{}", error);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        server.requests();
+    }
+
+    #[test]
+    fn test_install_swaps_and_keeps_path() {
+        let dir = TestDir::new("updater_install");
+        let current = dir.join("KakaoTalkAdBlock.exe");
+        let new = dir.join("download.exe");
+        std::fs::write(&current, b"old").unwrap();
+        std::fs::write(&new, b"new").unwrap();
+
+        install(&new, &current).unwrap();
+        assert_eq!(std::fs::read(&current).unwrap(), b"new");
+        assert_eq!(std::fs::read(old_exe_path(&current)).unwrap(), b"old");
+        assert!(!new.exists());
+
+        remove_previous_exe(&current);
+        assert!(!old_exe_path(&current).exists());
+
+        // A missing download rolls back to the previous executable
+        install(&new, &current).unwrap_err();
+        assert_eq!(std::fs::read(&current).unwrap(), b"new");
+    }
+
+    #[test]
+    fn test_wait_for_pid_arg() {
+        assert_eq!(wait_for_pid_arg(args(&["app.exe"])), None);
+        assert_eq!(
+            wait_for_pid_arg(args(&["app.exe", "--wait-for-pid", "1234"])),
+            Some(1234)
+        );
+        assert_eq!(wait_for_pid_arg(args(&["app.exe", "--wait-for-pid"])), None);
+    }
+}
@@ -1,66 +1,162 @@
-//! Version checking functionality
+//! Version parsing and comparison
 
-use serde::Deserialize;
+use std::cmp::Ordering;
+use std::fmt;
 
-const RELEASES_API_URL: &str =
-    "https://api.github.com/repos/ssut/kakaotalk-adblock-rs/releases/latest";
 pub const RELEASES_PAGE_URL: &str = "https://github.com/ssut/kakaotalk-adblock-rs/releases";
 
-#[derive(Deserialize)]
-struct GitHubRelease {
-    tag_name: String,
+/// A version as emitted by `build.rs` or used as a release tag
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Version {
+    /// Date-based release: `YYYYMMDD-NN`
+    Release { date: u32, build: u32 },
+    /// Legacy semver-style tag (`v1.2.3`), trailing zeros trimmed
+    Legacy(Vec<u32>),
+    /// Development build: `dev`, `dev-{sha}` or `dev-{branch}-{sha}`
+    Dev {
+        branch: Option<String>,
+        sha: Option<String>,
+    },
 }
 
-/// Check for the latest version on GitHub
-/// Returns (tag_name, has_new_release)
-pub fn check_latest_version(current_version: &str) -> (String, bool) {
-    let result = check_latest_version_inner(current_version);
-    result.unwrap_or_else(|_| (current_version.to_string(), false))
-}
+impl Version {
+    /// Parse any format `build.rs` emits, plus legacy semver tags
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        Self::parse_dev(s)
+            .or_else(|| Self::parse_release(s))
+            .or_else(|| Self::parse_legacy(s))
+    }
 
-fn check_latest_version_inner(
-    current_version: &str,
-) -> Result<(String, bool), Box<dyn std::error::Error>> {
-    let response: GitHubRelease = ureq::get(RELEASES_API_URL)
-        .set("User-Agent", "KakaoTalkAdBlock")
-        .call()?
-        .into_json()?;
+    /// Development builds have no meaningful position in the release order
+    pub fn is_dev(&self) -> bool {
+        matches!(self, Version::Dev { .. })
+    }
 
-    let tag_name = response.tag_name;
-    let has_new = has_new_release(current_version, &tag_name);
+    fn parse_dev(s: &str) -> Option<Self> {
+        if s == "dev" {
+            return Some(Version::Dev {
+                branch: None,
+                sha: None,
+            });
+        }
 
-    Ok((tag_name, has_new))
-}
+        let rest = s.strip_prefix("dev-")?;
+        let (branch, sha) = match rest.rsplit_once('-') {
+            Some((branch, sha)) => (Some(branch), sha),
+            None => (None, rest),
+        };
+        if sha.is_empty() {
+            return None;
+        }
 
-/// Compare version strings and determine if there's a new release
-fn has_new_release(current: &str, latest: &str) -> bool {
-    let parse_version = |v: &str| -> Vec<u32> {
-        v.trim_start_matches('v')
-            .split('.')
-            .filter_map(|s| s.parse().ok())
-            .collect()
-    };
+        Some(Version::Dev {
+            branch: branch.filter(|b| !b.is_empty()).map(String::from),
+            sha: Some(sha.to_string()),
+        })
+    }
+
+    fn parse_release(s: &str) -> Option<Self> {
+        let (date, build) = s.split_once('-')?;
+        if date.len() != 8
+            || !date.bytes().all(|b| b.is_ascii_digit())
+            || build.is_empty()
+            || !build.bytes().all(|b| b.is_ascii_digit())
+        {
+            return None;
+        }
+
+        let month: u32 = date[4..6].parse().ok()?;
+        let day: u32 = date[6..8].parse().ok()?;
+        if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+            return None;
+        }
 
-    let current_parts = parse_version(current);
-    let latest_parts = parse_version(latest);
+        Some(Version::Release {
+            date: date.parse().ok()?,
+            build: build.parse().ok()?,
+        })
+    }
+
+    fn parse_legacy(s: &str) -> Option<Self> {
+        let s = s.trim_start_matches(['v', 'V']);
+        // Ignore pre-release/build metadata suffixes ("1.2.3-beta", "1.2.3+abc")
+        let core = s.split(['-', '+']).next()?;
+        // A bare number is more likely a malformed date tag than a version
+        if !core.contains('.') {
+            return None;
+        }
+
+        let mut parts = core
+            .split('.')
+            .map(|p| p.parse::<u32>().ok())
+            .collect::<Option<Vec<u32>>>()?;
+        while parts.last() == Some(&0) {
+            parts.pop();
+        }
+        Some(Version::Legacy(parts))
+    }
+}
 
-    for i in 0..current_parts.len().max(latest_parts.len()) {
-        let c = current_parts.get(i).copied().unwrap_or(0);
-        let l = latest_parts.get(i).copied().unwrap_or(0);
+impl PartialOrd for Version {
+    /// Releases are ordered by (date, build) and are always newer than legacy
+    /// semver tags. Dev builds are unordered against everything else.
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (
+                Version::Release { date, build },
+                Version::Release {
+                    date: other_date,
+                    build: other_build,
+                },
+            ) => Some((date, build).cmp(&(other_date, other_build))),
+            (Version::Legacy(a), Version::Legacy(b)) => Some(a.cmp(b)),
+            (Version::Release { .. }, Version::Legacy(_)) => Some(Ordering::Greater),
+            (Version::Legacy(_), Version::Release { .. }) => Some(Ordering::Less),
+            _ if self == other => Some(Ordering::Equal),
+            _ => None,
+        }
+    }
+}
 
-        if c > l {
-            return false;
-        } else if c < l {
-            return true;
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Version::Release { date, build } => write!(f, "{:08}-{:02}", date, build),
+            Version::Legacy(parts) if parts.is_empty() => write!(f, "0"),
+            Version::Legacy(parts) => {
+                let parts: Vec<String> = parts.iter().map(u32::to_string).collect();
+                write!(f, "{}", parts.join("."))
+            }
+            Version::Dev { branch, sha } => {
+                write!(f, "dev")?;
+                if let Some(branch) = branch {
+                    write!(f, "-{}", branch)?;
+                }
+                if let Some(sha) = sha {
+                    write!(f, "-{}", sha)?;
+                }
+                Ok(())
+            }
         }
     }
+}
 
-    false
+/// Determine if `latest` is a newer release than `current`
+///
+/// Dev builds are never considered outdated, and unparseable tags are ignored.
+pub fn has_new_release(current: &str, latest: &str) -> bool {
+    match (Version::parse(current), Version::parse(latest)) {
+        (Some(current), Some(latest)) if !current.is_dev() && !latest.is_dev() => latest > current,
+        _ => false,
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::build_version;
+    use proptest::prelude::*;
 
     #[test]
     fn test_has_new_release() {
@@ -72,4 +168,116 @@ mod tests {
         assert!(!has_new_release("2.2.3", "2.1.0"));
         assert!(has_new_release("2.2.3", "v2.2.4"));
     }
+
+    #[test]
+    fn test_has_new_release_date_based() {
+        assert!(has_new_release("20250101-01", "20250101-02"));
+        assert!(has_new_release("20250101-09", "20250102-01"));
+        assert!(has_new_release("20241231-10", "20250101-01"));
+        assert!(!has_new_release("20250101-02", "20250101-02"));
+        assert!(!has_new_release("20250101-02", "20250101-01"));
+        assert!(has_new_release("v2.2.3", "20250101-01"));
+        assert!(!has_new_release("20250101-01", "v9.9.9"));
+    }
+
+    #[test]
+    fn test_dev_builds_are_never_outdated() {
+        assert!(!has_new_release("dev", "20250101-01"));
+        assert!(!has_new_release("dev-abc1234", "20250101-01"));
+        assert!(!has_new_release("dev-feature-x-abc1234", "20250101-01"));
+        assert!(!has_new_release("20250101-01", "dev-abc1234"));
+    }
+
+    #[test]
+    fn test_unparseable_versions() {
+        assert_eq!(Version::parse(""), None);
+        assert_eq!(Version::parse("latest"), None);
+        assert_eq!(Version::parse("20251301-01"), None); // month 13
+        assert_eq!(Version::parse("dev-"), None);
+        assert!(!has_new_release("20250101-01", "nightly"));
+    }
+
+    #[test]
+    fn test_parse_formats() {
+        assert_eq!(
+            Version::parse("20250101-02"),
+            Some(Version::Release {
+                date: 20250101,
+                build: 2
+            })
+        );
+        assert_eq!(Version::parse("v1.2.0"), Some(Version::Legacy(vec![1, 2])));
+        assert_eq!(
+            Version::parse("1.2.3-beta"),
+            Some(Version::Legacy(vec![1, 2, 3]))
+        );
+        assert_eq!(
+            Version::parse("dev"),
+            Some(Version::Dev {
+                branch: None,
+                sha: None
+            })
+        );
+        assert_eq!(
+            Version::parse("dev-feature-tray-icons-abc1234"),
+            Some(Version::Dev {
+                branch: Some("feature-tray-icons".to_string()),
+                sha: Some("abc1234".to_string())
+            })
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn prop_release_round_trip(
+            year in 2000u32..2100,
+            month in 1u32..=12,
+            day in 1u32..=28,
+            build in 1u32..100,
+        ) {
+            let date = format!("{:04}{:02}{:02}", year, month, day);
+            // As the release workflow formats it (`printf %02d`)
+            let tag = format!("{}-{:02}", date, build);
+            let version = Version::parse(&tag).unwrap();
+
+            prop_assert_eq!(&version, &Version::Release { date: date.parse().unwrap(), build });
+            prop_assert_eq!(version.to_string(), tag);
+        }
+
+        #[test]
+        fn prop_release_order_matches_date_then_build(
+            a in (20000101u32..21001231, 1u32..100),
+            b in (20000101u32..21001231, 1u32..100),
+        ) {
+            let va = Version::Release { date: a.0, build: a.1 };
+            let vb = Version::Release { date: b.0, build: b.1 };
+            prop_assert_eq!(va.partial_cmp(&vb), Some(a.cmp(&b)));
+        }
+
+        #[test]
+        fn prop_release_newer_than_legacy(
+            date in 20000101u32..21001231,
+            build in 1u32..100,
+            legacy in proptest::collection::vec(0u32..100, 1..4),
+        ) {
+            let release = Version::Release { date, build };
+            let legacy = Version::Legacy(legacy);
+            prop_assert!(release > legacy);
+        }
+
+        #[test]
+        fn prop_dev_versions_parse_as_dev(
+            branch in proptest::option::of("[A-Za-z0-9/_.-]{1,24}"),
+            sha in "[0-9a-f]{7}",
+        ) {
+            let tag = build_version::dev_version(branch.as_deref(), &sha);
+            let version = Version::parse(&tag).unwrap();
+
+            prop_assert!(version.is_dev());
+            if let Version::Dev { sha: parsed_sha, .. } = &version {
+                prop_assert_eq!(parsed_sha.as_deref(), Some(sha.as_str()));
+            }
+            prop_assert!(!has_new_release(&tag, "20991231-99"));
+        }
+    }
 }
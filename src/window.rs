@@ -1,21 +1,24 @@
 //! Window enumeration and manipulation utilities
 
+#[cfg(windows)]
 use windows::{
     Win32::Foundation::{BOOL, HWND, LPARAM, RECT, WPARAM},
     Win32::Graphics::Gdi::{InvalidateRect, UpdateWindow},
     Win32::UI::WindowsAndMessaging::{
-        EnumChildWindows, EnumWindows, GetClassNameW, GetParent, GetWindowRect, GetWindowTextW,
-        GetWindowThreadProcessId, IsWindow, IsWindowVisible, SendMessageW, SetWindowPos,
-        ShowWindow, HWND_TOP, SWP_NOMOVE, SW_HIDE, WM_CLOSE,
+        EnumChildWindows, EnumWindows, GetClassNameW, GetParent, GetWindow, GetWindowRect,
+        GetWindowTextW, GetWindowThreadProcessId, IsWindow, IsWindowVisible, SendMessageW,
+        SetWindowPos, ShowWindow, GW_CHILD, GW_HWNDNEXT, HWND_TOP, SWP_NOMOVE, SW_HIDE, WM_CLOSE,
     },
 };
 
 /// Check if a window handle is still valid
+#[cfg(windows)]
 pub fn is_window_valid(hwnd: HWND) -> bool {
     unsafe { IsWindow(hwnd).as_bool() }
 }
 
 /// Check if a window is visible
+#[cfg(windows)]
 pub fn is_window_visible(hwnd: HWND) -> bool {
     unsafe { IsWindowVisible(hwnd).as_bool() }
 }
@@ -35,6 +38,7 @@ pub mod window_texts {
 }
 
 /// Get the class name of a window
+#[cfg(windows)]
 pub fn get_class_name(hwnd: HWND) -> String {
     unsafe {
         let mut buffer = [0u16; 256];
@@ -48,6 +52,7 @@ pub fn get_class_name(hwnd: HWND) -> String {
 }
 
 /// Get the window text (title) of a window
+#[cfg(windows)]
 pub fn get_window_text(hwnd: HWND) -> String {
     unsafe {
         let mut buffer = [0u16; 256];
@@ -61,11 +66,13 @@ pub fn get_window_text(hwnd: HWND) -> String {
 }
 
 /// Get the parent window handle
+#[cfg(windows)]
 pub fn get_parent(hwnd: HWND) -> HWND {
-    unsafe { GetParent(hwnd).unwrap_or(HWND::default()) }
+    unsafe { GetParent(hwnd).unwrap_or_default() }
 }
 
 /// Get the process ID that owns the window
+#[cfg(windows)]
 pub fn get_window_process_id(hwnd: HWND) -> u32 {
     unsafe {
         let mut pid: u32 = 0;
@@ -75,6 +82,7 @@ pub fn get_window_process_id(hwnd: HWND) -> u32 {
 }
 
 /// Get window rectangle
+#[cfg(windows)]
 pub fn get_window_rect(hwnd: HWND) -> Option<RECT> {
     unsafe {
         let mut rect = RECT::default();
@@ -87,6 +95,7 @@ pub fn get_window_rect(hwnd: HWND) -> Option<RECT> {
 }
 
 /// Enumerate all top-level windows
+#[cfg(windows)]
 pub fn enum_windows<F>(mut callback: F)
 where
     F: FnMut(HWND) -> bool,
@@ -108,6 +117,7 @@ where
 }
 
 /// Enumerate child windows of a parent window
+#[cfg(windows)]
 pub fn enum_child_windows<F>(parent: HWND, mut callback: F)
 where
     F: FnMut(HWND) -> bool,
@@ -130,6 +140,7 @@ where
 }
 
 /// Get all child window handles
+#[cfg(windows)]
 pub fn get_child_windows(parent: HWND) -> Vec<HWND> {
     let mut children = Vec::new();
     enum_child_windows(parent, |hwnd| {
@@ -139,7 +150,22 @@ pub fn get_child_windows(parent: HWND) -> Vec<HWND> {
     children
 }
 
+/// Get direct child window handles only (in Z order)
+#[cfg(windows)]
+pub fn get_direct_children(parent: HWND) -> Vec<HWND> {
+    let mut children = Vec::new();
+    unsafe {
+        let mut child = GetWindow(parent, GW_CHILD).unwrap_or_default();
+        while !child.0.is_null() {
+            children.push(child);
+            child = GetWindow(child, GW_HWNDNEXT).unwrap_or_default();
+        }
+    }
+    children
+}
+
 /// Send WM_CLOSE message to a window
+#[cfg(windows)]
 pub fn close_window(hwnd: HWND) {
     unsafe {
         SendMessageW(hwnd, WM_CLOSE, WPARAM(0), LPARAM(0));
@@ -147,6 +173,7 @@ pub fn close_window(hwnd: HWND) {
 }
 
 /// Hide a window
+#[cfg(windows)]
 pub fn hide_window(hwnd: HWND) {
     unsafe {
         let _ = ShowWindow(hwnd, SW_HIDE);
@@ -154,6 +181,7 @@ pub fn hide_window(hwnd: HWND) {
 }
 
 /// Update window
+#[cfg(windows)]
 pub fn update_window(hwnd: HWND) {
     unsafe {
         let _ = UpdateWindow(hwnd);
@@ -161,6 +189,7 @@ pub fn update_window(hwnd: HWND) {
 }
 
 /// Force window to redraw (invalidate + update)
+#[cfg(windows)]
 pub fn refresh_window(hwnd: HWND) {
     unsafe {
         // Invalidate entire client area, erase background
@@ -171,6 +200,7 @@ pub fn refresh_window(hwnd: HWND) {
 }
 
 /// Set window position and size
+#[cfg(windows)]
 pub fn set_window_pos(hwnd: HWND, x: i32, y: i32, width: i32, height: i32, flags: u32) {
     unsafe {
         let _ = SetWindowPos(
@@ -186,11 +216,13 @@ pub fn set_window_pos(hwnd: HWND, x: i32, y: i32, width: i32, height: i32, flags
 }
 
 /// Set window size only (keeps position)
+#[cfg(windows)]
 pub fn set_window_size(hwnd: HWND, width: i32, height: i32) {
     set_window_pos(hwnd, 0, 0, width, height, SWP_NOMOVE.0);
 }
 
 /// Find all windows belonging to a specific process
+#[cfg(windows)]
 pub fn find_windows_by_pid(target_pid: u32) -> Vec<HWND> {
     let mut windows = Vec::new();
 
@@ -206,6 +238,7 @@ pub fn find_windows_by_pid(target_pid: u32) -> Vec<HWND> {
 }
 
 /// Check if a window or any of its children has a class name starting with the given prefix
+#[cfg(windows)]
 pub fn has_child_class_starting_with(hwnd: HWND, prefix: &str) -> bool {
     let class_name = get_class_name(hwnd);
     if class_name.starts_with(prefix) {
@@ -223,6 +256,7 @@ pub fn has_child_class_starting_with(hwnd: HWND, prefix: &str) -> bool {
 }
 
 /// Check if a window or any of its children has the "Chrome Legacy Window" text
+#[cfg(windows)]
 pub fn has_chrome_legacy_window(hwnd: HWND) -> bool {
     let text = get_window_text(hwnd);
     if text == window_texts::CHROME_LEGACY {
@@ -0,0 +1,622 @@
+//! Window tree snapshot and inspector model
+//!
+//! Captures the full KakaoTalk window hierarchy and keeps the navigation state
+//! (expanded nodes, selection, text filter) for the debug window's tree view.
+//! Everything except `capture` is plain data so it can be tested anywhere.
+
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::rules::Rule;
+
+/// Window rectangle in screen coordinates
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeRect {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+impl NodeRect {
+    pub fn width(&self) -> i32 {
+        self.right - self.left
+    }
+
+    pub fn height(&self) -> i32 {
+        self.bottom - self.top
+    }
+}
+
+/// A single window in the snapshot
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WindowNode {
+    pub hwnd: isize,
+    pub class_name: String,
+    pub text: String,
+    pub rect: NodeRect,
+    pub visible: bool,
+    /// Rule that classified this window, if any
+    pub rule: Option<Rule>,
+    pub children: Vec<WindowNode>,
+}
+
+impl WindowNode {
+    /// One-line label shown in the tree view
+    pub fn label(&self) -> String {
+        let mut label = self.class_name.clone();
+        if !self.text.is_empty() {
+            label.push_str(&format!(" \"{}\"", self.text));
+        }
+        if let Some(rule) = self.rule {
+            label.push_str(&format!(" [{}]", rule.name()));
+        }
+        if !self.visible {
+            label.push_str(" (hidden)");
+        }
+        label
+    }
+
+    /// Detail lines shown for the selected node
+    pub fn details(&self) -> Vec<String> {
+        vec![
+            format!("HWND:  0x{:08X}", self.hwnd),
+            format!("Class: {}", self.class_name),
+            format!("Text:  {}", self.text),
+            format!(
+                "Rect:  ({}, {}) {}x{}",
+                self.rect.left,
+                self.rect.top,
+                self.rect.width(),
+                self.rect.height()
+            ),
+            format!("Visible: {}", if self.visible { "yes" } else { "no" }),
+            format!("Rule:  {}", self.rule.map(Rule::name).unwrap_or("-")),
+        ]
+    }
+
+    /// Plain-text dump of this node and all descendants (for bug reports)
+    pub fn format_subtree(&self) -> String {
+        let mut out = String::new();
+        self.write_subtree(&mut out, 0);
+        out
+    }
+
+    fn write_subtree(&self, out: &mut String, depth: usize) {
+        out.push_str(&format!(
+            "{}0x{:08X} {} rect=({}, {}, {}, {}) visible={} rule={}\n",
+            "  ".repeat(depth),
+            self.hwnd,
+            self.label_without_flags(),
+            self.rect.left,
+            self.rect.top,
+            self.rect.right,
+            self.rect.bottom,
+            self.visible,
+            self.rule.map(Rule::name).unwrap_or("-"),
+        ));
+        for child in &self.children {
+            child.write_subtree(out, depth + 1);
+        }
+    }
+
+    fn label_without_flags(&self) -> String {
+        if self.text.is_empty() {
+            self.class_name.clone()
+        } else {
+            format!("{} \"{}\"", self.class_name, self.text)
+        }
+    }
+
+    /// Case-insensitive match against class, text, handle and rule name
+    fn matches(&self, filter_lower: &str) -> bool {
+        self.class_name.to_lowercase().contains(filter_lower)
+            || self.text.to_lowercase().contains(filter_lower)
+            || format!("0x{:08x}", self.hwnd).contains(filter_lower)
+            || self
+                .rule
+                .map(|r| r.name().contains(filter_lower))
+                .unwrap_or(false)
+    }
+
+    fn subtree_matches(&self, filter_lower: &str) -> bool {
+        self.matches(filter_lower)
+            || self
+                .children
+                .iter()
+                .any(|child| child.subtree_matches(filter_lower))
+    }
+}
+
+/// A visible row of the flattened tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Row {
+    pub depth: usize,
+    /// Child indices from the root list down to this node
+    pub path: Vec<usize>,
+    pub hwnd: isize,
+    pub has_children: bool,
+    pub expanded: bool,
+}
+
+/// Navigation keys understood by the inspector
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NavKey {
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    /// Expand, or move to the first child when already expanded
+    Right,
+    /// Collapse, or move to the parent when already collapsed
+    Left,
+    /// Toggle expansion of the selected node
+    Toggle,
+    /// Remove the last filter character
+    Backspace,
+    /// Clear the filter
+    Escape,
+    /// Append a character to the filter
+    Char(char),
+}
+
+const PAGE_SIZE: i32 = 10;
+
+/// Interactive state of the tree view
+#[derive(Debug, Default)]
+pub struct TreeInspector {
+    roots: Vec<WindowNode>,
+    expanded: HashSet<isize>,
+    selected: Option<isize>,
+    filter: String,
+}
+
+impl TreeInspector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the snapshot, keeping expansion and selection by window handle
+    pub fn set_roots(&mut self, roots: Vec<WindowNode>) {
+        self.roots = roots;
+        let rows = self.rows();
+        let still_visible = self
+            .selected
+            .map(|hwnd| rows.iter().any(|r| r.hwnd == hwnd))
+            .unwrap_or(false);
+        if !still_visible {
+            self.selected = rows.first().map(|r| r.hwnd);
+        }
+    }
+
+    pub fn filter(&self) -> &str {
+        &self.filter
+    }
+
+    /// Flatten the tree into the rows currently shown
+    pub fn rows(&self) -> Vec<Row> {
+        let filter_lower = self.filter.to_lowercase();
+        let mut rows = Vec::new();
+        let mut path = Vec::new();
+        for (i, node) in self.roots.iter().enumerate() {
+            path.push(i);
+            self.flatten(node, 0, &mut path, &filter_lower, &mut rows);
+            path.pop();
+        }
+        rows
+    }
+
+    fn flatten(
+        &self,
+        node: &WindowNode,
+        depth: usize,
+        path: &mut Vec<usize>,
+        filter_lower: &str,
+        rows: &mut Vec<Row>,
+    ) {
+        if !filter_lower.is_empty() && !node.subtree_matches(filter_lower) {
+            return;
+        }
+
+        // While filtering, ancestors of matches are always expanded
+        let child_matches = !filter_lower.is_empty()
+            && node
+                .children
+                .iter()
+                .any(|child| child.subtree_matches(filter_lower));
+        let expanded = self.expanded.contains(&node.hwnd) || child_matches;
+
+        rows.push(Row {
+            depth,
+            path: path.clone(),
+            hwnd: node.hwnd,
+            has_children: !node.children.is_empty(),
+            expanded,
+        });
+
+        if expanded {
+            for (i, child) in node.children.iter().enumerate() {
+                path.push(i);
+                self.flatten(child, depth + 1, path, filter_lower, rows);
+                path.pop();
+            }
+        }
+    }
+
+    /// Look up the node a row refers to
+    pub fn node(&self, row: &Row) -> Option<&WindowNode> {
+        let (first, rest) = row.path.split_first()?;
+        let mut node = self.roots.get(*first)?;
+        for &i in rest {
+            node = node.children.get(i)?;
+        }
+        Some(node)
+    }
+
+    /// Index of the selected row within `rows`
+    pub fn selected_index(&self, rows: &[Row]) -> Option<usize> {
+        let hwnd = self.selected?;
+        rows.iter().position(|r| r.hwnd == hwnd)
+    }
+
+    pub fn selected_node(&self) -> Option<&WindowNode> {
+        let rows = self.rows();
+        let index = self.selected_index(&rows)?;
+        self.node(&rows[index])
+    }
+
+    /// Select the row at the given index (e.g. from a mouse click)
+    pub fn select_row(&mut self, index: usize) {
+        if let Some(row) = self.rows().get(index) {
+            self.selected = Some(row.hwnd);
+        }
+    }
+
+    /// Text for the "copy subtree" action
+    pub fn copy_text(&self) -> Option<String> {
+        self.selected_node().map(WindowNode::format_subtree)
+    }
+
+    /// Apply a navigation key, returning true if anything changed
+    pub fn handle_key(&mut self, key: NavKey) -> bool {
+        let rows = self.rows();
+        let current = self.selected_index(&rows);
+
+        match key {
+            NavKey::Up => self.move_selection(&rows, current, -1),
+            NavKey::Down => self.move_selection(&rows, current, 1),
+            NavKey::PageUp => self.move_selection(&rows, current, -PAGE_SIZE),
+            NavKey::PageDown => self.move_selection(&rows, current, PAGE_SIZE),
+            NavKey::Home => self.select_at(&rows, 0),
+            NavKey::End => self.select_at(&rows, rows.len().saturating_sub(1)),
+            NavKey::Right => {
+                let Some(row) = current.map(|i| &rows[i]) else {
+                    return false;
+                };
+                if !row.has_children {
+                    false
+                } else if !row.expanded {
+                    self.expanded.insert(row.hwnd)
+                } else {
+                    self.select_at(&rows, current.unwrap() + 1)
+                }
+            }
+            NavKey::Left => {
+                let Some(index) = current else {
+                    return false;
+                };
+                let row = &rows[index];
+                if row.expanded && self.expanded.contains(&row.hwnd) {
+                    self.expanded.remove(&row.hwnd)
+                } else if row.depth > 0 {
+                    // Parent is the nearest preceding row one level up
+                    let parent = rows[..index].iter().rposition(|r| r.depth + 1 == row.depth);
+                    match parent {
+                        Some(p) => self.select_at(&rows, p),
+                        None => false,
+                    }
+                } else {
+                    false
+                }
+            }
+            NavKey::Toggle => {
+                let Some(row) = current.map(|i| &rows[i]) else {
+                    return false;
+                };
+                if !row.has_children {
+                    false
+                } else if self.expanded.contains(&row.hwnd) {
+                    self.expanded.remove(&row.hwnd)
+                } else {
+                    self.expanded.insert(row.hwnd)
+                }
+            }
+            NavKey::Backspace => {
+                let changed = self.filter.pop().is_some();
+                self.refresh_selection();
+                changed
+            }
+            NavKey::Escape => {
+                let changed = !self.filter.is_empty();
+                self.filter.clear();
+                self.refresh_selection();
+                changed
+            }
+            NavKey::Char(c) => {
+                if c.is_control() {
+                    return false;
+                }
+                self.filter.push(c);
+                self.refresh_selection();
+                true
+            }
+        }
+    }
+
+    fn move_selection(&mut self, rows: &[Row], current: Option<usize>, delta: i32) -> bool {
+        if rows.is_empty() {
+            return false;
+        }
+        let target = match current {
+            Some(i) => (i as i32 + delta).clamp(0, rows.len() as i32 - 1) as usize,
+            None => 0,
+        };
+        self.select_at(rows, target)
+    }
+
+    fn select_at(&mut self, rows: &[Row], index: usize) -> bool {
+        match rows.get(index) {
+            Some(row) if self.selected != Some(row.hwnd) => {
+                self.selected = Some(row.hwnd);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Keep the selection on a visible row after the filter changed
+    fn refresh_selection(&mut self) {
+        let rows = self.rows();
+        if self.selected_index(&rows).is_none() {
+            self.selected = rows.first().map(|r| r.hwnd);
+        }
+    }
+}
+
+/// Capture the window hierarchy of the given processes, each classified by
+/// the rules of its profile
+#[cfg(windows)]
+pub fn capture(targets: &[(u32, crate::rules::RuleSet)]) -> Vec<WindowNode> {
+    use crate::window;
+
+    let top_level: Vec<_> = targets
+        .iter()
+        .flat_map(|(pid, rules)| {
+            window::find_windows_by_pid(*pid)
+                .into_iter()
+                .map(move |hwnd| (hwnd, rules))
+        })
+        .collect();
+
+    // Find main windows first so embedded ads are classified regardless of Z order
+    let main_windows: HashSet<isize> = top_level
+        .iter()
+        .filter(|(hwnd, rules)| {
+            let parent = window::get_parent(*hwnd);
+            rules.classify_top_level(
+                &window::get_class_name(*hwnd),
+                &window::get_window_text(*hwnd),
+                !parent.0.is_null(),
+                false,
+            ) == Some(Rule::MainWindow)
+        })
+        .map(|(hwnd, _)| hwnd.0 as isize)
+        .collect();
+
+    top_level
+        .into_iter()
+        .map(|(hwnd, rules)| {
+            let parent = window::get_parent(hwnd);
+            let rule = rules.classify_top_level(
+                &window::get_class_name(hwnd),
+                &window::get_window_text(hwnd),
+                !parent.0.is_null(),
+                main_windows.contains(&(parent.0 as isize)),
+            );
+            capture_node(hwnd, rule, rules)
+        })
+        .collect()
+}
+
+#[cfg(windows)]
+fn capture_node(
+    hwnd: windows::Win32::Foundation::HWND,
+    rule: Option<Rule>,
+    rules: &crate::rules::RuleSet,
+) -> WindowNode {
+    use crate::window;
+
+    let text = window::get_window_text(hwnd);
+    let is_main = rule == Some(Rule::MainWindow);
+
+    let children = window::get_direct_children(hwnd)
+        .into_iter()
+        .enumerate()
+        .map(|(i, child)| {
+            // The first child of a main window is its content view and is never touched
+            let child_rule = if is_main && i > 0 {
+                rules.classify_main_child(
+                    &window::get_class_name(child),
+                    &window::get_window_text(child),
+                    &text,
+                )
+            } else {
+                None
+            };
+            capture_node(child, child_rule, rules)
+        })
+        .collect();
+
+    let rect = window::get_window_rect(hwnd)
+        .map(|r| NodeRect {
+            left: r.left,
+            top: r.top,
+            right: r.right,
+            bottom: r.bottom,
+        })
+        .unwrap_or_default();
+
+    WindowNode {
+        hwnd: hwnd.0 as isize,
+        class_name: window::get_class_name(hwnd),
+        text,
+        rect,
+        visible: window::is_window_visible(hwnd),
+        rule,
+        children,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(hwnd: isize, class_name: &str, text: &str, children: Vec<WindowNode>) -> WindowNode {
+        WindowNode {
+            hwnd,
+            class_name: class_name.to_string(),
+            text: text.to_string(),
+            rect: NodeRect {
+                left: 0,
+                top: 0,
+                right: 100,
+                bottom: 50,
+            },
+            visible: true,
+            rule: None,
+            children,
+        }
+    }
+
+    fn sample() -> TreeInspector {
+        let mut main = node(
+            1,
+            "EVA_Window_Dblclk",
+            "카카오톡",
+            vec![
+                node(2, "EVA_ChildWindow", "OnlineMainView_0x1", vec![]),
+                node(
+                    3,
+                    "EVA_ChildWindow",
+                    "",
+                    vec![node(
+                        4,
+                        "Chrome_WidgetWin_0",
+                        "Chrome Legacy Window",
+                        vec![],
+                    )],
+                ),
+            ],
+        );
+        main.rule = Some(Rule::MainWindow);
+        main.children[1].rule = Some(Rule::BannerChild);
+
+        let mut inspector = TreeInspector::new();
+        inspector.set_roots(vec![main, node(5, "EVA_Window", "", vec![])]);
+        inspector
+    }
+
+    fn visible_hwnds(inspector: &TreeInspector) -> Vec<isize> {
+        inspector.rows().iter().map(|r| r.hwnd).collect()
+    }
+
+    #[test]
+    fn test_collapsed_by_default() {
+        let inspector = sample();
+        assert_eq!(visible_hwnds(&inspector), vec![1, 5]);
+        assert_eq!(inspector.selected_node().unwrap().hwnd, 1);
+    }
+
+    #[test]
+    fn test_expand_collapse_navigation() {
+        let mut inspector = sample();
+
+        assert!(inspector.handle_key(NavKey::Right)); // expand 1
+        assert_eq!(visible_hwnds(&inspector), vec![1, 2, 3, 5]);
+        assert!(inspector.handle_key(NavKey::Right)); // move to first child
+        assert_eq!(inspector.selected_node().unwrap().hwnd, 2);
+        assert!(!inspector.handle_key(NavKey::Right)); // leaf
+
+        inspector.handle_key(NavKey::Down);
+        inspector.handle_key(NavKey::Toggle); // expand 3
+        assert_eq!(visible_hwnds(&inspector), vec![1, 2, 3, 4, 5]);
+
+        inspector.handle_key(NavKey::Down);
+        assert!(inspector.handle_key(NavKey::Left)); // leaf -> parent
+        assert_eq!(inspector.selected_node().unwrap().hwnd, 3);
+        assert!(inspector.handle_key(NavKey::Left)); // collapse 3
+        assert_eq!(visible_hwnds(&inspector), vec![1, 2, 3, 5]);
+
+        inspector.handle_key(NavKey::End);
+        assert_eq!(inspector.selected_node().unwrap().hwnd, 5);
+        assert!(!inspector.handle_key(NavKey::Down));
+        inspector.handle_key(NavKey::Home);
+        assert_eq!(inspector.selected_node().unwrap().hwnd, 1);
+    }
+
+    #[test]
+    fn test_filter_expands_ancestors() {
+        let mut inspector = sample();
+        for c in "legacy".chars() {
+            inspector.handle_key(NavKey::Char(c));
+        }
+        assert_eq!(inspector.filter(), "legacy");
+        assert_eq!(visible_hwnds(&inspector), vec![1, 3, 4]);
+
+        inspector.handle_key(NavKey::Escape);
+        assert_eq!(visible_hwnds(&inspector), vec![1, 5]);
+    }
+
+    #[test]
+    fn test_filter_matches_rule_and_handle() {
+        let mut inspector = sample();
+        for c in "banner".chars() {
+            inspector.handle_key(NavKey::Char(c));
+        }
+        assert_eq!(visible_hwnds(&inspector), vec![1, 3]);
+
+        inspector.handle_key(NavKey::Escape);
+        for c in "0x00000005".chars() {
+            inspector.handle_key(NavKey::Char(c));
+        }
+        assert_eq!(visible_hwnds(&inspector), vec![5]);
+        assert_eq!(inspector.selected_node().unwrap().hwnd, 5);
+    }
+
+    #[test]
+    fn test_selection_survives_refresh() {
+        let mut inspector = sample();
+        inspector.handle_key(NavKey::Right);
+        inspector.select_row(2);
+        let roots = inspector.roots.clone();
+        inspector.set_roots(roots);
+        assert_eq!(inspector.selected_node().unwrap().hwnd, 3);
+        assert_eq!(visible_hwnds(&inspector), vec![1, 2, 3, 5]);
+    }
+
+    #[test]
+    fn test_copy_subtree() {
+        let mut inspector = sample();
+        inspector.handle_key(NavKey::Right);
+        inspector.select_row(2);
+        let text = inspector.copy_text().unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("0x00000003 EVA_ChildWindow"));
+        assert!(lines[0].contains("rule=banner-child"));
+        assert!(lines[1].starts_with("  0x00000004 Chrome_WidgetWin_0 \"Chrome Legacy Window\""));
+    }
+}